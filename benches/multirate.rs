@@ -0,0 +1,108 @@
+//! Compares the time it takes to simulate a building that mixes lightweight
+//! (i.e., plywood and insulation) and heavyweight (i.e., concrete) constructions
+//! with and without [`ThermalModelOptions::multirate`].
+//!
+//! ```bash
+//! cargo bench --bench multirate
+//! ```
+
+use calendar::Date;
+use communication_protocols::{MetaOptions, SimulationModel};
+use heat::model::{ThermalModel, ThermalModelOptions};
+use heat::Float;
+use schedule::ScheduleConstant;
+use simple_model::SimpleModel;
+use std::time::{Duration, Instant};
+use weather::SyntheticWeather;
+
+const META_OPTIONS: MetaOptions = MetaOptions {
+    latitude: 0.,
+    longitude: 0.,
+    standard_meridian: 0.,
+    elevation: 0.0,
+};
+
+/// The number of main timesteps per hour
+const N: usize = 4;
+
+/// The number of simulated days
+const DAYS: usize = 30;
+
+/// The number of times each simulation is repeated, keeping the fastest
+const REPETITIONS: usize = 3;
+
+/// Simulates the building, returning the time it took, the number of
+/// surface steps (i.e., surfaces times the steps each of them took) and the
+/// final temperature of the zone
+fn simulate(multirate: bool) -> (Duration, usize, Float) {
+    let (simple_model, mut state_header) =
+        SimpleModel::from_file("./benches/mixed_building.spl".to_string()).unwrap();
+    let options = ThermalModelOptions {
+        multirate,
+        ..ThermalModelOptions::default()
+    };
+    let thermal_model =
+        ThermalModel::new(&META_OPTIONS, options, &simple_model, &mut state_header, N).unwrap();
+    let mut state = state_header.take_values().unwrap();
+    let mut memory = thermal_model.allocate_memory().unwrap();
+
+    let mut weather = SyntheticWeather::default();
+    weather.dry_bulb_temperature = Box::new(ScheduleConstant::new(5.));
+    weather.wind_direction = Box::new(ScheduleConstant::new(0.0));
+    weather.wind_speed = Box::new(ScheduleConstant::new(2.0));
+
+    let mut date = Date {
+        month: 1,
+        day: 1,
+        hour: 0.0,
+    };
+    let start = Instant::now();
+    for _ in 0..DAYS * 24 * N {
+        thermal_model
+            .march(date, &weather, &simple_model, &mut state, &mut memory)
+            .unwrap();
+        date.add_seconds(3600. / N as Float);
+    }
+    let elapsed = start.elapsed();
+
+    let subdivisions = thermal_model.dt_subdivisions();
+    let surface_steps = thermal_model
+        .surfaces
+        .iter()
+        .map(|s| subdivisions / s.substep_ratio)
+        .sum::<usize>()
+        * DAYS
+        * 24
+        * N;
+    let t = simple_model.spaces[0].dry_bulb_temperature(&state).unwrap();
+    (elapsed, surface_steps, t)
+}
+
+fn main() {
+    let mut results = Vec::with_capacity(2);
+    for multirate in [false, true] {
+        let mut best = simulate(multirate);
+        for _ in 1..REPETITIONS {
+            let run = simulate(multirate);
+            if run.0 < best.0 {
+                best = run;
+            }
+        }
+        let (elapsed, surface_steps, t) = best;
+        println!(
+            "{:<10} | {:>10.1} ms | {:>10} surface steps | final zone temperature {:.3} C",
+            if multirate { "multirate" } else { "uniform" },
+            elapsed.as_secs_f64() * 1e3,
+            surface_steps,
+            t
+        );
+        results.push(best);
+    }
+    let (uniform, multirate) = (&results[0], &results[1]);
+    println!(
+        "multirate took {:.0}% of the time of uniform sub-stepping ({:.0}% of the surface steps), with a difference of {:.3} C in the zone temperature",
+        100. * multirate.0.as_secs_f64() / uniform.0.as_secs_f64(),
+        100. * multirate.1 as Float / uniform.1 as Float,
+        (multirate.2 - uniform.2).abs()
+    );
+}
@@ -0,0 +1,276 @@
+/*
+MIT License
+Copyright (c) 2021 Germán Molina
+Permission is hereby granted, free of charge, to any person obtaining a copy
+of this software and associated documentation files (the "Software"), to deal
+in the Software without restriction, including without limitation the rights
+to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+copies of the Software, and to permit persons to whom the Software is
+furnished to do so, subject to the following conditions:
+The above copyright notice and this permission notice shall be included in all
+copies or substantial portions of the Software.
+THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+SOFTWARE.
+*/
+
+use crate::Float;
+use calendar::Date;
+
+/// The energy balance of a surface or fenestration during the last
+/// sub-timestep. All terms are in $`W`$, and are positive when they
+/// deliver heat into the surface.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct SurfaceEnergyBalance {
+    /// Solar radiation absorbed by all the nodes of the surface
+    pub solar: Float,
+
+    /// Net infrared radiation exchanged at the front of the surface
+    pub front_infrared: Float,
+
+    /// Net infrared radiation exchanged at the back of the surface
+    pub back_infrared: Float,
+
+    /// Convection at the front of the surface
+    pub front_convection: Float,
+
+    /// Convection at the back of the surface
+    pub back_convection: Float,
+
+    /// Heat conducted from the front node towards the back of the surface.
+    /// This is an internal flow, so it is not part of the balance.
+    pub front_conduction: Float,
+
+    /// Heat conducted from the back node towards the front of the surface.
+    /// This is an internal flow, so it is not part of the balance.
+    pub back_conduction: Float,
+
+    /// Change in the energy stored in the nodes of the surface, per second
+    pub storage: Float,
+}
+
+impl SurfaceEnergyBalance {
+    /// The difference between the heat delivered into the surface and
+    /// the change of its stored energy, in $`W`$
+    pub fn residual(&self) -> Float {
+        self.solar
+            + self.front_infrared
+            + self.back_infrared
+            + self.front_convection
+            + self.back_convection
+            - self.storage
+    }
+}
+
+/// The energy balance of the air of a zone during the last sub-timestep,
+/// following the terms in [`crate::model::ThermalModel`]'s zone heat balance.
+/// All terms are in $`W`$, and are positive when they deliver heat into the zone.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct ZoneEnergyBalance {
+    /// Convection from surfaces and fenestrations, plus the heat delivered
+    /// by their shading devices and ventilated cavities
+    pub surfaces: Float,
+
+    /// Infiltration
+    pub infiltration: Float,
+
+    /// Ventilation
+    pub ventilation: Float,
+
+    /// Luminaires
+    pub luminaires: Float,
+
+    /// Heating and cooling, including ideal loads
+    pub hvac: Float,
+
+    /// Change in the energy stored in the air (and contents) of the zone, per second
+    pub storage: Float,
+}
+
+impl ZoneEnergyBalance {
+    /// The difference between the heat delivered into the zone and
+    /// the change of its stored energy, in $`W`$
+    pub fn residual(&self) -> Float {
+        self.surfaces + self.infiltration + self.ventilation + self.luminaires + self.hvac
+            - self.storage
+    }
+}
+
+/// An element of the model whose energy balance is audited
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AuditedElement {
+    /// A zone, by index
+    Zone(usize),
+
+    /// A surface, by index
+    Surface(usize),
+
+    /// A fenestration, by index
+    Fenestration(usize),
+}
+
+/// An energy balance whose residual exceeded the tolerance of the [`EnergyAudit`]
+#[derive(Debug, Clone, Copy)]
+pub struct Imbalance {
+    /// The end of the sub-timestep in which it happened
+    pub date: Date,
+
+    /// The element that was not balanced
+    pub element: AuditedElement,
+
+    /// The residual of the balance, in $`W`$
+    pub residual: Float,
+}
+
+/// The energy balances of all the zones, surfaces and fenestrations
+/// in a [`crate::model::ThermalModel`], calculated during `march` when
+/// enabled through [`crate::model::ThermalModel::enable_audit`].
+///
+/// The balances of surfaces are evaluated at the end of each sub-timestep,
+/// while their temperatures are marched using the conditions at its
+/// beginning, so small residuals are expected. Zones' balances, on the
+/// contrary, should close up to rounding errors.
+#[derive(Debug, Clone)]
+pub struct EnergyAudit {
+    /// The absolute residual (in $`W`$) beyond which an [`Imbalance`] is registered
+    pub tolerance: Float,
+
+    /// The balance of each zone during the last sub-timestep
+    pub zones: Vec<ZoneEnergyBalance>,
+
+    /// The balance of each surface during the last sub-timestep
+    pub surfaces: Vec<SurfaceEnergyBalance>,
+
+    /// The balance of each fenestration during the last sub-timestep
+    pub fenestrations: Vec<SurfaceEnergyBalance>,
+
+    /// All the imbalances found since the audit was enabled
+    pub imbalances: Vec<Imbalance>,
+
+    /// The largest absolute residual found since the audit was enabled, in $`W`$
+    pub max_residual: Float,
+}
+
+impl EnergyAudit {
+    /// Creates a new audit for a model with a certain number of zones,
+    /// surfaces and fenestrations
+    pub fn new(
+        tolerance: Float,
+        n_zones: usize,
+        n_surfaces: usize,
+        n_fenestrations: usize,
+    ) -> Self {
+        Self {
+            tolerance,
+            zones: vec![ZoneEnergyBalance::default(); n_zones],
+            surfaces: vec![SurfaceEnergyBalance::default(); n_surfaces],
+            fenestrations: vec![SurfaceEnergyBalance::default(); n_fenestrations],
+            imbalances: Vec::new(),
+            max_residual: 0.0,
+        }
+    }
+
+    /// Registers the residuals of all the balances of the last sub-timestep,
+    /// which ended at `date`
+    pub(crate) fn check(&mut self, date: Date) {
+        let residuals = self
+            .zones
+            .iter()
+            .enumerate()
+            .map(|(i, b)| (AuditedElement::Zone(i), b.residual()))
+            .chain(
+                self.surfaces
+                    .iter()
+                    .enumerate()
+                    .map(|(i, b)| (AuditedElement::Surface(i), b.residual())),
+            )
+            .chain(
+                self.fenestrations
+                    .iter()
+                    .enumerate()
+                    .map(|(i, b)| (AuditedElement::Fenestration(i), b.residual())),
+            );
+        for (element, residual) in residuals {
+            let abs = residual.abs();
+            if abs > self.max_residual {
+                self.max_residual = abs;
+            }
+            if abs > self.tolerance {
+                self.imbalances.push(Imbalance {
+                    date,
+                    element,
+                    residual,
+                });
+            }
+        }
+    }
+
+    /// Gets the number of imbalances and the largest residual registered so far
+    /// (see [`EnergyAudit::rollback`])
+    pub(crate) fn mark(&self) -> (usize, Float) {
+        (self.imbalances.len(), self.max_residual)
+    }
+
+    /// Forgets whatever was registered after `mark` was taken (see [`EnergyAudit::mark`]);
+    /// e.g., the sub-timesteps rejected by [`crate::model::ThermalModelOptions::adaptive`]
+    pub(crate) fn rollback(&mut self, mark: (usize, Float)) {
+        self.imbalances.truncate(mark.0);
+        self.max_residual = mark.1;
+    }
+}
+
+/***********/
+/* TESTING */
+/***********/
+
+#[cfg(test)]
+mod testing {
+    use super::*;
+
+    #[test]
+    fn test_check() {
+        let date = Date {
+            month: 2,
+            day: 3,
+            hour: 4.0,
+        };
+        let mut audit = EnergyAudit::new(0.5, 1, 2, 0);
+        audit.zones[0] = ZoneEnergyBalance {
+            surfaces: -10.,
+            infiltration: 3.,
+            ventilation: 0.0,
+            luminaires: 4.,
+            hvac: 2.,
+            storage: -1.,
+        };
+        assert!(audit.zones[0].residual().abs() < 1e-9);
+        audit.surfaces[1] = SurfaceEnergyBalance {
+            solar: 10.,
+            front_convection: -5.,
+            back_infrared: -4.,
+            storage: 0.,
+            ..SurfaceEnergyBalance::default()
+        };
+        assert!((audit.surfaces[1].residual() - 1.).abs() < 1e-9);
+
+        audit.check(date);
+        assert_eq!(audit.imbalances.len(), 1);
+        assert_eq!(audit.imbalances[0].element, AuditedElement::Surface(1));
+        assert!((audit.imbalances[0].residual - 1.).abs() < 1e-9);
+        assert!((audit.max_residual - 1.).abs() < 1e-9);
+
+        // Forget a sub-timestep
+        let mark = audit.mark();
+        audit.surfaces[1].solar = 20.;
+        audit.check(date);
+        assert_eq!(audit.imbalances.len(), 2);
+        assert!((audit.max_residual - 11.).abs() < 1e-9);
+        audit.rollback(mark);
+        assert_eq!(audit.imbalances.len(), 1);
+        assert!((audit.max_residual - 1.).abs() < 1e-9);
+    }
+}
@@ -57,15 +57,249 @@ impl Cavity {
     /// U_{cavity} = \frac{4*{T_m}^3 * \Sigma  \epsilon_1 \epsilon_2}{1-(1-\epsilon_1)(1-\epsilon_2)} + h_{conv}
     /// ```
     pub fn u_value(&self, t_front: Float, t_back: Float) -> Float {
-        let conv =
-            self.gas
-                .cavity_convection(self.height, self.thickness, self.angle, t_front, t_back);
+        let conv = self.convection_coefficient(t_front, t_back);
+        let rad = self.radiation_coefficient(t_front, t_back);
+
+        rad + conv
+    }
+
+    /// Calculates the convective heat transfer coefficient between the two
+    /// sides of the cavity, assuming the gas is sealed.
+    pub fn convection_coefficient(&self, t_front: Float, t_back: Float) -> Float {
+        self.gas
+            .cavity_convection(self.height, self.thickness, self.angle, t_front, t_back)
+    }
+
+    /// Calculates the linearized radiative heat transfer coefficient between
+    /// the two sides of the cavity
+    ///
+    /// ```math
+    /// h_{rad} = \frac{4*{T_m}^3 * \Sigma  \epsilon_1 \epsilon_2}{1-(1-\epsilon_1)(1-\epsilon_2)}
+    /// ```
+    pub fn radiation_coefficient(&self, t_front: Float, t_back: Float) -> Float {
         let tm = (t_back + t_front) / 2. + 273.15;
 
-        let rad = 4. * tm.powi(3) * SIGMA * self.ein * self.eout
-            / (1. - (1. - self.ein) * (1. - self.eout));
+        4. * tm.powi(3) * SIGMA * self.ein * self.eout / (1. - (1. - self.ein) * (1. - self.eout))
+    }
+}
 
-        rad + conv
+/// Describes what drives the air through a [`VentilatedCavity`]
+#[derive(Debug, Clone, Copy)]
+pub enum CavityAirflow {
+    /// The mean air velocity within the cavity is fixed, in $`m/s`$
+    Fixed(Float),
+
+    /// The air is moved by the stack effect (i.e., buoyancy). The
+    /// mean velocity of the air in the cavity is estimated as
+    ///
+    /// ```math
+    /// V = C_d \frac{A_{open}}{A_{gap}} \sqrt{ \frac{2 g H |T_{av} - T_{in}|}{T_{av}} }
+    /// ```
+    ///
+    /// Where $`T_{av}`$ is the average temperature of the two sides of the cavity (in $`K`$),
+    /// $`T_{in}`$ is the temperature of the air entering the cavity and $`H`$ is the height
+    /// of the cavity.
+    Buoyancy {
+        /// The discharge coefficient $`C_d`$ of the openings
+        discharge_coefficient: Float,
+
+        /// The ratio between the area of the openings $`A_{open}`$ and the
+        /// cross-section of the cavity $`A_{gap}`$ (i.e., its thickness times its width)
+        opening_ratio: Float,
+    },
+
+    /// The air is moved by the wind. The mean velocity of the air in the
+    /// cavity is $`V = C_w V_{wind}`$, where $`V_{wind}`$ is the wind speed
+    /// next to the inlet.
+    Wind {
+        /// The coefficient $`C_w`$ relating the wind speed to the speed of the
+        /// air in the cavity
+        coefficient: Float,
+    },
+}
+
+/// Identifies a side of a surface
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CavitySide {
+    /// The front side of the surface containing the cavity
+    Front,
+
+    /// The back side of the surface containing the cavity
+    Back,
+}
+
+/// Represents a [`Cavity`] that exchanges air with the environment at one
+/// of the sides of the surface (e.g., a rainscreen or a double-skin façade)
+///
+/// It follows the approach of section 7 of ISO15099/2003. The sides of the
+/// cavity exchange heat with the air stream through a convection coefficient
+///
+/// ```math
+/// h_{cv} = 2 h_c + 4 V
+/// ```
+///
+/// where $`h_c`$ is the convection coefficient of the sealed cavity and $`V`$ the mean
+/// velocity of the air. The air temperature profile along the height of the cavity
+/// is exponential, with a characteristic height
+///
+/// ```math
+/// H_0 = \frac{\rho c_p b V}{2 h_{cv}}
+/// ```
+///
+/// ($`b`$ being the thickness of the cavity). So, the air leaves the cavity at
+///
+/// ```math
+/// T_{out} = T_{av} - (T_{av} - T_{in}) e^{-H/H_0}
+/// ```
+///
+/// and its mean temperature is $`T_{mean} = (1-f) T_{av} + f T_{in}`$, with
+/// $`f = \frac{H_0}{H}\left(1 - e^{-H/H_0}\right)`$.
+#[derive(Debug, Clone)]
+pub struct VentilatedCavity {
+    /// The geometry and gas of the cavity
+    pub cavity: Cavity,
+
+    /// What moves the air through the cavity
+    pub airflow: CavityAirflow,
+
+    /// The side of the surface from which the air enters the cavity
+    pub inlet: CavitySide,
+
+    /// The side of the surface to which the air is delivered when leaving the cavity
+    pub outlet: CavitySide,
+}
+
+impl VentilatedCavity {
+    /// Calculates the mean velocity of the air within the cavity, in $`m/s`$
+    pub fn air_velocity(
+        &self,
+        t_front: Float,
+        t_back: Float,
+        t_in: Float,
+        wind_speed: Float,
+    ) -> Float {
+        match self.airflow {
+            CavityAirflow::Fixed(v) => v,
+            CavityAirflow::Buoyancy {
+                discharge_coefficient,
+                opening_ratio,
+            } => {
+                const G: Float = 9.81;
+                let t_av = (t_front + t_back) / 2. + 273.15;
+                let delta_t = (t_av - 273.15 - t_in).abs();
+                discharge_coefficient
+                    * opening_ratio
+                    * (2. * G * self.cavity.height * delta_t / t_av).sqrt()
+            }
+            CavityAirflow::Wind { coefficient } => coefficient * wind_speed,
+        }
+    }
+
+    /// Calculates the convection coefficient $`h_{cv}`$ between each side of the
+    /// cavity and the air stream
+    fn surface_to_air_coefficient(&self, t_front: Float, t_back: Float, velocity: Float) -> Float {
+        2. * self.cavity.convection_coefficient(t_front, t_back) + 4. * velocity
+    }
+
+    /// Calculates the fraction $`f`$ of the mean air temperature that is
+    /// explained by the inlet temperature, and the characteristic height $`H_0`$
+    fn mixing_factor(&self, t_front: Float, t_back: Float, velocity: Float) -> (Float, Float) {
+        if velocity < 1e-9 {
+            return (0.0, 0.0);
+        }
+        let h_cv = self.surface_to_air_coefficient(t_front, t_back, velocity);
+        let t_av = (t_front + t_back) / 2. + 273.15;
+        let rho = self.cavity.gas.density(t_av);
+        let cp = self.cavity.gas.heat_capacity(t_av);
+        let h0 = rho * cp * self.cavity.thickness * velocity / (2. * h_cv);
+        let height = self.cavity.height;
+        (h0 / height * (1. - (-height / h0).exp()), h0)
+    }
+
+    /// Calculates the U-value connecting both sides of the cavity. This includes
+    /// the radiative exchange and the part of the convective exchange that does not
+    /// depend on the inlet temperature, so that the heat flow from the front to the
+    /// back side is $`q = U (T_{front} - T_{back})`$.
+    pub fn u_value(&self, t_front: Float, t_back: Float, t_in: Float, wind_speed: Float) -> Float {
+        let velocity = self.air_velocity(t_front, t_back, t_in, wind_speed);
+        let h_cv = self.surface_to_air_coefficient(t_front, t_back, velocity);
+        let (f, _) = self.mixing_factor(t_front, t_back, velocity);
+        self.cavity.radiation_coefficient(t_front, t_back) + h_cv * (1. - f) / 2.
+    }
+
+    /// Calculates the conductance $`g = h_{cv} f`$ connecting each side of the
+    /// cavity with the inlet air temperature, so that the heat flow from one side
+    /// into the air is $`q = g (T_{side} - T_{in})`$ (plus the part accounted for by `u_value()`).
+    pub fn air_conductance(
+        &self,
+        t_front: Float,
+        t_back: Float,
+        t_in: Float,
+        wind_speed: Float,
+    ) -> Float {
+        let velocity = self.air_velocity(t_front, t_back, t_in, wind_speed);
+        let h_cv = self.surface_to_air_coefficient(t_front, t_back, velocity);
+        let (f, _) = self.mixing_factor(t_front, t_back, velocity);
+        h_cv * f
+    }
+
+    /// Calculates the temperature of the air leaving the cavity
+    pub fn outlet_temperature(
+        &self,
+        t_front: Float,
+        t_back: Float,
+        t_in: Float,
+        wind_speed: Float,
+    ) -> Float {
+        let velocity = self.air_velocity(t_front, t_back, t_in, wind_speed);
+        let (_, h0) = self.mixing_factor(t_front, t_back, velocity);
+        let t_av = (t_front + t_back) / 2.;
+        if h0 < 1e-9 {
+            return t_av;
+        }
+        t_av - (t_av - t_in) * (-self.cavity.height / h0).exp()
+    }
+
+    /// Calculates the mean temperature of the air within the cavity
+    pub fn mean_air_temperature(
+        &self,
+        t_front: Float,
+        t_back: Float,
+        t_in: Float,
+        wind_speed: Float,
+    ) -> Float {
+        let velocity = self.air_velocity(t_front, t_back, t_in, wind_speed);
+        let (f, _) = self.mixing_factor(t_front, t_back, velocity);
+        (1. - f) * (t_front + t_back) / 2. + f * t_in
+    }
+
+    /// Calculates the product between the mass flow and the heat capacity of the
+    /// air going through the cavity, per square meter of surface (in $`W/m^2.K`$), so that
+    /// the heat delivered to the air stream is $`\dot{m}c_p (T_{out} - T_{in})`$
+    pub fn air_heat_capacity_flow(
+        &self,
+        t_front: Float,
+        t_back: Float,
+        t_in: Float,
+        wind_speed: Float,
+    ) -> Float {
+        let velocity = self.air_velocity(t_front, t_back, t_in, wind_speed);
+        let t_av = (t_front + t_back) / 2. + 273.15;
+        let rho = self.cavity.gas.density(t_av);
+        let cp = self.cavity.gas.heat_capacity(t_av);
+        rho * cp * velocity * self.cavity.thickness / self.cavity.height
+    }
+
+    /// Calculates the heat (in $`W/m^2`$ of surface) delivered to the air stream
+    pub fn heat_to_air(
+        &self,
+        t_front: Float,
+        t_back: Float,
+        t_in: Float,
+        wind_speed: Float,
+    ) -> Float {
+        let g = self.air_conductance(t_front, t_back, t_in, wind_speed);
+        g * (t_front - t_in) + g * (t_back - t_in)
     }
 }
 
@@ -92,4 +326,93 @@ mod testing {
         let exp_u = 0.069446 / gap_thickness;
         dbg!(u, exp_u);
     }
+
+    fn get_ventilated(airflow: CavityAirflow) -> VentilatedCavity {
+        VentilatedCavity {
+            cavity: Cavity {
+                thickness: 0.1,
+                height: 3.,
+                gas: crate::gas::AIR,
+                eout: 0.84,
+                ein: 0.84,
+                angle: crate::PI / 2.,
+            },
+            airflow,
+            inlet: CavitySide::Front,
+            outlet: CavitySide::Front,
+        }
+    }
+
+    #[test]
+    fn test_ventilated_still_air() {
+        // No airflow... behaves like a sealed cavity
+        let cav = get_ventilated(CavityAirflow::Fixed(0.0));
+        let (t_front, t_back, t_in) = (10., 30., 5.);
+        let u = cav.u_value(t_front, t_back, t_in, 0.0);
+        let exp_u = cav.cavity.u_value(t_front, t_back);
+        assert!((u - exp_u).abs() < 1e-9, "Expecting {exp_u}... found {u}");
+        assert!(cav.air_conductance(t_front, t_back, t_in, 0.0).abs() < 1e-12);
+        assert!(cav.heat_to_air(t_front, t_back, t_in, 0.0).abs() < 1e-12);
+    }
+
+    #[test]
+    fn test_ventilated_energy_balance() {
+        let cav = get_ventilated(CavityAirflow::Fixed(0.5));
+        let (t_front, t_back, t_in) = (10., 30., 5.);
+        let t_out = cav.outlet_temperature(t_front, t_back, t_in, 0.0);
+        let t_mean = cav.mean_air_temperature(t_front, t_back, t_in, 0.0);
+        assert!(t_in < t_mean && t_mean < t_out && t_out < (t_front + t_back) / 2.);
+
+        // The heat picked up by the air stream...
+        let t_av = (t_front + t_back) / 2. + 273.15;
+        let rho = cav.cavity.gas.density(t_av);
+        let cp = cav.cavity.gas.heat_capacity(t_av);
+        let exp = rho * cp * 0.5 * cav.cavity.thickness * (t_out - t_in) / cav.cavity.height;
+        // ... is what the surfaces lose
+        let found = cav.heat_to_air(t_front, t_back, t_in, 0.0);
+        assert!(
+            (exp - found).abs() < 1e-6,
+            "Expecting {exp}... found {found}"
+        );
+    }
+
+    #[test]
+    fn test_ventilated_hand_calculation() {
+        // Both sides at 30C, so the sealed cavity only conducts (i.e., Nu = 1)
+        // and the air enters well below the mean temperature of the cavity.
+        let cav = get_ventilated(CavityAirflow::Fixed(0.5));
+        let (t_front, t_back, t_in) = (30., 30., 10.);
+
+        // At 303.15K: lambda = 0.026397 W/m.K, cp = 1006.473 J/kg.K, rho = 1.164591 kg/m3
+        // h_cv = 2 * lambda / 0.1 + 4 * 0.5 = 2.527949 W/m2.K
+        // H_0 = rho * cp * 0.1 * 0.5 / (2 * h_cv) = 11.591702 m
+        // T_out = 30 - (30 - 10) * exp(-3 / H_0) = 14.560542 C
+        let t_out = cav.outlet_temperature(t_front, t_back, t_in, 0.0);
+        assert!((t_out - 14.560542).abs() < 1e-4, "found {t_out}");
+
+        // f = H_0 / 3 * (1 - exp(-3 / H_0)) = 0.881074, so T_mean = 12.378519 C
+        let t_mean = cav.mean_air_temperature(t_front, t_back, t_in, 0.0);
+        assert!((t_mean - 12.378519).abs() < 1e-4, "found {t_mean}");
+
+        // Heat gain = rho * cp * 0.5 * 0.1 * (T_out - T_in) / 3 = 89.092403 W/m2
+        let q = cav.heat_to_air(t_front, t_back, t_in, 0.0);
+        assert!((q - 89.092403).abs() < 1e-3, "found {q}");
+        let m_cp = cav.air_heat_capacity_flow(t_front, t_back, t_in, 0.0);
+        assert!((m_cp * (t_out - t_in) - 89.092403).abs() < 1e-3);
+    }
+
+    #[test]
+    fn test_ventilated_airflow() {
+        let cav = get_ventilated(CavityAirflow::Wind { coefficient: 0.1 });
+        assert!((cav.air_velocity(10., 10., 10., 3.) - 0.3).abs() < 1e-12);
+
+        let cav = get_ventilated(CavityAirflow::Buoyancy {
+            discharge_coefficient: 0.6,
+            opening_ratio: 0.5,
+        });
+        // No temperature difference, no flow
+        assert!(cav.air_velocity(10., 10., 10., 3.).abs() < 1e-12);
+        // A warm cavity drives air through it
+        assert!(cav.air_velocity(30., 30., 10., 0.) > 0.0);
+    }
 }
@@ -0,0 +1,195 @@
+/*
+MIT License
+Copyright (c) 2021 Germán Molina
+Permission is hereby granted, free of charge, to any person obtaining a copy
+of this software and associated documentation files (the "Software"), to deal
+in the Software without restriction, including without limitation the rights
+to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+copies of the Software, and to permit persons to whom the Software is
+furnished to do so, subject to the following conditions:
+The above copyright notice and this permission notice shall be included in all
+copies or substantial portions of the Software.
+THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+SOFTWARE.
+*/
+
+use crate::error::ThermalError;
+use crate::Float;
+use matrix::Matrix;
+use std::io::{Read, Write};
+
+/// The first bytes of every checkpoint
+pub(crate) const MAGIC: &[u8; 8] = b"SIMTHERM";
+
+/// The version of the checkpoint format
+pub(crate) const VERSION: usize = 3;
+
+/// Writes data into a checkpoint
+pub(crate) struct CheckpointWriter<'a, W: Write> {
+    writer: &'a mut W,
+}
+
+impl<'a, W: Write> CheckpointWriter<'a, W> {
+    /// Wraps a writer
+    pub fn new(writer: &'a mut W) -> Self {
+        Self { writer }
+    }
+
+    /// Writes raw bytes
+    pub fn bytes(&mut self, v: &[u8]) -> Result<(), ThermalError> {
+        self.writer
+            .write_all(v)
+            .map_err(|e| ThermalError::checkpoint(format!("could not write: {}", e)))
+    }
+
+    /// Writes a `usize`
+    pub fn usize(&mut self, v: usize) -> Result<(), ThermalError> {
+        self.bytes(&(v as u64).to_le_bytes())
+    }
+
+    /// Writes a `bool`
+    pub fn bool(&mut self, v: bool) -> Result<(), ThermalError> {
+        self.bytes(&[v as u8])
+    }
+
+    /// Writes a [`Float`]
+    pub fn float(&mut self, v: Float) -> Result<(), ThermalError> {
+        self.bytes(&v.to_le_bytes())
+    }
+
+    /// Writes a [`Matrix`], including its size
+    pub fn matrix(&mut self, m: &Matrix) -> Result<(), ThermalError> {
+        let (rows, cols) = m.size();
+        self.usize(rows)?;
+        self.usize(cols)?;
+        for r in 0..rows {
+            for c in 0..cols {
+                self.float(m.get(r, c).map_err(ThermalError::checkpoint)?)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Reads data from a checkpoint
+pub(crate) struct CheckpointReader<'a, R: Read> {
+    reader: &'a mut R,
+}
+
+impl<'a, R: Read> CheckpointReader<'a, R> {
+    /// Wraps a reader
+    pub fn new(reader: &'a mut R) -> Self {
+        Self { reader }
+    }
+
+    /// Fills `buf` with the next bytes
+    pub fn bytes(&mut self, buf: &mut [u8]) -> Result<(), ThermalError> {
+        self.reader
+            .read_exact(buf)
+            .map_err(|e| ThermalError::checkpoint(format!("could not read: {}", e)))
+    }
+
+    /// Reads a `usize`
+    pub fn usize(&mut self) -> Result<usize, ThermalError> {
+        let mut buf = [0u8; 8];
+        self.bytes(&mut buf)?;
+        Ok(u64::from_le_bytes(buf) as usize)
+    }
+
+    /// Reads a `usize`, failing if it is not the `expected` one
+    pub fn expect_usize(&mut self, expected: usize, what: &str) -> Result<(), ThermalError> {
+        let found = self.usize()?;
+        if found != expected {
+            return Err(ThermalError::checkpoint(format!(
+                "it does not match the model... expecting {} {}, found {}",
+                expected, what, found
+            )));
+        }
+        Ok(())
+    }
+
+    /// Reads a `bool`
+    pub fn bool(&mut self) -> Result<bool, ThermalError> {
+        let mut buf = [0u8; 1];
+        self.bytes(&mut buf)?;
+        Ok(buf[0] != 0)
+    }
+
+    /// Reads a [`Float`]
+    pub fn float(&mut self) -> Result<Float, ThermalError> {
+        let mut buf = [0u8; std::mem::size_of::<Float>()];
+        self.bytes(&mut buf)?;
+        Ok(Float::from_le_bytes(buf))
+    }
+
+    /// Reads a [`Matrix`] into `m`, which needs to be of the same size
+    /// as the one that was written
+    pub fn matrix(&mut self, m: &mut Matrix) -> Result<(), ThermalError> {
+        let (rows, cols) = m.size();
+        self.expect_usize(rows, "matrix rows")?;
+        self.expect_usize(cols, "matrix columns")?;
+        for r in 0..rows {
+            for c in 0..cols {
+                let v = self.float()?;
+                m.set(r, c, v).map_err(ThermalError::checkpoint)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/***********/
+/* TESTING */
+/***********/
+
+#[cfg(test)]
+mod testing {
+    use super::*;
+
+    #[test]
+    fn test_roundtrip() {
+        let mut m = Matrix::new(0.0, 2, 3);
+        m.set(1, 2, 0.1 + 0.2).unwrap();
+        m.set(0, 0, -1e-300 as Float).unwrap();
+
+        let mut buf: Vec<u8> = Vec::new();
+        let mut w = CheckpointWriter::new(&mut buf);
+        w.usize(31).unwrap();
+        w.bool(true).unwrap();
+        w.float(1. / 3.).unwrap();
+        w.matrix(&m).unwrap();
+
+        let mut data = buf.as_slice();
+        let mut r = CheckpointReader::new(&mut data);
+        assert_eq!(r.usize().unwrap(), 31);
+        assert!(r.bool().unwrap());
+        assert_eq!(r.float().unwrap().to_bits(), (1. / 3. as Float).to_bits());
+        let mut found = Matrix::new(1.0, 2, 3);
+        r.matrix(&mut found).unwrap();
+        for i in 0..2 {
+            for j in 0..3 {
+                assert_eq!(
+                    found.get(i, j).unwrap().to_bits(),
+                    m.get(i, j).unwrap().to_bits()
+                );
+            }
+        }
+
+        // Nothing else to read
+        assert!(r.float().is_err());
+
+        // Wrong size
+        let mut data = buf.as_slice();
+        let mut r = CheckpointReader::new(&mut data);
+        r.usize().unwrap();
+        r.bool().unwrap();
+        r.float().unwrap();
+        let mut wrong = Matrix::new(0.0, 3, 2);
+        assert!(r.matrix(&mut wrong).is_err());
+    }
+}
@@ -18,9 +18,149 @@ OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
 SOFTWARE.
 */
 
+use crate::sun::hourly_value;
 use crate::Float;
+use calendar::Date;
+use simple_model::Substance;
 const MIN_H: Float = 0.1;
 
+/// The roughness of a surface, which affects its forced convection
+/// coefficient (see [`ConvectionParams::get_tarp_convection_coefficient`])
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Roughness {
+    /// E.g., stucco
+    VeryRough,
+
+    /// E.g., brick
+    #[default]
+    Rough,
+
+    /// E.g., concrete
+    MediumRough,
+
+    /// E.g., clear pine
+    MediumSmooth,
+
+    /// E.g., smooth plaster
+    Smooth,
+
+    /// E.g., glass
+    VerySmooth,
+}
+
+impl Roughness {
+    /// Gets the roughness multiplier $`R_f`$ (see [`ConvectionParams::get_tarp_convection_coefficient`])
+    pub fn multiplier(&self) -> Float {
+        match self {
+            Self::VeryRough => 2.17,
+            Self::Rough => 1.67,
+            Self::MediumRough => 1.52,
+            Self::MediumSmooth => 1.13,
+            Self::Smooth => 1.11,
+            Self::VerySmooth => 1.,
+        }
+    }
+
+    /// Guesses the roughness of the outer face of a [`Substance`].
+    ///
+    /// Substances do not carry a roughness, so this is only a heuristic: transparent
+    /// ones (i.e., glass) are considered [`Roughness::VerySmooth`] and all others take
+    /// the default [`Roughness::Rough`]. The finish of opaque materials cannot be told
+    /// from their properties, so smooth ones (e.g., plaster or metal) will get a forced
+    /// convection coefficient up to 50% too high. Use
+    /// [`crate::model::ThermalModel::set_construction_roughness`] (or the roughness
+    /// fields of each surface) when the actual roughness is known.
+    pub fn from_substance(substance: &Substance) -> Self {
+        match substance {
+            Substance::Normal(s) if *s.solar_transmittance().unwrap_or(&0.0) > 0.0 => {
+                Self::VerySmooth
+            }
+            _ => Self::default(),
+        }
+    }
+}
+
+/// A user-specified convection coefficient, which replaces the one
+/// that would otherwise be calculated by the convection algorithms.
+/// This is analogous to EnergyPlus' `SurfaceProperty:ConvectionCoefficients`
+#[derive(Debug, Clone, PartialEq)]
+pub enum ConvectionCoefficient {
+    /// A constant value, in $`W/m^2.K`$
+    Fixed(Float),
+
+    /// Hourly values, in $`W/m^2.K`$, starting at midnight on January 1st.
+    /// The values are repeated cyclically, so 24 values represent a daily profile
+    /// and 8760 represent a whole year.
+    Scheduled(Vec<Float>),
+}
+
+impl ConvectionCoefficient {
+    /// Creates a new scheduled [`ConvectionCoefficient`], checking
+    /// that the values are valid
+    pub fn new_scheduled(values: Vec<Float>) -> Result<Self, String> {
+        if values.is_empty() {
+            return Err("Scheduled convection coefficients need at least one value".into());
+        }
+        if let Some(v) = values.iter().find(|v| v.is_nan() || **v < 0.0) {
+            return Err(format!(
+                "Scheduled convection coefficients need to be non-negative... found {v}"
+            ));
+        }
+        Ok(Self::Scheduled(values))
+    }
+
+    /// Gets the value of the convection coefficient at a certain date
+    pub fn value(&self, date: Date) -> Float {
+        match self {
+            Self::Fixed(v) => *v,
+            Self::Scheduled(values) => hourly_value(values, date),
+        }
+    }
+}
+
+/// The algorithms available for calculating the convection coefficients
+/// of surfaces facing a Space (i.e., interior surfaces)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum InteriorConvectionAlgorithm {
+    /// The TARP model (see [`ConvectionParams::get_tarp_natural_convection_coefficient`])
+    #[default]
+    Tarp,
+
+    /// The constant coefficients of ASHRAE's simple model (see [`ConvectionParams::get_ashrae_simple_convection_coefficient`])
+    AshraeSimple,
+
+    /// The Alamdari-Hammond correlations used by EnergyPlus' adaptive
+    /// algorithm for buoyancy-driven flow (see [`ConvectionParams::get_adaptive_convection_coefficient`])
+    Adaptive,
+
+    /// The Fisher-Pedersen correlations for rooms served by ceiling diffusers
+    /// (see [`ConvectionParams::get_ceiling_diffuser_convection_coefficient`])
+    CeilingDiffuser,
+
+    /// The Beausoleil-Morrison correlations for mixed (i.e., forced and
+    /// buoyancy-driven) convection (see [`ConvectionParams::get_mixed_convection_coefficient`])
+    MixedConvection,
+
+    /// The correlations of ISO 15099 for the interior side of windows
+    /// (see [`ConvectionParams::get_iso15099_window_convection_coefficient`])
+    Iso15099Window,
+}
+
+/// The algorithms available for calculating the convection coefficients
+/// of surfaces facing the exterior
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ExteriorConvectionAlgorithm {
+    /// The TARP model (see [`ConvectionParams::get_tarp_convection_coefficient`])
+    #[default]
+    Tarp,
+
+    /// The DOE-2 model (see [`ConvectionParams::get_doe2_convection_coefficient`])
+    Doe2,
+
+    /// The MoWiTT model (see [`ConvectionParams::get_mowitt_convection_coefficient`])
+    MoWiTT,
+}
+
 /// Represents a border condition of between a Surface
 /// and a Zone or the exterior
 #[derive(Debug, Clone, Copy)]
@@ -43,8 +183,8 @@ pub struct ConvectionParams {
     /// The surface temperature in $`C`$
     pub surface_temperature: Float,
 
-    /// The roughness index, between 1 (Very Rough) and 6 (Very smooth)
-    pub roughness_index: usize,
+    /// The roughness of the surface
+    pub roughness: Roughness,
 
     /// The cosine of the surface tilt. Zero is 90 degrees; >0 means
     /// facing up; <0 means facing down
@@ -94,12 +234,9 @@ impl ConvectionParams {
             || (delta_t > 0. && self.cos_surface_tilt > 0.)
         {
             9.482 * abs_delta_t.powf(1. / 3.) / (7.238 - self.cos_surface_tilt.abs())
-        } else if (delta_t > 0. && self.cos_surface_tilt < 0.)
-            || (delta_t < 0. && self.cos_surface_tilt > 0.)
-        {
-            1.81 * abs_delta_t.powf(1. / 3.) / (1.382 + self.cos_surface_tilt.abs())
         } else {
-            unreachable!()
+            // Stable... a NaN delta_t also ends up here, and propagates into the results
+            1.81 * abs_delta_t.powf(1. / 3.) / (1.382 + self.cos_surface_tilt.abs())
         };
 
         if h < MIN_H {
@@ -154,9 +291,7 @@ impl ConvectionParams {
         perimeter: Float,
         windward: bool,
     ) -> Float {
-        const COEFFICIENTS: [Float; 6] = [2.17, 1.67, 1.52, 1.13, 1.11, 1.];
-
-        let rf = COEFFICIENTS[self.roughness_index];
+        let rf = self.roughness.multiplier();
 
         let wf = if windward { 1.0 } else { 0.5 };
 
@@ -166,17 +301,511 @@ impl ConvectionParams {
 
         forced + natural // this will never be less than MIN_HS because natural is already limited
     }
+
+    /// Calculates the interior convection coefficient using the chosen algorithm.
+    ///
+    /// The `height` and `hydraulic_diameter` (i.e., $`4A/P`$) of the surface are
+    /// only used by [`InteriorConvectionAlgorithm::Adaptive`], [`InteriorConvectionAlgorithm::MixedConvection`]
+    /// and [`InteriorConvectionAlgorithm::Iso15099Window`] (which only uses the `height`),
+    /// while the `air_changes` (i.e., the supply air changes per hour of the zone) are
+    /// only used by [`InteriorConvectionAlgorithm::CeilingDiffuser`] and [`InteriorConvectionAlgorithm::MixedConvection`]
+    pub fn get_interior_convection_coefficient(
+        &self,
+        algorithm: InteriorConvectionAlgorithm,
+        height: Float,
+        hydraulic_diameter: Float,
+        air_changes: Float,
+    ) -> Float {
+        match algorithm {
+            InteriorConvectionAlgorithm::Tarp => self.get_tarp_natural_convection_coefficient(),
+            InteriorConvectionAlgorithm::AshraeSimple => {
+                self.get_ashrae_simple_convection_coefficient()
+            }
+            InteriorConvectionAlgorithm::Adaptive => {
+                self.get_adaptive_convection_coefficient(height, hydraulic_diameter)
+            }
+            InteriorConvectionAlgorithm::CeilingDiffuser => {
+                self.get_ceiling_diffuser_convection_coefficient(air_changes)
+            }
+            InteriorConvectionAlgorithm::MixedConvection => {
+                self.get_mixed_convection_coefficient(height, hydraulic_diameter, air_changes)
+            }
+            InteriorConvectionAlgorithm::Iso15099Window => {
+                self.get_iso15099_window_convection_coefficient(height)
+            }
+        }
+    }
+
+    /// Calculates the exterior convection coefficient using the chosen algorithm.
+    pub fn get_exterior_convection_coefficient(
+        &self,
+        algorithm: ExteriorConvectionAlgorithm,
+        area: Float,
+        perimeter: Float,
+        windward: bool,
+    ) -> Float {
+        match algorithm {
+            ExteriorConvectionAlgorithm::Tarp => {
+                self.get_tarp_convection_coefficient(area, perimeter, windward)
+            }
+            ExteriorConvectionAlgorithm::Doe2 => self.get_doe2_convection_coefficient(windward),
+            ExteriorConvectionAlgorithm::MoWiTT => self.get_mowitt_convection_coefficient(windward),
+        }
+    }
+
+    /// Checks whether the air next to the surface is unstably stratified (i.e., heat
+    /// flows upwards). This follows the same convention as [`ConvectionParams::get_tarp_natural_convection_coefficient`]
+    fn is_unstable(&self) -> bool {
+        let delta_t = self.air_temperature - self.surface_temperature;
+        (delta_t < 0. && self.cos_surface_tilt < 0.) || (delta_t > 0. && self.cos_surface_tilt > 0.)
+    }
+
+    /// Calculates the interior convection coefficient according to the constant
+    /// coefficients of ASHRAE's simple model, as described in EnergyPlus'
+    /// Engineering Reference.
+    ///
+    /// | Orientation | Heat flow | $`h`$ |
+    /// |-------------|-----------|-------|
+    /// | Vertical    | -         | 3.076 |
+    /// | Horizontal  | Upwards   | 4.040 |
+    /// | Horizontal  | Downwards | 0.948 |
+    /// | Tilted      | Upwards   | 3.870 |
+    /// | Tilted      | Downwards | 2.281 |
+    ///
+    /// Surfaces within 22.5 degrees of the vertical are considered vertical, and those
+    /// within 22.5 degrees of the horizontal are considered horizontal.
+    pub fn get_ashrae_simple_convection_coefficient(&self) -> Float {
+        let cos = self.cos_surface_tilt.abs();
+        let delta_t = self.air_temperature - self.surface_temperature;
+        if cos < 0.38 || delta_t.abs() < 1e-3 {
+            3.076
+        } else if cos > 0.92 {
+            if self.is_unstable() {
+                4.040
+            } else {
+                0.948
+            }
+        } else if self.is_unstable() {
+            3.870
+        } else {
+            2.281
+        }
+    }
+
+    /// Calculates the interior convection coefficient using the Alamdari-Hammond
+    /// correlations for buoyancy-driven flow, which are the ones used by EnergyPlus'
+    /// adaptive algorithm when there is no HVAC-driven flow.
+    ///
+    /// For vertical surfaces:
+    ///
+    /// ```math
+    /// h = \left(\left[1.5 \left(\frac{|\Delta T|}{H}\right)^{1/4}\right]^6 + \left[1.23 |\Delta T|^{1/3}\right]^6\right)^{1/6}
+    /// ```
+    ///
+    /// For horizontal (or tilted) surfaces with unstable stratification:
+    ///
+    /// ```math
+    /// h = \left(\left[1.4 \left(\frac{|\Delta T|}{D_h}\right)^{1/4}\right]^6 + \left[1.63 |\Delta T|^{1/3}\right]^6\right)^{1/6}
+    /// ```
+    ///
+    /// And for those with stable stratification:
+    ///
+    /// ```math
+    /// h = 0.6 \left(\frac{|\Delta T|}{{D_h}^2}\right)^{1/5}
+    /// ```
+    pub fn get_adaptive_convection_coefficient(
+        &self,
+        height: Float,
+        hydraulic_diameter: Float,
+    ) -> Float {
+        let delta_t = (self.air_temperature - self.surface_temperature).abs();
+        let h = if self.cos_surface_tilt.abs() < 0.38 {
+            ((1.5 * (delta_t / height).powf(0.25)).powi(6) + (1.23 * delta_t.powf(1. / 3.)).powi(6))
+                .powf(1. / 6.)
+        } else if self.is_unstable() {
+            ((1.4 * (delta_t / hydraulic_diameter).powf(0.25)).powi(6)
+                + (1.63 * delta_t.powf(1. / 3.)).powi(6))
+            .powf(1. / 6.)
+        } else {
+            0.6 * (delta_t / hydraulic_diameter.powi(2)).powf(0.2)
+        };
+        h.max(MIN_H)
+    }
+
+    /// Calculates the forced convection coefficient driven by the air supplied
+    /// by ceiling diffusers (see [`ConvectionParams::get_ceiling_diffuser_convection_coefficient`])
+    fn ceiling_diffuser_forced(&self, air_changes: Float) -> Float {
+        if self.cos_surface_tilt > 0.707 {
+            3.873 + 0.082 * air_changes.powf(0.98)
+        } else if self.cos_surface_tilt < -0.707 {
+            2.234 + 4.099 * air_changes.powf(0.503)
+        } else {
+            1.208 + 1.012 * air_changes.powf(0.604)
+        }
+    }
+
+    /// Calculates the interior convection coefficient of surfaces in a room served
+    /// by ceiling diffusers, according to Fisher and Pedersen (1997).
+    ///
+    /// | Surface | $`h`$ |
+    /// |---------|-------|
+    /// | Floor   | $`3.873 + 0.082 ACH^{0.98}`$ |
+    /// | Ceiling | $`2.234 + 4.099 ACH^{0.503}`$ |
+    /// | Walls   | $`1.208 + 1.012 ACH^{0.604}`$ |
+    ///
+    /// Surfaces within 45 degrees of the horizontal are considered floors or ceilings.
+    ///
+    /// Just like in EnergyPlus, these correlations are only used when there are more than
+    /// 3 air changes per hour. Otherwise, the natural convection coefficient of the TARP
+    /// model is returned.
+    pub fn get_ceiling_diffuser_convection_coefficient(&self, air_changes: Float) -> Float {
+        if air_changes <= 3. {
+            return self.get_tarp_natural_convection_coefficient();
+        }
+        self.ceiling_diffuser_forced(air_changes)
+    }
+
+    /// Calculates the interior convection coefficient of surfaces in a room with
+    /// mixed convection, according to Beausoleil-Morrison (2000). This blends the natural
+    /// convection coefficient $`h_n`$ of [`ConvectionParams::get_adaptive_convection_coefficient`] with
+    /// the forced convection coefficient $`h_f`$ of the Fisher-Pedersen correlations.
+    ///
+    /// When buoyancy assists the supply air (i.e., on floors and ceilings, and on walls
+    /// colder than the air, where both flow downwards):
+    ///
+    /// ```math
+    /// h = \left({h_n}^3 + {h_f}^3\right)^{1/3}
+    /// ```
+    ///
+    /// And when it opposes it:
+    ///
+    /// ```math
+    /// h = \max\left( \left({h_n}^3 - {h_f}^3\right)^{1/3}, 0.8 h_n, 0.8 h_f \right)
+    /// ```
+    ///
+    /// If there is no supply air, this is simply $`h_n`$.
+    pub fn get_mixed_convection_coefficient(
+        &self,
+        height: Float,
+        hydraulic_diameter: Float,
+        air_changes: Float,
+    ) -> Float {
+        let natural = self.get_adaptive_convection_coefficient(height, hydraulic_diameter);
+        if air_changes <= 0.0 {
+            return natural;
+        }
+        let forced = self.ceiling_diffuser_forced(air_changes);
+        let is_wall = self.cos_surface_tilt.abs() <= 0.707;
+        let opposing = is_wall && self.surface_temperature > self.air_temperature;
+        if opposing {
+            (natural.powi(3) - forced.powi(3))
+                .cbrt()
+                .max(0.8 * natural)
+                .max(0.8 * forced)
+        } else {
+            (natural.powi(3) + forced.powi(3)).cbrt()
+        }
+    }
+
+    /// Calculates the interior convection coefficient of a window of a certain
+    /// `height`, according to Section 8.3.2.2 of ISO15099/2003.
+    ///
+    /// ```math
+    /// h = Nu \frac{\lambda}{H}
+    /// ```
+    ///
+    /// Where $`\lambda`$ is the thermal conductivity of the air at the mean temperature
+    /// between the air and the glass, and the Nusselt number depends on the Rayleigh
+    /// number based on the height $`Ra_H`$ and on the angle $`\gamma`$ (in degrees)—which is
+    /// $`0`$ for horizontal windows with unstable stratification, $`90`$ for vertical windows and
+    /// $`180`$ for horizontal windows with stable stratification:
+    ///
+    /// | Tilt | $`Nu`$ |
+    /// |------|--------|
+    /// | $`0 \leq \gamma < 15`$ | $`0.13 {Ra_H}^{1/3}`$ |
+    /// | $`15 \leq \gamma \leq 90`$, $`Ra_H \leq Ra_{cv}`$ | $`0.56 (Ra_H \sin{\gamma})^{1/4}`$ |
+    /// | $`15 \leq \gamma \leq 90`$, $`Ra_H > Ra_{cv}`$ | $`0.13 ({Ra_H}^{1/3} - {Ra_{cv}}^{1/3}) + 0.56 (Ra_{cv} \sin{\gamma})^{1/4}`$ |
+    /// | $`90 < \gamma \leq 179`$ | $`0.56 (Ra_H \sin{\gamma})^{1/4}`$ |
+    /// | $`179 < \gamma \leq 180`$ | $`0.58 {Ra_H}^{1/5}`$ |
+    ///
+    /// With $`Ra_{cv} = 2.5\times 10^5 \left(e^{0.72\gamma} / \sin{\gamma}\right)^{1/5}`$
+    pub fn get_iso15099_window_convection_coefficient(&self, height: Float) -> Float {
+        const G: Float = 9.81;
+        let air = crate::gas::AIR;
+        let delta_t = (self.air_temperature - self.surface_temperature).abs();
+        if delta_t < 1e-5 {
+            return MIN_H;
+        }
+
+        // Properties at the mean temperature
+        let temp = (self.air_temperature + self.surface_temperature) / 2. + 273.15;
+        let beta = 1. / temp;
+        let c_p = air.heat_capacity(temp);
+        let mu = air.dynamic_viscosity(temp);
+        let lambda = air.thermal_conductivity(temp);
+        let rho = air.density(temp);
+        let ra = rho.powi(2) * height.powi(3) * G * beta * c_p * delta_t / (mu * lambda);
+
+        // Angle between the normal and the vertical... then flip it if stable.
+        let mut gamma = self.cos_surface_tilt.abs().min(1.).acos().to_degrees();
+        if !self.is_unstable() {
+            gamma = 180. - gamma;
+        }
+        let sin = gamma.to_radians().sin();
+
+        let nu = if gamma < 15. {
+            0.13 * ra.powf(1. / 3.)
+        } else if gamma <= 90. {
+            let ra_cv = 2.5e5 * ((0.72 * gamma).exp() / sin).powf(0.2);
+            if ra <= ra_cv {
+                0.56 * (ra * sin).powf(0.25)
+            } else {
+                0.13 * (ra.powf(1. / 3.) - ra_cv.powf(1. / 3.)) + 0.56 * (ra_cv * sin).powf(0.25)
+            }
+        } else if gamma <= 179. {
+            0.56 * (ra * sin).powf(0.25)
+        } else {
+            0.58 * ra.powf(0.2)
+        };
+
+        (nu * lambda / height).max(MIN_H)
+    }
+
+    /// Calculates the exterior convection coefficient according to the MoWiTT
+    /// model, as described in EnergyPlus' Engineering Reference
+    ///
+    /// ```math
+    /// h_c = \sqrt{\left[C_t |\Delta T|^{1/3}\right]^2 + \left[a {V_z}^b\right]^2}
+    /// ```
+    ///
+    /// Where $`C_t = 0.84`$; and $`a = 3.26`$ and $`b=0.89`$ for windward surfaces, or
+    /// $`a = 3.55`$ and $`b=0.617`$ for leeward ones.
+    pub fn get_mowitt_convection_coefficient(&self, windward: bool) -> Float {
+        let natural = 0.84
+            * (self.air_temperature - self.surface_temperature)
+                .abs()
+                .powf(1. / 3.);
+        let forced = self.mowitt_forced(windward);
+        (natural.powi(2) + forced.powi(2)).sqrt().max(MIN_H)
+    }
+
+    /// The forced component of the MoWiTT model
+    fn mowitt_forced(&self, windward: bool) -> Float {
+        let (a, b) = if windward {
+            (3.26, 0.89)
+        } else {
+            (3.55, 0.617)
+        };
+        a * self.air_speed.powf(b)
+    }
+
+    /// Calculates the exterior convection coefficient according to the DOE-2
+    /// model, as described in EnergyPlus' Engineering Reference
+    ///
+    /// ```math
+    /// h_c = h_n + R_f (h_{c,glass} - h_n)
+    /// ```
+    ///
+    /// Where $`h_n`$ is the natural convection coefficient of the TARP model, $`R_f`$ is the
+    /// roughness multiplier (see [`ConvectionParams::get_tarp_convection_coefficient`]) and
+    ///
+    /// ```math
+    /// h_{c,glass} = \sqrt{{h_n}^2 + \left[a {V_z}^b\right]^2}
+    /// ```
+    ///
+    /// with the same $`a`$ and $`b`$ as in [`ConvectionParams::get_mowitt_convection_coefficient`].
+    pub fn get_doe2_convection_coefficient(&self, windward: bool) -> Float {
+        let natural = self.get_tarp_natural_convection_coefficient();
+        let forced = self.mowitt_forced(windward);
+        let h_glass = (natural.powi(2) + forced.powi(2)).sqrt();
+        natural + self.roughness.multiplier() * (h_glass - natural)
+    }
 }
 
-// #[cfg(test)]
-// mod testing {
-//     #[test]
-//     fn test_indoor_coefficient(){
-//         assert!(false)
-//     }
-
-//     #[test]
-//     fn test_outdoor_coefficient(){
-//         assert!(false)
-//     }
-// }
+#[cfg(test)]
+mod testing {
+    use super::*;
+
+    #[test]
+    fn test_convection_coefficient() {
+        let date = Date {
+            month: 1,
+            day: 2,
+            hour: 3.5,
+        };
+        assert!((ConvectionCoefficient::Fixed(3.).value(date) - 3.).abs() < 1e-9);
+
+        assert!(ConvectionCoefficient::new_scheduled(vec![]).is_err());
+        assert!(ConvectionCoefficient::new_scheduled(vec![1., -1.]).is_err());
+
+        let daily: Vec<Float> = (0..24).map(|i| i as Float).collect();
+        let h = ConvectionCoefficient::new_scheduled(daily).unwrap();
+        assert!((h.value(date) - 3.).abs() < 1e-9);
+
+        let yearly: Vec<Float> = (0..8760).map(|i| i as Float).collect();
+        let h = ConvectionCoefficient::new_scheduled(yearly).unwrap();
+        assert!((h.value(date) - 27.).abs() < 1e-9);
+    }
+
+    fn params(air_temperature: Float, surface_temperature: Float, cos: Float) -> ConvectionParams {
+        ConvectionParams {
+            air_temperature,
+            air_speed: 0.0,
+            rad_temperature: air_temperature,
+            surface_temperature,
+            roughness: Roughness::Rough,
+            cos_surface_tilt: cos,
+        }
+    }
+
+    #[test]
+    fn test_ashrae_simple() {
+        assert_eq!(
+            params(20., 25., 0.).get_ashrae_simple_convection_coefficient(),
+            3.076
+        );
+        // warm floor... heat goes up
+        assert_eq!(
+            params(20., 25., 1.).get_ashrae_simple_convection_coefficient(),
+            4.040
+        );
+        // warm ceiling... heat stays up
+        assert_eq!(
+            params(20., 25., -1.).get_ashrae_simple_convection_coefficient(),
+            0.948
+        );
+        assert_eq!(
+            params(20., 25., -0.7).get_ashrae_simple_convection_coefficient(),
+            2.281
+        );
+    }
+
+    #[test]
+    fn test_adaptive() {
+        let vertical = params(20., 25., 0.);
+        let h = vertical.get_adaptive_convection_coefficient(3., 2.);
+        let exp = ((1.5 * (5. / 3. as Float).powf(0.25)).powi(6)
+            + (1.23 * (5. as Float).powf(1. / 3.)).powi(6))
+        .powf(1. / 6.);
+        assert!((h - exp).abs() < 1e-9);
+
+        // Stable is smaller than unstable
+        let stable = params(20., 25., -1.).get_adaptive_convection_coefficient(3., 2.);
+        let unstable = params(20., 25., 1.).get_adaptive_convection_coefficient(3., 2.);
+        assert!(stable < unstable);
+
+        // Never below the minimum
+        let h = params(20., 20., 0.).get_adaptive_convection_coefficient(3., 2.);
+        assert!((h - MIN_H).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_ceiling_diffuser() {
+        // Few air changes fall back to TARP
+        let mut env = params(20., 15., 0.);
+        let tarp = env.get_tarp_natural_convection_coefficient();
+        let h = env.get_ceiling_diffuser_convection_coefficient(2.);
+        assert!((h - tarp).abs() < 1e-9);
+
+        // Walls
+        let ach: Float = 6.;
+        let h = env.get_ceiling_diffuser_convection_coefficient(ach);
+        assert!((h - (1.208 + 1.012 * ach.powf(0.604))).abs() < 1e-9);
+
+        // Floors
+        env.cos_surface_tilt = 1.;
+        let h = env.get_ceiling_diffuser_convection_coefficient(ach);
+        assert!((h - (3.873 + 0.082 * ach.powf(0.98))).abs() < 1e-9);
+
+        // Ceilings
+        env.cos_surface_tilt = -1.;
+        let h = env.get_ceiling_diffuser_convection_coefficient(ach);
+        assert!((h - (2.234 + 4.099 * ach.powf(0.503))).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_mixed_convection() {
+        let (height, dh) = (3., 2.);
+
+        // No supply air means natural convection
+        let env = params(20., 15., 0.);
+        let natural = env.get_adaptive_convection_coefficient(height, dh);
+        let h = env.get_mixed_convection_coefficient(height, dh, 0.0);
+        assert!((h - natural).abs() < 1e-9);
+
+        // A cold wall assists the supply air...
+        let ach: Float = 6.;
+        let forced = 1.208 + 1.012 * ach.powf(0.604);
+        let assisting = env.get_mixed_convection_coefficient(height, dh, ach);
+        let exp = (natural.powi(3) + forced.powi(3)).cbrt();
+        assert!((assisting - exp).abs() < 1e-9);
+
+        // ... while a warm one opposes it
+        let env = params(15., 20., 0.);
+        let opposing = env.get_mixed_convection_coefficient(height, dh, ach);
+        assert!(opposing < assisting);
+        assert!(opposing >= 0.8 * forced);
+    }
+
+    #[test]
+    fn test_iso15099_window() {
+        let height = 1.5;
+
+        // Vertical window, colder than the air. Laminar regime.
+        let env = params(22., 15., 0.);
+        let h = env.get_iso15099_window_convection_coefficient(height);
+        let air = crate::gas::AIR;
+        let temp = 18.5 + 273.15;
+        let lambda = air.thermal_conductivity(temp);
+        let ra =
+            air.density(temp).powi(2) * height.powi(3) * 9.81 / temp * air.heat_capacity(temp) * 7.
+                / (air.dynamic_viscosity(temp) * lambda);
+        let exp = 0.56 * ra.powf(0.25) * lambda / height;
+        assert!((h - exp).abs() < 1e-9, "h = {h} | exp = {exp}");
+        // ... which is a sensible value
+        assert!(h > 1. && h < 5., "h = {h}");
+
+        // Horizontal windows have more convection when unstable. Air at 22C
+        // over a 15C surface facing up (cos = 1) is unstable, while the same
+        // surface facing down (cos = -1) is stable.
+        let unstable = params(22., 15., 1.);
+        let stable = params(22., 15., -1.);
+        assert!(unstable.is_unstable());
+        assert!(!stable.is_unstable());
+        assert!(
+            unstable.get_iso15099_window_convection_coefficient(height)
+                > stable.get_iso15099_window_convection_coefficient(height)
+        );
+    }
+
+    #[test]
+    fn test_exterior() {
+        let mut env = params(10., 15., 0.);
+        env.air_speed = 3.;
+        let windward = env.get_mowitt_convection_coefficient(true);
+        let exp = ((0.84 * (5. as Float).powf(1. / 3.)).powi(2)
+            + (3.26 * (3. as Float).powf(0.89)).powi(2))
+        .sqrt();
+        assert!((windward - exp).abs() < 1e-9);
+
+        // DOE-2 of a very smooth surface is MoWiTT with TARP's natural convection
+        env.roughness = Roughness::VerySmooth;
+        let natural = env.get_tarp_natural_convection_coefficient();
+        let exp = (natural.powi(2) + (3.26 * (3. as Float).powf(0.89)).powi(2)).sqrt();
+        let doe2 = env.get_doe2_convection_coefficient(true);
+        assert!((doe2 - exp).abs() < 1e-9);
+
+        // Rougher surfaces have more convection
+        env.roughness = Roughness::VeryRough;
+        assert!(env.get_doe2_convection_coefficient(true) > doe2);
+
+        // Dispatch
+        let tarp = env.get_exterior_convection_coefficient(
+            ExteriorConvectionAlgorithm::Tarp,
+            10.,
+            13.,
+            true,
+        );
+        assert!((tarp - env.get_tarp_convection_coefficient(10., 13., true)).abs() < 1e-9);
+    }
+}
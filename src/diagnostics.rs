@@ -0,0 +1,538 @@
+/*
+MIT License
+Copyright (c) 2021 Germán Molina
+Permission is hereby granted, free of charge, to any person obtaining a copy
+of this software and associated documentation files (the "Software"), to deal
+in the Software without restriction, including without limitation the rights
+to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+copies of the Software, and to permit persons to whom the Software is
+furnished to do so, subject to the following conditions:
+The above copyright notice and this permission notice shall be included in all
+copies or substantial portions of the Software.
+THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+SOFTWARE.
+*/
+
+use crate::discretization::Discretization;
+use crate::error::{Element, ThermalError};
+use crate::glazing::Glazing;
+use crate::heating_cooling::ThermalHVAC;
+use crate::luminaire::ThermalLuminaire;
+use crate::model::ThermalModelOptions;
+use crate::Float;
+use geometry3d::Polygon3D;
+use simple_model::{Boundary, SimpleModel, Substance};
+use std::fmt;
+
+/// The solar absorbtance assumed for materials that do not have one (the
+/// same one used by [`Glazing`])
+const DEFAULT_SOLAR_ABSORBTANCE: Float = 0.84;
+
+/// How serious a [`Diagnostic`] is
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Severity {
+    /// Just for information
+    Info,
+
+    /// The model can be simulated, but probably not as the user expects
+    Warning,
+
+    /// The model cannot be simulated
+    Fatal,
+}
+
+/// Something found when validating a [`SimpleModel`] before building
+/// a [`crate::model::ThermalModel`] out of it
+#[derive(Debug, Clone, PartialEq)]
+pub enum Diagnostic {
+    /// A layer of a `Construction` that cannot be subdivided into elements
+    /// that keep the simulation stable, and is thus treated as a no-mass layer
+    LumpedLayer {
+        /// The name of the construction
+        construction: String,
+        /// The index of the layer within the construction
+        layer: usize,
+        /// The name of the material of the layer
+        material: String,
+    },
+
+    /// The number of sub-timesteps per timestep that a `Construction` requires
+    TimestepSubdivisions {
+        /// The name of the construction
+        construction: String,
+        /// The number of sub-timesteps
+        subdivisions: usize,
+    },
+
+    /// A boundary of a surface or fenestration that refers to a Space
+    /// that is not in the model
+    UnmatchedBoundary {
+        /// The surface or fenestration
+        element: Element,
+        /// Whether it is the front boundary (or the back one)
+        front: bool,
+        /// The name of the space that was not found
+        space: String,
+    },
+
+    /// A surface or fenestration whose area is zero
+    ZeroArea(Element),
+
+    /// A property required for simulating is missing
+    MissingProperty {
+        /// The element whose property is missing
+        element: Element,
+        /// The name of the property
+        property: String,
+    },
+
+    /// An optional property is missing, so a default value is used
+    DefaultProperty {
+        /// The element whose property is missing
+        element: Element,
+        /// The name of the property
+        property: String,
+        /// The default value used
+        value: Float,
+    },
+
+    /// Any other problem that prevents simulating the model
+    Error(ThermalError),
+}
+
+impl Diagnostic {
+    /// How serious the diagnostic is
+    pub fn severity(&self) -> Severity {
+        match self {
+            Self::TimestepSubdivisions { .. } => Severity::Info,
+            Self::LumpedLayer { .. } | Self::DefaultProperty { .. } => Severity::Warning,
+            Self::UnmatchedBoundary { .. }
+            | Self::ZeroArea(_)
+            | Self::MissingProperty { .. }
+            | Self::Error(_) => Severity::Fatal,
+        }
+    }
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::LumpedLayer {
+                construction,
+                layer,
+                material,
+            } => write!(
+                f,
+                "Layer {} ('{}') of Construction '{}' cannot be discretized while keeping the simulation stable, so it is treated as a no-mass layer",
+                layer, material, construction
+            ),
+            Self::TimestepSubdivisions {
+                construction,
+                subdivisions,
+            } => write!(
+                f,
+                "Construction '{}' requires {} sub-timesteps per timestep",
+                construction, subdivisions
+            ),
+            Self::UnmatchedBoundary {
+                element,
+                front,
+                space,
+            } => write!(
+                f,
+                "The {} boundary of {} is a Space called '{}'... but it was not found",
+                if *front { "front" } else { "back" },
+                element,
+                space
+            ),
+            Self::ZeroArea(element) => write!(f, "{} has zero area", element),
+            Self::MissingProperty { element, property } => {
+                write!(f, "{} has no '{}'", element, property)
+            }
+            Self::DefaultProperty {
+                element,
+                property,
+                value,
+            } => write!(
+                f,
+                "{} has no '{}'... using a default value of {}",
+                element, property, value
+            ),
+            Self::Error(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+/// The result of validating a [`SimpleModel`] before building a
+/// [`crate::model::ThermalModel`] out of it (see
+/// [`crate::model::ThermalModel::diagnose`])
+#[derive(Debug, Clone, Default)]
+pub struct DiagnosticsReport {
+    /// Everything that was found, without repetitions
+    pub diagnostics: Vec<Diagnostic>,
+}
+
+impl fmt::Display for DiagnosticsReport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for d in self.diagnostics.iter() {
+            writeln!(f, "[{:?}] {}", d.severity(), d)?;
+        }
+        Ok(())
+    }
+}
+
+impl DiagnosticsReport {
+    /// Validates a `model` whose main timestep is `main_dt` seconds,
+    /// and which will be discretized according to `options`
+    pub(crate) fn new(model: &SimpleModel, main_dt: Float, options: &ThermalModelOptions) -> Self {
+        let mut ret = Self::default();
+
+        for space in model.spaces.iter() {
+            if space.volume().is_err() {
+                ret.push(Diagnostic::MissingProperty {
+                    element: Element::Space(space.name().clone()),
+                    property: "volume".to_string(),
+                });
+            }
+        }
+
+        let mut checked: Vec<&String> = Vec::new();
+        for s in model.surfaces.iter() {
+            ret.check_surface(
+                model,
+                Element::Surface(s.name().clone()),
+                &s.vertices,
+                s.area(),
+                [&s.front_boundary, &s.back_boundary],
+            );
+            if !checked.contains(&&s.construction) {
+                checked.push(&s.construction);
+                ret.check_construction(model, &s.construction, main_dt, options);
+            }
+        }
+        for s in model.fenestrations.iter() {
+            ret.check_surface(
+                model,
+                Element::Fenestration(s.name().clone()),
+                &s.vertices,
+                s.area(),
+                [&s.front_boundary, &s.back_boundary],
+            );
+            if !checked.contains(&&s.construction) {
+                checked.push(&s.construction);
+                ret.check_construction(model, &s.construction, main_dt, options);
+            }
+        }
+
+        for hvac in model.hvacs.iter() {
+            if let Err(e) = ThermalHVAC::from(hvac, model) {
+                ret.push(Diagnostic::Error(e));
+            }
+        }
+        for luminaire in model.luminaires.iter() {
+            if let Err(e) = ThermalLuminaire::from(luminaire, model) {
+                ret.push(Diagnostic::Error(e.into()));
+            }
+        }
+
+        ret
+    }
+
+    /// Iterates over the diagnostics with a [`Severity::Warning`]
+    pub fn warnings(&self) -> impl Iterator<Item = &Diagnostic> {
+        self.diagnostics
+            .iter()
+            .filter(|d| d.severity() == Severity::Warning)
+    }
+
+    /// Iterates over the diagnostics with a [`Severity::Fatal`]
+    pub fn fatal(&self) -> impl Iterator<Item = &Diagnostic> {
+        self.diagnostics
+            .iter()
+            .filter(|d| d.severity() == Severity::Fatal)
+    }
+
+    /// Checks whether the model cannot be simulated
+    pub fn is_fatal(&self) -> bool {
+        self.fatal().next().is_some()
+    }
+
+    /// Registers a diagnostic, unless it had been registered before
+    fn push(&mut self, diagnostic: Diagnostic) {
+        if !self.diagnostics.contains(&diagnostic) {
+            self.diagnostics.push(diagnostic)
+        }
+    }
+
+    /// Checks the geometry and boundaries of a surface or fenestration
+    fn check_surface(
+        &mut self,
+        model: &SimpleModel,
+        element: Element,
+        polygon: &Polygon3D,
+        area: Float,
+        boundaries: [&Boundary; 2],
+    ) {
+        if area.is_nan() || area <= 1e-9 {
+            self.push(Diagnostic::ZeroArea(element.clone()));
+        }
+        if let Err(e) = polygon.outer().perimeter() {
+            self.push(Diagnostic::Error(ThermalError::model_definition(
+                element.clone(),
+                e,
+            )));
+        }
+        if let Err(e) = polygon.outer().centroid() {
+            self.push(Diagnostic::Error(ThermalError::model_definition(
+                element.clone(),
+                e,
+            )));
+        }
+
+        for (i, boundary) in boundaries.iter().enumerate() {
+            match boundary {
+                Boundary::Space { space } => {
+                    if !model.spaces.iter().any(|s| s.name() == space) {
+                        self.push(Diagnostic::UnmatchedBoundary {
+                            element: element.clone(),
+                            front: i == 0,
+                            space: space.clone(),
+                        });
+                    }
+                }
+                Boundary::Ground => {
+                    self.push(Diagnostic::Error(ThermalError::model_definition(
+                        element.clone(),
+                        "Ground boundaries are not yet supported",
+                    )));
+                }
+                Boundary::AmbientTemperature { .. } | Boundary::Outdoor => {}
+            }
+        }
+    }
+
+    /// Checks the materials of a construction, and how it is discretized
+    fn check_construction(
+        &mut self,
+        model: &SimpleModel,
+        name: &str,
+        main_dt: Float,
+        options: &ThermalModelOptions,
+    ) {
+        let construction = match model.get_construction(name) {
+            Ok(c) => c,
+            Err(e) => return self.push(Diagnostic::Error(e.into())),
+        };
+        let mut substances = Vec::with_capacity(construction.materials.len());
+        for mat_name in construction.materials.iter() {
+            match model.get_material_substance(mat_name) {
+                Ok(s) => substances.push(s),
+                Err(e) => return self.push(Diagnostic::Error(e.into())),
+            }
+        }
+
+        // Thermal properties... without them, the construction cannot be discretized
+        let mut complete = true;
+        for (mat_name, substance) in construction.materials.iter().zip(substances.iter()) {
+            if let Substance::Normal(s) = substance {
+                for (property, found) in [
+                    ("thermal conductivity", s.thermal_conductivity().is_ok()),
+                    ("density", s.density().is_ok()),
+                    ("specific heat capacity", s.specific_heat_capacity().is_ok()),
+                ] {
+                    if !found {
+                        complete = false;
+                        self.push(Diagnostic::MissingProperty {
+                            element: Element::Material(mat_name.clone()),
+                            property: property.to_string(),
+                        });
+                    }
+                }
+            }
+        }
+
+        // Optical properties of the layers that face the surroundings or a cavity
+        let n_layers = substances.len();
+        for (i, (mat_name, substance)) in construction
+            .materials
+            .iter()
+            .zip(substances.iter())
+            .enumerate()
+        {
+            let s = match substance {
+                Substance::Normal(s) => s,
+                Substance::Gas(_) => continue,
+            };
+            let faces_front = i == 0 || matches!(substances[i - 1], Substance::Gas(_));
+            let faces_back = i + 1 == n_layers || matches!(substances[i + 1], Substance::Gas(_));
+            let mut defaults = Vec::new();
+            if faces_front && s.front_thermal_absorbtance().is_err() {
+                defaults.push(("front thermal absorbtance", options.default_emissivity));
+            }
+            if faces_back && s.back_thermal_absorbtance().is_err() {
+                defaults.push(("back thermal absorbtance", options.default_emissivity));
+            }
+            if i == 0 && s.front_solar_absorbtance().is_err() {
+                defaults.push(("front solar absorbtance", DEFAULT_SOLAR_ABSORBTANCE));
+            }
+            if i + 1 == n_layers && s.back_solar_absorbtance().is_err() {
+                defaults.push(("back solar absorbtance", DEFAULT_SOLAR_ABSORBTANCE));
+            }
+            for (property, value) in defaults {
+                self.push(Diagnostic::DefaultProperty {
+                    element: Element::Material(mat_name.clone()),
+                    property: property.to_string(),
+                    value,
+                });
+            }
+        }
+        if !complete {
+            return;
+        }
+
+        // Discretization... the height and angle only matter for cavities,
+        // which are not checked here
+        match Discretization::new(&construction, model, main_dt, options, 1., 0.) {
+            Ok(d) => {
+                self.push(Diagnostic::TimestepSubdivisions {
+                    construction: construction.name().clone(),
+                    subdivisions: d.tstep_subdivision,
+                });
+                for (layer, n) in d.n_elements.iter().enumerate() {
+                    if *n == 0 {
+                        if let Substance::Normal(_) = substances[layer] {
+                            self.push(Diagnostic::LumpedLayer {
+                                construction: construction.name().clone(),
+                                layer,
+                                material: construction.materials[layer].clone(),
+                            });
+                        }
+                    }
+                }
+            }
+            Err(e) => self.push(Diagnostic::Error(e)),
+        }
+
+        // Solar properties
+        for layers in [
+            Glazing::get_front_glazing_layers(&construction, model),
+            Glazing::get_back_glazing_layers(&construction, model),
+        ] {
+            if let Err(e) = layers {
+                self.push(Diagnostic::Error(e));
+            }
+        }
+    }
+}
+
+/***********/
+/* TESTING */
+/***********/
+
+#[cfg(test)]
+mod testing {
+    use super::*;
+    use geometry3d::{Loop3D, Point3D};
+    use simple_model::{
+        substance::Normal as NormalSubstance, Construction, Material, SimpleModel, Surface,
+    };
+
+    #[test]
+    fn test_diagnose() {
+        let mut model = SimpleModel::default();
+
+        let mut concrete = NormalSubstance::new("concrete".to_string());
+        concrete
+            .set_density(2400.)
+            .set_specific_heat_capacity(880.)
+            .set_thermal_conductivity(1.4)
+            .set_front_thermal_absorbtance(0.9)
+            .set_front_solar_absorbtance(0.7)
+            .set_back_solar_absorbtance(0.7);
+        let concrete = model.add_substance(concrete.wrap());
+
+        let mut aluminium = NormalSubstance::new("aluminium".to_string());
+        aluminium
+            .set_density(2700.)
+            .set_specific_heat_capacity(900.)
+            .set_thermal_conductivity(200.);
+        let aluminium = model.add_substance(aluminium.wrap());
+
+        let concrete = model.add_material(Material::new(
+            "concrete".to_string(),
+            concrete.name().clone(),
+            0.1,
+        ));
+        let aluminium = model.add_material(Material::new(
+            "aluminium".to_string(),
+            aluminium.name().clone(),
+            0.001,
+        ));
+
+        let mut c = Construction::new("wall".to_string());
+        c.materials.push(concrete.name().clone());
+        c.materials.push(aluminium.name().clone());
+        let c = model.add_construction(c);
+
+        let mut the_loop = Loop3D::new();
+        the_loop.push(Point3D::new(0., 0., 0.)).unwrap();
+        the_loop.push(Point3D::new(1., 0., 0.)).unwrap();
+        the_loop.push(Point3D::new(1., 1., 0.)).unwrap();
+        the_loop.push(Point3D::new(0., 1., 0.)).unwrap();
+        the_loop.close().unwrap();
+        let p = Polygon3D::new(the_loop).unwrap();
+        let s = Surface::new(
+            "Surface 1",
+            p,
+            c.name(),
+            Boundary::Outdoor,
+            Boundary::Space {
+                space: "Nowhere".to_string(),
+            },
+        );
+        model.add_surface(s);
+
+        let report = DiagnosticsReport::new(&model, 3600., &ThermalModelOptions::default());
+        assert!(report.is_fatal());
+        assert_eq!(report.fatal().count(), 1);
+        assert!(report.diagnostics.contains(&Diagnostic::UnmatchedBoundary {
+            element: Element::Surface("Surface 1".to_string()),
+            front: false,
+            space: "Nowhere".to_string(),
+        }));
+
+        // The aluminium is too thin (and conductive) to be discretized
+        assert!(report.diagnostics.contains(&Diagnostic::LumpedLayer {
+            construction: "wall".to_string(),
+            layer: 1,
+            material: "aluminium".to_string(),
+        }));
+        assert!(report.diagnostics.iter().any(|d| matches!(
+            d,
+            Diagnostic::TimestepSubdivisions { construction, .. } if construction == "wall"
+        )));
+
+        // Its back emissivity and solar absorbtance were not given
+        assert!(report.diagnostics.contains(&Diagnostic::DefaultProperty {
+            element: Element::Material("aluminium".to_string()),
+            property: "back thermal absorbtance".to_string(),
+            value: 0.84,
+        }));
+        assert!(report.diagnostics.contains(&Diagnostic::DefaultProperty {
+            element: Element::Material("aluminium".to_string()),
+            property: "back solar absorbtance".to_string(),
+            value: DEFAULT_SOLAR_ABSORBTANCE,
+        }));
+        // ... while the concrete only faces the front
+        assert!(!report.diagnostics.iter().any(|d| matches!(
+            d,
+            Diagnostic::DefaultProperty { element, .. } if *element == Element::Material("concrete".to_string())
+        )));
+        assert_eq!(report.warnings().count(), 3);
+    }
+}
@@ -20,8 +20,13 @@ SOFTWARE.
 
 pub(crate) const MAX_RS: Float = 0.05;
 use crate::convection::ConvectionParams;
+use crate::error::{Element, ThermalError};
+use crate::model::ThermalModelOptions;
 use crate::Float;
-use crate::{cavity::Cavity, surface::ChunkMemory};
+use crate::{
+    cavity::{Cavity, CavityAirflow, CavitySide, VentilatedCavity},
+    surface::ChunkMemory,
+};
 use matrix::Matrix;
 use simple_model::{Construction, SimpleModel, Substance};
 use std::sync::Arc;
@@ -36,6 +41,9 @@ pub enum UValue {
     /// A cavity, comprised of a gas
     Cavity(Box<Cavity>),
 
+    /// A cavity that exchanges air with one of the sides of the surface
+    VentilatedCavity(Box<VentilatedCavity>),
+
     /// The resistance is a surface coefficient.
     Back,
 
@@ -44,13 +52,59 @@ pub enum UValue {
 }
 
 impl UValue {
-    /// Gets the U-value of a `UValue` object
-    pub fn u_value(&self, t_before: Float, t_after: Float) -> Float {
+    /// Gets the U-value of a `UValue` object.
+    ///
+    /// The U-value of a ventilated cavity depends on the temperature and speed of the
+    /// air entering it, so this assumes that the air enters at the mean temperature of
+    /// the cavity (i.e., $`(T_{before}+T_{after})/2`$) and that there is no wind. The
+    /// air stream then carries heat from the warm side to the cold one. Use
+    /// [`UValue::conductances`] when simulating.
+    pub fn u_value(&self, t_before: Float, t_after: Float) -> Result<Float, ThermalError> {
+        match self {
+            Self::Solid(u) => Ok(*u),
+            Self::Cavity(c) => Ok(c.u_value(t_before, t_after)),
+            Self::VentilatedCavity(c) => {
+                let t_in = (t_before + t_after) / 2.;
+                let u = c.u_value(t_before, t_after, t_in, 0.0);
+                let g = c.air_conductance(t_before, t_after, t_in, 0.0);
+                Ok(u + g / 2.)
+            }
+            Self::Back => Ok(0.), // This should be calculated appart
+            Self::None => Err(ThermalError::model_definition(
+                Element::Model,
+                "attempting to get the u-value of a segment whose U-value is undefined",
+            )),
+        }
+    }
+
+    /// Gets the U-value of a `UValue` object, as well as the conductance `g` that
+    /// connects each of the two nodes it joins with an air stream at a temperature `t_air`.
+    ///
+    /// The latter is only relevant for ventilated cavities (i.e., `g` is Zero otherwise),
+    /// for which the temperature and speed of the air entering them are taken from the
+    /// `front_env` or the `back_env`, depending on their inlet.
+    pub fn conductances(
+        &self,
+        t_before: Float,
+        t_after: Float,
+        front_env: &ConvectionParams,
+        back_env: &ConvectionParams,
+    ) -> Result<(Float, Float, Float), ThermalError> {
         match self {
-            Self::Solid(u) => *u,
-            Self::Cavity(c) => c.u_value(t_before, t_after),
-            Self::Back => 0., // This should be calculated appart
-            Self::None => panic!("Attempting to get the u-value of None"),
+            Self::VentilatedCavity(c) => {
+                let env = match c.inlet {
+                    CavitySide::Front => front_env,
+                    CavitySide::Back => back_env,
+                };
+                let t_in = env.air_temperature;
+                let wind_speed = env.air_speed;
+                Ok((
+                    c.u_value(t_before, t_after, t_in, wind_speed),
+                    c.air_conductance(t_before, t_after, t_in, wind_speed),
+                    t_in,
+                ))
+            }
+            _ => Ok((self.u_value(t_before, t_after)?, 0.0, 0.0)),
         }
     }
 }
@@ -61,6 +115,18 @@ impl std::default::Default for UValue {
     }
 }
 
+/// Splits a layer of a certain `thickness` into `n` elements. Their thickness grows
+/// geometrically—by a factor of `grading`—from both faces of the layer towards its
+/// middle, so the mesh is finer near the surfaces and the interfaces between layers,
+/// where gradients are steeper. A `grading` of 1 produces elements of equal thickness.
+pub(crate) fn element_thicknesses(thickness: Float, n: usize, grading: Float) -> Vec<Float> {
+    let weights: Vec<Float> = (0..n)
+        .map(|i| grading.powi(i.min(n - 1 - i) as i32))
+        .collect();
+    let total: Float = weights.iter().sum();
+    weights.into_iter().map(|w| thickness * w / total).collect()
+}
+
 /// Represents the discretization of a [`Construction`] for heat transfer
 /// calculation purposes.
 ///
@@ -91,28 +157,118 @@ impl Discretization {
     ///
     /// It first calculates the `tstep_subdivision` and the number of elements
     /// on each layer of Construction by calling `discretize_construction()`; and then builds the
-    /// `Discretization` by calling `build()`.
+    /// `Discretization` by calling `build()`. The maximum element thickness, the minimum
+    /// sub-timestep, the grading of the mesh and the default emissivity are taken from `options`.
     pub fn new(
         construction: &Arc<Construction>,
         model: &SimpleModel,
         model_dt: Float,
-        max_dx: Float,
-        min_dt: Float,
+        options: &ThermalModelOptions,
         height: Float,
         angle: Float,
-    ) -> Result<Self, String> {
+    ) -> Result<Self, ThermalError> {
+        if construction.materials.is_empty() {
+            return Err(ThermalError::model_definition(
+                Element::Construction(construction.name().clone()),
+                "it has no materials",
+            ));
+        }
         let (tstep_subdivision, n_elements) =
-            Self::discretize_construction(construction, model, model_dt, max_dx, min_dt)?;
+            Self::discretize_construction(construction, model, model_dt, options)?;
         Self::build(
             construction,
             model,
             tstep_subdivision,
             n_elements,
+            options,
             height,
             angle,
         )
     }
 
+    /// Gets the index of the first segment (i.e., node) of a layer
+    pub fn layer_first_segment(&self, layer_index: usize) -> usize {
+        self.n_elements
+            .iter()
+            .take(layer_index)
+            .map(|n| if *n == 0 { 1 } else { *n })
+            .sum()
+    }
+
+    /// Transforms the sealed cavity in the layer `layer_index` of the construction
+    /// into a [`VentilatedCavity`].
+    pub fn ventilate_cavity(
+        &mut self,
+        layer_index: usize,
+        airflow: CavityAirflow,
+        inlet: CavitySide,
+        outlet: CavitySide,
+    ) -> Result<(), ThermalError> {
+        if layer_index >= self.n_elements.len() {
+            return Err(ThermalError::model_definition(
+                Element::Model,
+                format!(
+                    "trying to ventilate layer {} of a construction with {} layers",
+                    layer_index,
+                    self.n_elements.len()
+                ),
+            ));
+        }
+        let i = self.layer_first_segment(layer_index);
+        let cavity = match &self.segments[i].1 {
+            UValue::Cavity(c) => (**c).clone(),
+            UValue::VentilatedCavity(c) => c.cavity.clone(),
+            _ => {
+                return Err(ThermalError::model_definition(
+                    Element::Model,
+                    format!(
+                        "trying to ventilate layer {}, which is not a cavity",
+                        layer_index
+                    ),
+                ))
+            }
+        };
+        self.segments[i].1 = UValue::VentilatedCavity(Box::new(VentilatedCavity {
+            cavity,
+            airflow,
+            inlet,
+            outlet,
+        }));
+        Ok(())
+    }
+
+    /// Calculates the air leaving all the ventilated cavities in
+    /// this `Discretization`, given the temperatures of the nodes.
+    ///
+    /// Returns a vector containing, for each ventilated cavity, the side of the surface
+    /// to which the air is delivered, the mass flow times heat capacity of the air
+    /// (in $`W/m^2.K`$) and the temperature at which it leaves the cavity.
+    pub fn ventilated_cavities_outflow(
+        &self,
+        temperatures: &Matrix,
+        front_env: &ConvectionParams,
+        back_env: &ConvectionParams,
+    ) -> Result<Vec<(CavitySide, Float, Float)>, ThermalError> {
+        let mut ret = Vec::new();
+        for (i, (_, uvalue)) in self.segments.iter().enumerate() {
+            if let UValue::VentilatedCavity(c) = uvalue {
+                let env = match c.inlet {
+                    CavitySide::Front => front_env,
+                    CavitySide::Back => back_env,
+                };
+                let t_in = env.air_temperature;
+                let wind_speed = env.air_speed;
+                let t_before = temperatures.get(i, 0)?;
+                let t_after = temperatures.get(i + 1, 0)?;
+
+                let t_out = c.outlet_temperature(t_before, t_after, t_in, wind_speed);
+                let m_cp = c.air_heat_capacity_flow(t_before, t_after, t_in, wind_speed);
+                ret.push((c.outlet, m_cp, t_out))
+            }
+        }
+        Ok(ret)
+    }
+
     /// Auxiliary function for `get_chunks()` function
     fn chunk_segments(&self, indexes: &[usize]) -> Vec<(usize, usize)> {
         if indexes.is_empty() {
@@ -159,15 +315,19 @@ impl Discretization {
         (mass, nomass)
     }
 
-    /// Creates the `segments` of the `Discretization`.
+    /// Creates the `segments` of the `Discretization`. The elements of each layer
+    /// are graded according to `options.mesh_grading` (see [`element_thicknesses`]), and
+    /// cavities bounded by materials without a thermal emissivity use
+    /// `options.default_emissivity`.
     fn build(
         construction: &Arc<Construction>,
         model: &SimpleModel,
         tstep_subdivision: usize,
         n_elements: Vec<usize>,
+        options: &ThermalModelOptions,
         height: Float,
         angle: Float,
-    ) -> Result<Self, String> {
+    ) -> Result<Self, ThermalError> {
         debug_assert_eq!(n_elements.len(), construction.materials.len());
 
         // Let's start with an empty set of segments
@@ -177,6 +337,18 @@ impl Discretization {
         // n_nodes = n_nodes.max(construction.materials.len() + 1); // At least one per layer... but Zero means  "no_mass wall"
 
         let mut segments: Vec<(Float, UValue)> = vec![(0.0, UValue::default()); n_nodes];
+        let invalid = |message: String| {
+            ThermalError::model_definition(
+                Element::Construction(construction.name().clone()),
+                message,
+            )
+        };
+        let missing = |material: &str, property: &str| {
+            ThermalError::missing_data(
+                Element::Material(material.to_string()),
+                format!("its substance has no '{}'", property),
+            )
+        };
 
         let mut n_segment = 0;
         for (n_layer, n) in n_elements.iter().enumerate() {
@@ -186,16 +358,17 @@ impl Discretization {
             let material = model.get_material(mat_name)?;
             let substance = model.get_substance(&material.substance)?;
 
-            // get the mass of each segment.
-            let mass = if n == 0 {
+            // get the heat capacity (per unit volume) of the segments.
+            let rho_cp = if n == 0 {
                 0.0
             } else {
                 match &substance {
                     Substance::Normal(s) => {
-                        let dx = material.thickness / n as Float;
-                        let rho = s.density()?;
-                        let cp = s.specific_heat_capacity()?;
-                        rho * cp * dx
+                        let rho = s.density().map_err(|_| missing(mat_name, "density"))?;
+                        let cp = s
+                            .specific_heat_capacity()
+                            .map_err(|_| missing(mat_name, "specific heat capacity"))?;
+                        rho * cp
                     }
                     Substance::Gas(_s) => 0.0, // should be zero... so should have been captured earlier
                 }
@@ -205,16 +378,18 @@ impl Discretization {
                 n = 1;
             }
             // Now iterate all segments...
-            for _ in 0..n {
+            for dx in element_thicknesses(material.thickness, n, options.mesh_grading) {
                 match &substance {
                     Substance::Normal(s) => {
                         // Add mass to this and next nodes (if it is NoMass, it is Zero)
+                        let mass = rho_cp * dx;
                         segments[n_segment].0 += mass / 2.;
                         segments[n_segment + 1].0 += mass / 2.;
 
                         // Add resistance
-                        let dx = material.thickness / n as Float;
-                        let k = s.thermal_conductivity()?;
+                        let k = s
+                            .thermal_conductivity()
+                            .map_err(|_| missing(mat_name, "thermal conductivity"))?;
                         // Push U-value
                         segments[n_segment].1 = UValue::Solid(k / dx);
                     }
@@ -233,18 +408,15 @@ impl Discretization {
                                 crate::gas::KRYPTON
                             }
                             _ => {
-                                return Err(format!(
-                                    "Substance '{}' does not have a standard gas.",
+                                return Err(invalid(format!(
+                                    "substance '{}' does not have a standard gas",
                                     substance.name()
-                                ))
+                                )))
                             }
                         };
                         if n_layer == 0 {
                             dbg!("This should be checked earlier.");
-                            return Err(format!(
-                                "Construction '{}' has a Gas as its first layer",
-                                construction.name
-                            ));
+                            return Err(invalid("it has a gas as its first layer".to_string()));
                         }
                         let prev_mat_name = construction.materials.get(n_layer - 1).unwrap(); // we already checked this
                                                                                               // let prev_mat = model.get_material(prev_mat_name)?;
@@ -252,25 +424,37 @@ impl Discretization {
                         let next_mat_name = match construction.materials.get(n_layer + 1) {
                             Some(v) => v,
                             None => {
-                                return Err(format!(
-                                    "Construction '{}' has a Gas as its last layer",
-                                    construction.name
-                                ))
+                                return Err(invalid("it has a gas as its last layer".to_string()))
                             }
                         };
                         // let next_mat = model.get_material(next_mat_name)?;
                         let next_substance = model.get_material_substance(next_mat_name)?;
                         let prev_substance = model.get_material_substance(prev_mat_name)?;
 
-                        const DEFAULT_EM: Float = 0.84;
-                        let ein = match &next_substance{
-                            Substance::Normal(s)=>s.front_thermal_absorbtance_or(crate::model::MODULE_NAME,DEFAULT_EM),
-                            Substance::Gas(_)=>return Err(format!("Construction '{}' has two gases without a solid layer between them", construction.name))
+                        let ein = match &next_substance {
+                            Substance::Normal(s) => s.front_thermal_absorbtance_or(
+                                crate::model::MODULE_NAME,
+                                options.default_emissivity,
+                            ),
+                            Substance::Gas(_) => {
+                                return Err(invalid(
+                                    "it has two gases without a solid layer between them"
+                                        .to_string(),
+                                ))
+                            }
                         };
 
                         let eout = match &prev_substance {
-                            Substance::Normal(s)=>s.back_thermal_absorbtance_or(crate::model::MODULE_NAME,DEFAULT_EM),
-                            Substance::Gas(_)=>return Err(format!("Construction '{}' has two gases without a solid layer between them", construction.name))
+                            Substance::Normal(s) => s.back_thermal_absorbtance_or(
+                                crate::model::MODULE_NAME,
+                                options.default_emissivity,
+                            ),
+                            Substance::Gas(_) => {
+                                return Err(invalid(
+                                    "it has two gases without a solid layer between them"
+                                        .to_string(),
+                                ))
+                            }
                         };
 
                         let c = Cavity {
@@ -299,23 +483,32 @@ impl Discretization {
 
     /// Calculates the R value of the whole system
     ///
-    /// # Panics
-    /// Panics if the calculated R value is Zero (i.e., if there are no
+    /// Cavities are evaluated at nominal conditions (i.e., with their
+    /// sides at 20 C and 10 C, without any airflow through ventilated ones).
+    /// Fails if the calculated R value is not positive (i.e., if there are no
     /// layers or something like that)
-    pub fn r_value(&self) -> Float {
+    pub fn r_value(&self) -> Result<Float, ThermalError> {
+        const T_FRONT: Float = 20.;
+        const T_BACK: Float = 10.;
         let mut r = 0.0;
 
         for (_, u_value) in &self.segments {
             r += match u_value {
-                UValue::Cavity(_c) => todo!(), //c.u_value(t_front, t_back),
+                UValue::Cavity(c) => 1. / c.u_value(T_FRONT, T_BACK),
+                UValue::VentilatedCavity(c) => 1. / c.cavity.u_value(T_FRONT, T_BACK),
                 UValue::Solid(v) => 1. / v,
-                UValue::Back => 0.0,
-                UValue::None => unreachable!(),
+                UValue::Back | UValue::None => 0.0,
             }
         }
 
-        assert!(r > 0.0, "Found Zero r-value");
-        r
+        if r > 0.0 {
+            Ok(r)
+        } else {
+            Err(ThermalError::numerical(
+                Element::Model,
+                format!("found a non-positive R-value ({})", r),
+            ))
+        }
     }
 
     /// Given a Maximum element thickness ($`\Delta x_{max}`$) and a minimum timestep ($`\Delta t_{min}`$), this function
@@ -411,18 +604,17 @@ impl Discretization {
         construction: &Arc<Construction>,
         model: &SimpleModel,
         model_dt: Float,
-        max_dx: Float,
-        min_dt: Float,
-    ) -> Result<(usize, Vec<usize>), String> {
+        options: &ThermalModelOptions,
+    ) -> Result<(usize, Vec<usize>), ThermalError> {
         // I could only think of how to make this recursively... so I did this.
         fn aux(
             construction: &Arc<Construction>,
             model: &SimpleModel,
             main_dt: Float,
             n: usize,
-            max_dx: Float,
-            min_dt: Float,
-        ) -> Result<(usize, Vec<usize>), String> {
+            options: &ThermalModelOptions,
+        ) -> Result<(usize, Vec<usize>), ThermalError> {
+            let (max_dx, min_dt, grading) = (options.max_dx, options.min_dt, options.mesh_grading);
             let dt = main_dt / (n as Float);
 
             // So, for each layer
@@ -439,9 +631,19 @@ impl Discretization {
                 let thickness = material.thickness;
                 let (k, rho, cp) = match substance {
                     Substance::Normal(s) => {
-                        let k = s.thermal_conductivity().map_err(|_x| "Trying to discretize a construction that contains a Normal Substance without a 'thermal conductivity'")?;
-                        let rho = s.density().map_err(|_x| "Trying to discretize a construction that contains a Normal Substance without a 'density'")?;
-                        let cp = s.specific_heat_capacity().map_err(|_x| "Trying to discretize a construction that contains a Normal Substance without a 'specific heat capacity'")?;
+                        let missing = |property: &str| {
+                            ThermalError::missing_data(
+                                Element::Material(mat_name.clone()),
+                                format!("its substance has no '{}'", property),
+                            )
+                        };
+                        let k = s
+                            .thermal_conductivity()
+                            .map_err(|_| missing("thermal conductivity"))?;
+                        let rho = s.density().map_err(|_| missing("density"))?;
+                        let cp = s
+                            .specific_heat_capacity()
+                            .map_err(|_| missing("specific heat capacity"))?;
                         (*k, *rho, *cp)
                     }
                     Substance::Gas(_) => {
@@ -472,24 +674,31 @@ impl Discretization {
                     let next_dt = main_dt / ((n + 1) as Float);
                     if next_dt > min_dt {
                         // If there is room for that, do it.
-                        return aux(construction, model, main_dt, n + 1, max_dx, min_dt);
+                        return aux(construction, model, main_dt, n + 1, options);
                     } else {
                         // otherwise, mark this layer as no-mass
                         n_elements.push(0);
                     }
                 } else {
-                    // subdivide the layer, making all the elements of equal thickness
-                    let m = (thickness / min_dx).floor();
+                    // subdivide the layer, making all the elements of equal thickness...
+                    let mut m = (thickness / min_dx).floor() as usize;
                     // this case belongs to the other branch of this if/else
-                    debug_assert!(m as usize != 0);
-                    let dx = thickness / m;
+                    debug_assert!(m != 0);
+                    // ... unless the mesh is graded, in which case the thinnest element
+                    // (at the faces of the layer) is the one that needs to be stable
+                    while m > 1 && element_thicknesses(thickness, m, grading)[0] < min_dx {
+                        m -= 1;
+                    }
+                    let dx = element_thicknesses(thickness, m, grading)
+                        .into_iter()
+                        .fold(0.0, Float::max);
                     if dx > max_dx {
                         // If the found dx is larger than the max allowed d_x, try to change timestep
                         // check if there is room for reducing dt...
                         let next_dt = main_dt / ((n + 1) as Float);
                         if next_dt > min_dt {
                             // If there is room for that, do it.
-                            return aux(construction, model, main_dt, n + 1, max_dx, min_dt);
+                            return aux(construction, model, main_dt, n + 1, options);
                         } else {
                             // otherwise, mark this layer as no-mass
                             n_elements.push(0);
@@ -497,7 +706,7 @@ impl Discretization {
                     } else {
                         // "dx" is smaller than max_dx, and thus this works
                         // fine.
-                        n_elements.push(m as usize)
+                        n_elements.push(m)
                     }
                 }
             }
@@ -524,7 +733,13 @@ impl Discretization {
                         Substance::Gas(_) => continue,
                     };
                     let dt = main_dt / n as Float;
-                    let dx = thickness / n_elements[n_layer] as Float;
+                    // the thinnest element is the least stable one
+                    let dx = match element_thicknesses(thickness, n_elements[n_layer], grading)
+                        .first()
+                    {
+                        Some(dx) => *dx,
+                        None => continue, // no-mass
+                    };
 
                     // assert!(alpha * dt / dx / dx <= 0.5);
                     let lambda1 = -dt / (MAX_RS * rho * cp * dx);
@@ -540,7 +755,7 @@ impl Discretization {
             // return
             Ok((n, n_elements))
         }
-        aux(construction, model, model_dt, 1, max_dx, min_dt)
+        aux(construction, model, model_dt, 1, options)
     }
 
     /// Produces $`\overline{K}`$ and $`\vec{q}`$ (as in the equation $`\overline{C} \dot{\vec{T}} =  \overline{K} \vec{T} + \vec{q}`$),
@@ -605,7 +820,7 @@ impl Discretization {
         back_hs: Float,
         back_rad_hs: Float,
         memory: &mut ChunkMemory,
-    ) -> Result<(), String> {
+    ) -> Result<(), ThermalError> {
         let (nrows, ncols) = temperatures.size();
         assert_eq!(
             ncols, 1,
@@ -636,7 +851,7 @@ impl Discretization {
             let t_this = temperatures.get(global_i, 0)?;
             let t_next = get_t_after(global_i);
             let (.., uvalue) = &self.segments[global_i];
-            let u = uvalue.u_value(t_this, t_next);
+            let (u, g, t_air) = uvalue.conductances(t_this, t_next, front_env, back_env)?;
 
             // Top left... should be there
             memory.k.add_to_element(local_i, local_i, -u)?;
@@ -652,6 +867,13 @@ impl Discretization {
             if let Ok(old_value) = memory.k.get(local_i + 1, local_i) {
                 memory.k.set(local_i + 1, local_i, old_value + u)?;
             }
+            // Air streams (i.e., ventilated cavities) connect both nodes to the air
+            if g > 0.0 {
+                memory.k.add_to_element(local_i, local_i, -g)?;
+                memory.q.add_to_element(local_i, 0, g * t_air)?;
+                memory.k.add_to_element(local_i + 1, local_i + 1, -g)?;
+                memory.q.add_to_element(local_i + 1, 0, g * t_air)?;
+            }
         }
 
         // Add front border conditions
@@ -668,17 +890,17 @@ impl Discretization {
             let (.., uvalue) = &self.segments[ini - 1];
             let t_before = temperatures.get(ini - 1, 0)?; // this should NEVER fail
             let t_after = temperatures.get(ini, 0)?; // this should NEVER fail
-            let u = uvalue.u_value(t_before, t_after);
+            let (u, g, t_air) = uvalue.conductances(t_before, t_after, front_env, back_env)?;
 
-            (u, u * t_before)
+            (u + g, u * t_before + g * t_air)
         };
 
-        memory.q.add_to_element(0, 0, front_q).unwrap();
-        memory.k.add_to_element(0, 0, -hs_front).unwrap();
+        memory.q.add_to_element(0, 0, front_q)?;
+        memory.k.add_to_element(0, 0, -hs_front)?;
 
         // Add back border conditions
         let (hs_back, back_q) = if fin == nrows {
-            let ts = temperatures.get(fin - 1, 0).unwrap();
+            let ts = temperatures.get(fin - 1, 0)?;
             // Solar radiation is added later because it also depends
             // on the solar absorption of different layers.
             let back_q = back_env.air_temperature * back_hs  // convection
@@ -689,9 +911,9 @@ impl Discretization {
             let (.., uvalue) = &self.segments[fin - 1];
             let t_before = temperatures.get(fin - 1, 0)?; // this should NEVER fail
             let t_after = get_t_after(fin - 1);
-            let u = uvalue.u_value(t_before, t_after);
+            let (u, g, t_air) = uvalue.conductances(t_before, t_after, front_env, back_env)?;
 
-            (u, u * t_after)
+            (u + g, u * t_after + g * t_air)
         };
         memory.q.add_to_element(nnodes - 1, 0, back_q)?;
         memory.k.add_to_element(nnodes - 1, nnodes - 1, -hs_back)?;
@@ -707,6 +929,7 @@ impl Discretization {
 #[cfg(test)]
 mod testing {
     use super::*;
+    use crate::convection::Roughness;
 
     impl std::default::Default for ConvectionParams {
         fn default() -> Self {
@@ -717,7 +940,7 @@ mod testing {
                 surface_temperature: DEFAULT_AIR_TEMP,
                 air_speed: 0.,
                 rad_temperature: DEFAULT_AIR_TEMP,
-                roughness_index: 1,
+                roughness: Roughness::default(),
                 cos_surface_tilt: 0.0,
             }
         }
@@ -762,7 +985,16 @@ mod testing {
         let tstep_sub = 10;
 
         let (model, construction) = get_normal(thermal_cond, density, cp, thickness);
-        let d = Discretization::build(&construction, &model, tstep_sub, vec![1], 1., 0.).unwrap();
+        let d = Discretization::build(
+            &construction,
+            &model,
+            tstep_sub,
+            vec![1],
+            &ThermalModelOptions::default(),
+            1.,
+            0.,
+        )
+        .unwrap();
         // normal --> linear
 
         assert_eq!(d.tstep_subdivision, tstep_sub);
@@ -799,7 +1031,16 @@ mod testing {
 
         let (model, construction) = get_normal(thermal_cond, density, cp, thickness);
 
-        let d = Discretization::build(&construction, &model, tstep_sub, vec![0], 1., 0.).unwrap();
+        let d = Discretization::build(
+            &construction,
+            &model,
+            tstep_sub,
+            vec![0],
+            &ThermalModelOptions::default(),
+            1.,
+            0.,
+        )
+        .unwrap();
 
         // normal --> linear
         assert_eq!(d.tstep_subdivision, tstep_sub);
@@ -829,6 +1070,68 @@ mod testing {
         }
     }
 
+    #[test]
+    fn test_element_thicknesses() {
+        let dx = element_thicknesses(0.1, 4, 1.);
+        assert!(dx.iter().all(|v| (v - 0.025).abs() < 1e-12));
+
+        let dx = element_thicknesses(0.1, 5, 2.);
+        // 1, 2, 4, 2, 1
+        assert!((dx[0] - 0.01).abs() < 1e-12);
+        assert!((dx[1] - 0.02).abs() < 1e-12);
+        assert!((dx[2] - 0.04).abs() < 1e-12);
+        assert!((dx[3] - dx[1]).abs() < 1e-12);
+        assert!((dx[4] - dx[0]).abs() < 1e-12);
+        assert!((dx.iter().sum::<Float>() - 0.1).abs() < 1e-12);
+
+        assert!(element_thicknesses(0.1, 0, 2.).is_empty());
+    }
+
+    #[test]
+    fn build_graded_mass() {
+        let thermal_cond = 1.3;
+        let density = 2100.;
+        let cp = 800.;
+        let thickness = 0.2;
+        let tstep_sub = 10;
+        let n = 6;
+
+        let (model, construction) = get_normal(thermal_cond, density, cp, thickness);
+        let options = ThermalModelOptions {
+            mesh_grading: 1.5,
+            ..ThermalModelOptions::default()
+        };
+        let d = Discretization::build(&construction, &model, tstep_sub, vec![n], &options, 1., 0.)
+            .unwrap();
+        assert_eq!(d.segments.len(), n + 1);
+
+        // The same total mass and resistance as a uniform mesh...
+        let mass: Float = d.segments.iter().map(|(m, _)| m).sum();
+        let exp_mass = density * cp * thickness;
+        assert!((mass - exp_mass).abs() < 1e-6 * exp_mass);
+        let r = d.r_value().unwrap();
+        assert!((r - thickness / thermal_cond).abs() < 1e-12);
+
+        // ... but finer near the surfaces
+        let u = |i: usize| match d.segments[i].1 {
+            UValue::Solid(u) => u,
+            _ => panic!("Expecting Solid!"),
+        };
+        assert!(u(0) > u(1));
+        assert!(u(1) > u(2));
+        assert!((u(0) - u(n - 1)).abs() < 1e-9 * u(0));
+        assert!(d.segments[0].0 < d.segments[1].0);
+        assert!((d.segments[0].0 - d.segments[n].0).abs() < 1e-9 * d.segments[0].0);
+
+        // When discretizing it, no element is thicker than allowed
+        let (_, n_elements) =
+            Discretization::discretize_construction(&construction, &model, 3600., &options)
+                .unwrap();
+        assert!(n_elements[0] > 0);
+        let dx = element_thicknesses(thickness, n_elements[0], options.mesh_grading);
+        assert!(dx.iter().all(|dx| *dx <= options.max_dx));
+    }
+
     #[test]
     fn build_normal_gas_normal_mass() {
         let thermal_cond = 1.;
@@ -883,8 +1186,16 @@ mod testing {
 
         // Test
         ///////////////////////////
-        let d =
-            Discretization::build(&construction, &model, tstep_sub, vec![1, 1, 1], 1., 0.).unwrap();
+        let d = Discretization::build(
+            &construction,
+            &model,
+            tstep_sub,
+            vec![1, 1, 1],
+            &ThermalModelOptions::default(),
+            1.,
+            0.,
+        )
+        .unwrap();
 
         // has gas --> linear
         assert_eq!(d.tstep_subdivision, tstep_sub);
@@ -939,6 +1250,31 @@ mod testing {
         } else {
             panic!("Expecting Solid!")
         }
+
+        // The cavity is evaluated at nominal conditions
+        let r = d.r_value().unwrap();
+        assert!(r > 2. * thickness / thermal_cond);
+
+        // Gases cannot be the first layer
+        let mut construction = simple_model::Construction::new("gas first");
+        construction.materials.push(gas.name().clone());
+        construction.materials.push(normal.name().clone());
+        let construction = model.add_construction(construction);
+        let err = Discretization::build(
+            &construction,
+            &model,
+            tstep_sub,
+            vec![1, 1],
+            &ThermalModelOptions::default(),
+            1.,
+            0.,
+        )
+        .unwrap_err();
+        assert!(matches!(err, ThermalError::ModelDefinition { .. }));
+        assert_eq!(
+            err.element(),
+            Some(&Element::Construction("gas first".to_string()))
+        );
     }
 
     #[test]
@@ -993,8 +1329,16 @@ mod testing {
 
         // Test
         ///////////////////////////
-        let d =
-            Discretization::build(&construction, &model, tstep_sub, vec![0, 0, 0], 1., 0.).unwrap();
+        let d = Discretization::build(
+            &construction,
+            &model,
+            tstep_sub,
+            vec![0, 0, 0],
+            &ThermalModelOptions::default(),
+            1.,
+            0.,
+        )
+        .unwrap();
 
         // has gas --> linear
         assert_eq!(d.tstep_subdivision, tstep_sub);
@@ -1108,6 +1452,116 @@ mod testing {
         return (d, temperatures, front_env, front_hs, back_env, back_hs);
     }
 
+    #[test]
+    fn test_get_q_k_ventilated_cavity() {
+        let u_solid = 10.;
+        let cavity = VentilatedCavity {
+            cavity: Cavity {
+                thickness: 0.05,
+                height: 3.,
+                gas: crate::gas::AIR,
+                eout: 0.84,
+                ein: 0.84,
+                angle: crate::PI / 2.,
+            },
+            airflow: CavityAirflow::Fixed(0.5),
+            inlet: CavitySide::Front,
+            outlet: CavitySide::Front,
+        };
+        let d = Discretization {
+            segments: vec![
+                (1., UValue::Solid(u_solid)),
+                (1., UValue::VentilatedCavity(Box::new(cavity.clone()))),
+                (1., UValue::Solid(u_solid)),
+                (1., UValue::Back),
+            ],
+            tstep_subdivision: 1,
+            n_elements: vec![1, 0, 1],
+        };
+        assert_eq!(d.layer_first_segment(1), 1);
+
+        let front_env = ConvectionParams {
+            air_temperature: 5.,
+            ..ConvectionParams::default()
+        };
+        let back_env = ConvectionParams::default();
+        let temperatures = Matrix::from_data(4, 1, vec![8., 10., 20., 22.]);
+        let mut memory = ChunkMemory {
+            aux: Matrix::new(0.0, 4, 1),
+            k: Matrix::new(0.0, 4, 4),
+            c: Matrix::new(0.0, 4, 4),
+            q: Matrix::new(0.0, 4, 1),
+            temps: Matrix::new(0.0, 4, 1),
+            k1: Matrix::new(0.0, 4, 1),
+            k2: Matrix::new(0.0, 4, 1),
+            k3: Matrix::new(0.0, 4, 1),
+            k4: Matrix::new(0.0, 4, 1),
+        };
+        d.get_k_q(
+            0,
+            4,
+            &temperatures,
+            &front_env,
+            1.,
+            1.,
+            &back_env,
+            1.,
+            1.,
+            &mut memory,
+        )
+        .unwrap();
+
+        let u = cavity.u_value(10., 20., 5., 0.0);
+        let g = cavity.air_conductance(10., 20., 5., 0.0);
+        assert!(g > 0.0);
+        for node in [1, 2] {
+            let found = memory.k.get(node, node).unwrap();
+            let exp = -u_solid - u - g;
+            assert!(
+                (found - exp).abs() < 1e-9,
+                "Expecting {exp}... found {found}"
+            );
+            let found = memory.q.get(node, 0).unwrap();
+            let exp = g * 5.;
+            assert!(
+                (found - exp).abs() < 1e-9,
+                "Expecting {exp}... found {found}"
+            );
+        }
+        let found = memory.k.get(1, 2).unwrap();
+        assert!((found - u).abs() < 1e-9, "Expecting {u}... found {found}");
+
+        // Without an inlet, the air is assumed to enter at the mean temperature
+        let (_, uvalue) = &d.segments[1];
+        let exp =
+            cavity.u_value(10., 20., 15., 0.0) + cavity.air_conductance(10., 20., 15., 0.0) / 2.;
+        let found = uvalue.u_value(10., 20.).unwrap();
+        assert!(
+            (found - exp).abs() < 1e-9,
+            "Expecting {exp}... found {found}"
+        );
+        let (found_u, found_g, t_air) = uvalue
+            .conductances(10., 20., &front_env, &back_env)
+            .unwrap();
+        assert!((found_u - u).abs() < 1e-9);
+        assert!((found_g - g).abs() < 1e-9);
+        assert!((t_air - 5.).abs() < 1e-9);
+
+        // The air leaves at the outlet temperature
+        let outflow = d
+            .ventilated_cavities_outflow(&temperatures, &front_env, &back_env)
+            .unwrap();
+        assert_eq!(outflow.len(), 1);
+        let (side, m_cp, t_out) = outflow[0];
+        assert_eq!(side, CavitySide::Front);
+        let exp = cavity.heat_to_air(10., 20., 5., 0.0);
+        let found = m_cp * (t_out - 5.);
+        assert!(
+            (found - exp).abs() < 1e-9,
+            "Expecting {exp}... found {found}"
+        );
+    }
+
     #[test]
     fn test_get_q_k_solid() {
         let n = 5;
@@ -0,0 +1,225 @@
+/*
+MIT License
+Copyright (c) 2021 Germán Molina
+Permission is hereby granted, free of charge, to any person obtaining a copy
+of this software and associated documentation files (the "Software"), to deal
+in the Software without restriction, including without limitation the rights
+to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+copies of the Software, and to permit persons to whom the Software is
+furnished to do so, subject to the following conditions:
+The above copyright notice and this permission notice shall be included in all
+copies or substantial portions of the Software.
+THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+SOFTWARE.
+*/
+
+use std::fmt;
+
+/// The element of the model (or of the inputs of the simulation)
+/// to which a [`ThermalError`] refers
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Element {
+    /// A `Surface`, by name
+    Surface(String),
+
+    /// A `Fenestration`, by name
+    Fenestration(String),
+
+    /// A `Construction`, by name
+    Construction(String),
+
+    /// A `Material`, by name
+    Material(String),
+
+    /// A `Space`, by name
+    Space(String),
+
+    /// An `HVAC`, by name
+    Hvac(String),
+
+    /// A `Luminaire`, by name
+    Luminaire(String),
+
+    /// The weather data
+    Weather,
+
+    /// The model as a whole (or the options used for simulating it)
+    Model,
+}
+
+impl fmt::Display for Element {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Surface(name) => write!(f, "Surface '{}'", name),
+            Self::Fenestration(name) => write!(f, "Fenestration '{}'", name),
+            Self::Construction(name) => write!(f, "Construction '{}'", name),
+            Self::Material(name) => write!(f, "Material '{}'", name),
+            Self::Space(name) => write!(f, "Space '{}'", name),
+            Self::Hvac(name) => write!(f, "HVAC '{}'", name),
+            Self::Luminaire(name) => write!(f, "Luminaire '{}'", name),
+            Self::Weather => write!(f, "Weather"),
+            Self::Model => write!(f, "Model"),
+        }
+    }
+}
+
+/// The errors returned by this crate
+#[derive(Debug, Clone, PartialEq)]
+pub enum ThermalError {
+    /// The model—or one of its elements—cannot be simulated as defined
+    /// (e.g., a construction whose first layer is a gas)
+    ModelDefinition {
+        /// The element that is not properly defined
+        element: Element,
+        /// What is wrong with it
+        message: String,
+    },
+
+    /// Some data required for simulating is missing (e.g., the volume of a
+    /// space, or the wind speed in the weather)
+    MissingData {
+        /// The element whose data is missing
+        element: Element,
+        /// What is missing
+        message: String,
+    },
+
+    /// The simulation failed numerically (e.g., a temperature became NaN)
+    Numerical {
+        /// The element where the failure was detected
+        element: Element,
+        /// What happened
+        message: String,
+    },
+
+    /// A checkpoint could not be written or read (e.g., the underlying
+    /// reader failed, or the checkpoint was written by a different model)
+    Checkpoint(String),
+
+    /// An error reported by another module or library, which report their
+    /// errors as plain text
+    Other(String),
+}
+
+impl ThermalError {
+    /// Creates a [`ThermalError::ModelDefinition`]
+    pub fn model_definition<S: Into<String>>(element: Element, message: S) -> Self {
+        Self::ModelDefinition {
+            element,
+            message: message.into(),
+        }
+    }
+
+    /// Creates a [`ThermalError::MissingData`]
+    pub fn missing_data<S: Into<String>>(element: Element, message: S) -> Self {
+        Self::MissingData {
+            element,
+            message: message.into(),
+        }
+    }
+
+    /// Creates a [`ThermalError::Numerical`]
+    pub fn numerical<S: Into<String>>(element: Element, message: S) -> Self {
+        Self::Numerical {
+            element,
+            message: message.into(),
+        }
+    }
+
+    /// Creates a [`ThermalError::Checkpoint`]
+    pub fn checkpoint<S: Into<String>>(message: S) -> Self {
+        Self::Checkpoint(message.into())
+    }
+
+    /// The element to which the error refers, if known
+    pub fn element(&self) -> Option<&Element> {
+        match self {
+            Self::ModelDefinition { element, .. }
+            | Self::MissingData { element, .. }
+            | Self::Numerical { element, .. } => Some(element),
+            Self::Checkpoint(_) | Self::Other(_) => None,
+        }
+    }
+}
+
+impl fmt::Display for ThermalError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::ModelDefinition { element, message } => {
+                write!(f, "{} is not properly defined: {}", element, message)
+            }
+            Self::MissingData { element, message } => {
+                write!(f, "{} is missing data: {}", element, message)
+            }
+            Self::Numerical { element, message } => {
+                write!(f, "Numerical error in {}: {}", element, message)
+            }
+            Self::Checkpoint(message) => write!(f, "Checkpoint error: {}", message),
+            Self::Other(message) => write!(f, "{}", message),
+        }
+    }
+}
+
+impl std::error::Error for ThermalError {}
+
+impl From<String> for ThermalError {
+    fn from(message: String) -> Self {
+        Self::Other(message)
+    }
+}
+
+impl From<&str> for ThermalError {
+    fn from(message: &str) -> Self {
+        Self::Other(message.to_string())
+    }
+}
+
+/// The [`communication_protocols::SimulationModel`] trait (and the other
+/// libraries in the SIMPLE ecosystem) report errors as `String`
+impl From<ThermalError> for String {
+    fn from(e: ThermalError) -> Self {
+        e.to_string()
+    }
+}
+
+/***********/
+/* TESTING */
+/***********/
+
+#[cfg(test)]
+mod testing {
+    use super::*;
+
+    #[test]
+    fn test_display() {
+        let e = ThermalError::model_definition(
+            Element::Construction("Wall".to_string()),
+            "it has a gas as its first layer",
+        );
+        assert_eq!(
+            e.to_string(),
+            "Construction 'Wall' is not properly defined: it has a gas as its first layer"
+        );
+        assert_eq!(e.element(), Some(&Element::Construction("Wall".into())));
+
+        let e = ThermalError::missing_data(Element::Weather, "no wind speed");
+        assert_eq!(e.to_string(), "Weather is missing data: no wind speed");
+
+        let e = ThermalError::checkpoint("expecting 3 zones, found 2");
+        assert_eq!(
+            e.to_string(),
+            "Checkpoint error: expecting 3 zones, found 2"
+        );
+        assert_eq!(e.element(), None);
+
+        let e: ThermalError = "something else".into();
+        assert_eq!(e.element(), None);
+        let s: String = e.into();
+        assert_eq!(s, "something else");
+    }
+}
@@ -212,11 +212,10 @@ fn nusselt(ra: Float, gamma: Float, a_gi: Float) -> Float {
     } else if gamma < 3. * THIRTY_RAD + EPSILON_RAD {
         // 90 degrees
         nu_90(ra, a_gi)
-    } else if gamma < 6. * THIRTY_RAD {
-        // between 90 and 180 degrees
-        nu_90_180(ra, a_gi, gamma)
     } else {
-        unreachable!("gamma is {}", gamma)
+        // between 90 and 180 degrees... a NaN gamma also ends up here, and
+        // propagates into the results
+        nu_90_180(ra, a_gi, gamma)
     }
 }
 
@@ -289,11 +288,9 @@ fn nu_90(ra: Float, a_gi: Float) -> Float {
     } else if ra < 5e4 {
         // Eq. 51
         0.028154 * ra.powf(0.4134)
-    } else if ra > 5e4 {
+    } else {
         // Eq. 50
         0.0673838 * ra.powf(1. / 3.)
-    } else {
-        unreachable!()
     };
     // Eq. 53
     let nu2 = 0.242 * (ra / a_gi).powf(0.272);
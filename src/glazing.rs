@@ -20,6 +20,7 @@ SOFTWARE.
 
 use simple_model::{Construction, SimpleModel, Substance};
 
+use crate::error::{Element, ThermalError};
 use crate::Float;
 
 /// An abstraction of a glazing layer for optical purposes.
@@ -46,15 +47,17 @@ pub struct Glazing {
 }
 
 impl Glazing {
-    /// Creates a new `Glazing`
-    pub fn new(tau: Float, rho_front: Float, rho_back: Float) -> Self {
-        assert!(tau >= 0.0, "Found transmittance less than Zero");
-        assert!(tau <= 1., "Found transmittance more than 1");
-        assert!(rho_front >= 0.0, "Found front reflectance less than 0.");
-        assert!(rho_back >= 0.0, "Found back reflectance less than 0");
-        assert!(rho_front <= 1., "Found front reflectance more than 1.");
-        assert!(rho_back <= 1., "Found back reflectance more than 1.");
+    /// Creates a new `Glazing`, failing if the transmittance or the reflectances
+    /// are not between 0 and 1
+    pub fn new(tau: Float, rho_front: Float, rho_back: Float) -> Result<Self, ThermalError> {
+        Self::check_properties(tau, rho_front, rho_back)
+            .map_err(|e| ThermalError::model_definition(Element::Model, e))?;
+        Ok(Self::from_properties(tau, rho_front, rho_back))
+    }
 
+    /// Creates a `Glazing` whose properties are known to be valid (e.g., because
+    /// they were derived from those of other valid layers)
+    fn from_properties(tau: Float, rho_front: Float, rho_back: Float) -> Self {
         Self {
             tau,
             rho_back,
@@ -64,32 +67,56 @@ impl Glazing {
         }
     }
 
+    /// Checks that the transmittance and the reflectances are between 0 and 1
+    fn check_properties(tau: Float, rho_front: Float, rho_back: Float) -> Result<(), String> {
+        let check = |name: &str, v: Float| -> Result<(), String> {
+            if (0.0..=1.).contains(&v) {
+                Ok(())
+            } else {
+                Err(format!(
+                    "Found {} of {}, which is not between 0 and 1",
+                    name, v
+                ))
+            }
+        };
+        check("transmittance", tau)?;
+        check("front reflectance", rho_front)?;
+        check("back reflectance", rho_back)
+    }
+
+    /// Gets the layers—and their index within the construction—that are reached by
+    /// the solar radiation, in the order given by the iterator. Gases are skipped, and
+    /// the iteration stops after the first opaque layer.
     fn get_glazing_from_iter<T>(
-        mut i: T,
+        i: T,
         model: &SimpleModel,
         cap: usize,
-    ) -> Result<Vec<Glazing>, String>
+    ) -> Result<Vec<(usize, Glazing)>, ThermalError>
     where
-        T: std::iter::Iterator<Item = String>,
+        T: std::iter::Iterator<Item = (usize, String)>,
     {
         let mut ret = Vec::with_capacity(cap);
-        loop {
-            // Get layer
-            let mat_name = i.next().unwrap();
+        for (layer_index, mat_name) in i {
             let sub = model.get_material_substance(&mat_name)?;
             match sub {
                 Substance::Gas(_) => {
-                    // if it is a gas, something went wong.
-                    panic!("NOT expecting a gas")
+                    // Gases are transparent and do not absorb solar radiation
+                    continue;
                 }
                 Substance::Normal(s) => {
-                    // if it is a normal, the push it
                     let tau = s.solar_transmittance().unwrap_or(&0.0);
                     let alpha_front = s.front_solar_absorbtance().unwrap_or(&0.84);
                     let alpha_back = s.back_solar_absorbtance().unwrap_or(&0.84);
                     let rho_front = 1. - tau - alpha_front;
                     let rho_back = 1. - tau - alpha_back;
-                    ret.push(Glazing::new(*tau, rho_front, rho_back));
+                    Glazing::check_properties(*tau, rho_front, rho_back).map_err(|e| {
+                        ThermalError::model_definition(
+                            Element::Material(mat_name.clone()),
+                            format!("its solar properties are inconsistent... {}", e),
+                        )
+                    })?;
+                    let g = Glazing::from_properties(*tau, rho_front, rho_back);
+                    ret.push((layer_index, g));
 
                     // if not translucent, then we are done.
                     if *tau < 1e-9 {
@@ -97,43 +124,53 @@ impl Glazing {
                     }
                 }
             }
-            // We only get here if we pushed somthing and need to continue... so, this should be a cavity... if any
-            if let Some(mat_name) = i.next() {
-                let sub = model.get_material_substance(&mat_name)?;
-                if let Substance::Normal(_) = sub {
-                    panic!("Expecting a Gas")
-                }
-            } else {
-                // Else, we are done
-                break;
-            }
         }
         Ok(ret)
     }
 
-    /// Gets the front translucent layers in a construction
-    pub fn get_front_glazing_system(
+    /// Gets the layers reached by the front solar radiation, from front to back,
+    /// along with their index within the construction.
+    pub fn get_front_glazing_layers(
         construction: &Construction,
         model: &SimpleModel,
-    ) -> Result<Vec<Glazing>, String> {
+    ) -> Result<Vec<(usize, Glazing)>, ThermalError> {
         if construction.materials.is_empty() {
-            return Err(format!(
-                "Trying to get front_glazing_system of an empty construction, called '{}'",
-                construction.name()
+            return Err(ThermalError::model_definition(
+                Element::Construction(construction.name().clone()),
+                "it has no materials",
             ));
         }
 
-        let i = construction.materials.iter().cloned();
+        let i = construction.materials.iter().cloned().enumerate();
+        Self::get_glazing_from_iter(i, model, construction.materials.len())
+    }
+
+    /// Gets the layers reached by the back solar radiation, from back to front,
+    /// along with their index within the construction.
+    pub fn get_back_glazing_layers(
+        construction: &Construction,
+        model: &SimpleModel,
+    ) -> Result<Vec<(usize, Glazing)>, ThermalError> {
+        let i = construction.materials.iter().cloned().enumerate().rev();
         Self::get_glazing_from_iter(i, model, construction.materials.len())
     }
 
+    /// Gets the front translucent layers in a construction
+    pub fn get_front_glazing_system(
+        construction: &Construction,
+        model: &SimpleModel,
+    ) -> Result<Vec<Glazing>, ThermalError> {
+        let layers = Self::get_front_glazing_layers(construction, model)?;
+        Ok(layers.into_iter().map(|(_, g)| g).collect())
+    }
+
     /// Gets the front back translucent in a construction
     pub fn get_back_glazing_system(
         construction: &Construction,
         model: &SimpleModel,
-    ) -> Result<Vec<Glazing>, String> {
-        let i = construction.materials.iter().cloned().rev();
-        Self::get_glazing_from_iter(i, model, construction.materials.len())
+    ) -> Result<Vec<Glazing>, ThermalError> {
+        let layers = Self::get_back_glazing_layers(construction, model)?;
+        Ok(layers.into_iter().map(|(_, g)| g).collect())
     }
 
     /// Gets the transmittance
@@ -161,6 +198,41 @@ impl Glazing {
         self.alpha_back
     }
 
+    /// Gets the properties of this `Glazing`—assumed to be given at normal
+    /// incidence—for beam radiation with an angle of incidence $`\theta`$
+    /// whose cosine is `cos_theta`.
+    ///
+    /// The transmittance follows the ASHRAE incidence angle modifier
+    /// (with $`b_0 = 0.1`$) and the reflectances follow Schlick's approximation
+    /// of Fresnel's equations:
+    ///
+    /// ```math
+    /// \tau(\theta) = \tau_0 \max\left(0, 1 - b_0 \left(\frac{1}{\cos(\theta)} - 1 \right) \right)
+    /// ```
+    ///
+    /// ```math
+    /// \rho(\theta) = \rho_0 + (1-\rho_0)(1 - \cos(\theta))^5
+    /// ```
+    ///
+    /// Opaque layers (i.e., $`\tau_0 = 0`$) are returned unchanged.
+    pub fn at_incidence(&self, cos_theta: Float) -> Self {
+        const B0: Float = 0.1;
+        if self.tau <= 0.0 {
+            return *self;
+        }
+        let c = cos_theta.clamp(0.0, 1.0);
+        if c < 1e-6 {
+            return Self::from_properties(0.0, 1.0, 1.0);
+        }
+        let tau = self.tau * (1. - B0 * (1. / c - 1.)).max(0.0);
+        let schlick = (1. - c).powi(5);
+        let rho = |rho_0: Float| -> Float {
+            let rho = rho_0 + (1. - rho_0) * schlick;
+            rho.min(1. - tau)
+        };
+        Self::from_properties(tau, rho(self.rho_front), rho(self.rho_back))
+    }
+
     /// Calculates the overall transmittance of a system of two glazing layers
     ///
     /// Source: ISO-9050/2003, Equation 2
@@ -214,7 +286,7 @@ impl Glazing {
         let rho_back = self.combined_rho_back(other);
         let rho_front = self.combined_rho_front(other);
         let tau = self.combined_tau(other);
-        Self::new(tau, rho_front, rho_back)
+        Self::from_properties(tau, rho_front, rho_back)
     }
 
     /// Combines several `Glazing` into a new `Glazing`
@@ -286,6 +358,193 @@ impl Glazing {
     }
 }
 
+/// The number of steps used for integrating angular properties
+/// over the hemisphere
+const N_HEMISPHERE_STEPS: usize = 90;
+
+/// The optical properties of a [`Glazing`] as a function of the
+/// angle of incidence
+#[derive(Debug, Clone)]
+pub enum AngularGlazing {
+    /// Uses standard angular curves, derived from the properties
+    /// at normal incidence (see [`Glazing::at_incidence`])
+    Standard(Glazing),
+
+    /// Linearly interpolates the properties given for several angles of
+    /// incidence, in degrees. The first element must be at normal incidence
+    /// (i.e., zero degrees), and angles must be sorted in ascending order.
+    Table(Vec<(Float, Glazing)>),
+}
+
+impl AngularGlazing {
+    /// Creates an [`AngularGlazing::Table`], checking that it is valid
+    pub fn new_table(table: Vec<(Float, Glazing)>) -> Result<Self, ThermalError> {
+        let invalid = |message: String| {
+            ThermalError::model_definition(
+                Element::Model,
+                format!("Angular glazing table is not valid... {}", message),
+            )
+        };
+        match table.first() {
+            None => return Err(invalid("it is empty".to_string())),
+            Some((angle, _)) => {
+                if angle.abs() > 1e-9 {
+                    return Err(invalid(format!(
+                        "it must start at normal incidence (0 degrees)... found {}",
+                        angle
+                    )));
+                }
+            }
+        }
+        for pair in table.windows(2) {
+            if pair[1].0 <= pair[0].0 || pair[1].0 > 90. {
+                return Err(invalid(format!(
+                    "its angles must be sorted in ascending order, between 0 and 90 degrees... found {} after {}",
+                    pair[1].0, pair[0].0
+                )));
+            }
+        }
+        Ok(Self::Table(table))
+    }
+
+    /// Gets the properties at normal incidence
+    pub fn normal(&self) -> Glazing {
+        match self {
+            Self::Standard(g) => *g,
+            Self::Table(t) => t[0].1,
+        }
+    }
+
+    /// Checks whether the properties of this layer depend on the angle
+    /// of incidence. Opaque layers are assumed not to.
+    pub fn is_angle_dependent(&self) -> bool {
+        match self {
+            Self::Standard(g) => g.tau > 0.0,
+            Self::Table(t) => t.len() > 1,
+        }
+    }
+
+    /// Gets the properties for beam radiation, given the cosine of the
+    /// angle of incidence
+    pub fn at_incidence(&self, cos_theta: Float) -> Glazing {
+        match self {
+            Self::Standard(g) => g.at_incidence(cos_theta),
+            Self::Table(t) => {
+                let angle = cos_theta.clamp(0.0, 1.0).acos().to_degrees();
+                let (last_angle, last) = t.last().unwrap(); // Tables are never empty
+                if angle >= *last_angle {
+                    return *last;
+                }
+                let i = t.iter().position(|(a, _)| *a > angle).unwrap(); // there is one, checked before
+                let (a0, g0) = t[i - 1];
+                let (a1, g1) = t[i];
+                let x = (angle - a0) / (a1 - a0);
+                let interp = |v0: Float, v1: Float| v0 + x * (v1 - v0);
+                Glazing::from_properties(
+                    interp(g0.tau, g1.tau),
+                    interp(g0.rho_front, g1.rho_front),
+                    interp(g0.rho_back, g1.rho_back),
+                )
+            }
+        }
+    }
+
+    /// Gets the properties for diffuse (i.e., isotropic) radiation, by
+    /// integrating the beam properties over the hemisphere.
+    ///
+    /// ```math
+    /// \tau_{dif} = \int_0^{\pi/2}{\tau(\theta) 2 \sin(\theta) \cos(\theta) d\theta} = \int_0^{1}{\tau(c) 2 c\, dc }
+    /// ```
+    pub fn diffuse(&self) -> Glazing {
+        if !self.is_angle_dependent() {
+            return self.normal();
+        }
+        let dc = 1. / N_HEMISPHERE_STEPS as Float;
+        let (mut tau, mut rho_front, mut rho_back) = (0.0, 0.0, 0.0);
+        for i in 0..N_HEMISPHERE_STEPS {
+            let c = (i as Float + 0.5) * dc;
+            let g = self.at_incidence(c);
+            let w = 2. * c * dc;
+            tau += g.tau * w;
+            rho_front += g.rho_front * w;
+            rho_back += g.rho_back * w;
+        }
+        Glazing::from_properties(tau, rho_front, rho_back)
+    }
+
+    /// Calculates the absorbtances of each layer of the system for beam
+    /// radiation with an angle of incidence whose cosine is `cos_theta`
+    /// (see [`Glazing::alphas`])
+    pub fn alphas_at_incidence(layers: &[AngularGlazing], cos_theta: Float) -> Vec<Float> {
+        let layers: Vec<Glazing> = layers.iter().map(|g| g.at_incidence(cos_theta)).collect();
+        Glazing::alphas(&layers)
+    }
+
+    /// Calculates the absorbtances of each layer of the system for
+    /// diffuse radiation (see [`Glazing::alphas`])
+    pub fn diffuse_alphas(layers: &[AngularGlazing]) -> Vec<Float> {
+        let layers: Vec<Glazing> = layers.iter().map(|g| g.diffuse()).collect();
+        Glazing::alphas(&layers)
+    }
+}
+
+/// One of the states of a [`SwitchableGlazing`]
+#[derive(Debug, Clone)]
+pub struct GlazingState {
+    /// The layers that receive the front solar radiation, from the
+    /// front to the back
+    pub front: Vec<AngularGlazing>,
+
+    /// The layers that receive the back solar radiation, from the
+    /// back to the front
+    pub back: Vec<AngularGlazing>,
+}
+
+/// Defines how a [`SwitchableGlazing`] chooses its state
+#[derive(Debug, Clone)]
+pub enum SwitchingControl {
+    /// The state is set at runtime, through the corresponding element of the
+    /// simulation state (see [`crate::model::ThermalModel::set_fenestration_glazing_state`])
+    Manual,
+
+    /// The state is chosen based on the front incident solar irradiance
+    /// (e.g., electrochromic glazing controlled by a sensor). The state index
+    /// is the number of thresholds (in $`W/m^2`$, ascending) that are exceeded.
+    IncidentSolar(Vec<Float>),
+
+    /// The state is chosen based on the mean temperature of the glazing (e.g.,
+    /// thermochromic glazing). The state index is the number of thresholds
+    /// (in $`C`$, ascending) that are exceeded.
+    Temperature(Vec<Float>),
+}
+
+/// A glazing system that can switch between several optical states (e.g.,
+/// electrochromic or thermochromic glazing)
+#[derive(Debug, Clone)]
+pub struct SwitchableGlazing {
+    /// The states. All of them must have the same number of layers
+    pub states: Vec<GlazingState>,
+
+    /// How the state is chosen
+    pub control: SwitchingControl,
+}
+
+impl SwitchableGlazing {
+    /// Selects the index of the state to use, given the front incident solar
+    /// irradiance, the mean temperature of the glazing and the manually set state.
+    pub fn select_state(&self, incident_solar: Float, temperature: Float, manual: usize) -> usize {
+        let last = self.states.len().saturating_sub(1);
+        let exceeded =
+            |thresholds: &[Float], v: Float| thresholds.iter().filter(|t| v > **t).count();
+        let i = match &self.control {
+            SwitchingControl::Manual => manual,
+            SwitchingControl::IncidentSolar(thresholds) => exceeded(thresholds, incident_solar),
+            SwitchingControl::Temperature(thresholds) => exceeded(thresholds, temperature),
+        };
+        i.min(last)
+    }
+}
+
 #[cfg(test)]
 mod testing {
 
@@ -429,17 +688,121 @@ mod testing {
         assert_eq!(props, exp);
     }
 
+    #[test]
+    fn test_at_incidence() {
+        let g = Glazing::new(0.8, 0.08, 0.08).unwrap();
+
+        // Normal incidence does not change anything
+        let n = g.at_incidence(1.0);
+        assert!((n.tau() - 0.8).abs() < 1e-9);
+        assert!((n.rho_front() - 0.08).abs() < 1e-9);
+
+        // Transmittance decreases and reflectance increases with the angle
+        let mut last = n;
+        for deg in [20., 40., 60., 80.] {
+            let cos: Float = (deg as Float).to_radians().cos();
+            let this = g.at_incidence(cos);
+            assert!(this.tau() < last.tau());
+            assert!(this.rho_front() > last.rho_front());
+            assert!(this.alpha_front() >= 0.0);
+            last = this;
+        }
+
+        // Grazing incidence
+        let grazing = g.at_incidence(0.0);
+        assert!(grazing.tau().abs() < 1e-9);
+        assert!((grazing.rho_front() - 1.0).abs() < 1e-9);
+
+        // Opaque layers do not change
+        let opaque = Glazing::new(0.0, 0.3, 0.3).unwrap();
+        let o = opaque.at_incidence(0.2);
+        assert!((o.rho_front() - 0.3).abs() < 1e-9);
+        assert!((o.alpha_front() - 0.7).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_new() {
+        assert!(Glazing::new(0.8, 0.08, 0.1).is_ok());
+        assert!(Glazing::new(1.2, 0.08, 0.1).is_err());
+        assert!(Glazing::new(0.8, -0.04, 0.1).is_err());
+        assert!(Glazing::new(0.8, 0.08, Float::NAN).is_err());
+    }
+
+    #[test]
+    fn test_angular_glazing() {
+        let g = Glazing::new(0.8, 0.08, 0.08).unwrap();
+
+        // Diffuse transmittance is lower than the normal one
+        let standard = AngularGlazing::Standard(g);
+        let dif = standard.diffuse();
+        assert!(
+            dif.tau() < 0.8 && dif.tau() > 0.5,
+            "tau_dif = {}",
+            dif.tau()
+        );
+
+        // Opaque layers are isotropic
+        let opaque = AngularGlazing::Standard(Glazing::new(0.0, 0.3, 0.3).unwrap());
+        assert!((opaque.diffuse().alpha_front() - 0.7).abs() < 1e-9);
+
+        // Tables
+        assert!(AngularGlazing::new_table(vec![]).is_err());
+        assert!(AngularGlazing::new_table(vec![(10., g)]).is_err());
+        assert!(AngularGlazing::new_table(vec![(0., g), (60., g), (30., g)]).is_err());
+        let table = AngularGlazing::new_table(vec![
+            (0., g),
+            (60., Glazing::new(0.6, 0.2, 0.2).unwrap()),
+            (90., Glazing::new(0.0, 1.0, 1.0).unwrap()),
+        ])
+        .unwrap();
+        let at_30 = table.at_incidence((30. as Float).to_radians().cos());
+        assert!((at_30.tau() - 0.7).abs() < 1e-9);
+        assert!((at_30.rho_front() - 0.14).abs() < 1e-9);
+        assert!((at_30.rho_back() - 0.14).abs() < 1e-9);
+
+        // Alphas are consistent with the single layer
+        let alphas = AngularGlazing::alphas_at_incidence(&[table.clone()], 0.5);
+        assert!((alphas[0] - table.at_incidence(0.5).alpha_front()).abs() < 1e-9);
+        let alphas = AngularGlazing::diffuse_alphas(&[table.clone()]);
+        assert!((alphas[0] - table.diffuse().alpha_front()).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_select_state() {
+        let clear = Glazing::new(0.6, 0.1, 0.1).unwrap();
+        let tinted = Glazing::new(0.05, 0.1, 0.1).unwrap();
+        let state = |g: Glazing| GlazingState {
+            front: vec![AngularGlazing::Standard(g)],
+            back: vec![AngularGlazing::Standard(g)],
+        };
+        let mut sw = SwitchableGlazing {
+            states: vec![state(clear), state(tinted)],
+            control: SwitchingControl::IncidentSolar(vec![300.]),
+        };
+        assert_eq!(sw.select_state(100., 50., 1), 0);
+        assert_eq!(sw.select_state(400., 0., 0), 1);
+
+        sw.control = SwitchingControl::Temperature(vec![30., 40.]);
+        assert_eq!(sw.select_state(1000., 25., 1), 0);
+        // there are only two states
+        assert_eq!(sw.select_state(0., 45., 0), 1);
+
+        sw.control = SwitchingControl::Manual;
+        assert_eq!(sw.select_state(0., 0., 1), 1);
+        assert_eq!(sw.select_state(0., 0., 5), 1);
+    }
+
     #[test]
     fn test_9050() {
         let tau1 = 0.1;
         let rho_b1 = 0.3;
         let rho_f1 = 0.13;
-        let g1 = Glazing::new(tau1, rho_f1, rho_b1);
+        let g1 = Glazing::new(tau1, rho_f1, rho_b1).unwrap();
 
         let tau2 = 0.21;
         let rho_b2 = 0.34;
         let rho_f2 = 0.1123;
-        let g2 = Glazing::new(tau2, rho_f2, rho_b2);
+        let g2 = Glazing::new(tau2, rho_f2, rho_b2).unwrap();
 
         // Eq. 2 of ISO9050/2003
         let tau12 = g1.combined_tau(&g2);
@@ -454,7 +817,7 @@ mod testing {
         let tau3 = 0.21;
         let rho_b3 = 0.34;
         let rho_f3 = 0.1123;
-        let g3 = Glazing::new(tau3, rho_f3, rho_b3);
+        let g3 = Glazing::new(tau3, rho_f3, rho_b3).unwrap();
 
         let g12 = g1.combine(&g2);
         let g13 = g12.combine(&g3);
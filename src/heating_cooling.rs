@@ -18,6 +18,7 @@ OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
 SOFTWARE.
 */
 
+use crate::error::{Element, ThermalError};
 use crate::Float;
 use simple_model::hvac::{ElectricHeater, IdealHeaterCooler, HVAC};
 use simple_model::{SimpleModel, SimulationState};
@@ -49,7 +50,7 @@ pub enum ThermalHVAC {
 
 impl ThermalHVAC {
     /// Builds a new [`ThermalHVAC`] from an HVAC and its location
-    pub fn from(hvac: &HVAC, model: &SimpleModel) -> Result<Self, String> {
+    pub fn from(hvac: &HVAC, model: &SimpleModel) -> Result<Self, ThermalError> {
         match hvac {
             HVAC::ElectricHeater(e) => {
                 let parent = (**e).clone();
@@ -61,10 +62,19 @@ impl ThermalHVAC {
                         });
                     }
                 }
-                Err(format!("ElectricHeater is supposed to be in a space called '{}'... but it was not found", parent.target_space()?))
+                Err(ThermalError::model_definition(
+                    Element::Hvac(parent.name().clone()),
+                    format!(
+                        "it is supposed to be in a space called '{}'... but it was not found",
+                        parent.target_space()?
+                    ),
+                ))
             }
-            HVAC::IdealHeaterCooler(_e) => {
-                todo!();
+            HVAC::IdealHeaterCooler(e) => {
+                Err(ThermalError::model_definition(
+                    Element::Hvac(e.name().clone()),
+                    "IdealHeaterCooler systems are not yet supported by the thermal model",
+                ))
                 // let parent = (**e).clone();
                 // let mut target_spaces: Vec<usize> = Vec::with_capacity(parent.target_spaces.len());
                 // let thermostat_space_name = e.thermostat_location()?;
@@ -96,14 +106,17 @@ impl ThermalHVAC {
     pub fn calc_cooling_heating_power(
         &self,
         state: &SimulationState,
-    ) -> Result<Vec<(usize, Float)>, String> {
+    ) -> Result<Vec<(usize, Float)>, ThermalError> {
         match self {
             Self::IdealHeaterCooler {
+                parent,
                 ..
-                // parent,
                 // target_spaces,
             } => {
-                todo!();
+                Err(ThermalError::model_definition(
+                    Element::Hvac(parent.name().clone()),
+                    "IdealHeaterCooler systems are not yet supported by the thermal model",
+                ))
                 // let mut ret = Vec::with_capacity(target_spaces.len());
 
                 // for index in target_spaces.iter() {
@@ -129,10 +142,12 @@ impl ThermalHVAC {
                     let consumption_power =
                         match parent.heating_cooling_consumption(state) {
                             Some(v) => v,
-                            None => return Err(format!(
-                                "Could not get Heating consumption if ElectricHeater called '{}'",
-                                parent.name()
-                            )),
+                            None => {
+                                return Err(ThermalError::missing_data(
+                                    Element::Hvac(parent.name().clone()),
+                                    "it has no heating consumption state",
+                                ))
+                            }
                         };
                     ret.push((*target_space_index, consumption_power))
                 }
@@ -52,6 +52,20 @@ pub const SIGMA: Float = 5.670374419e-8;
 /// solution
 pub mod model;
 
+/// For auditing the energy balances of zones and surfaces.
+pub mod audit;
+
+/// The errors reported by this crate.
+pub mod error;
+
+/// For validating a model before simulating it.
+pub mod diagnostics;
+
+/// For writing and reading checkpoints, in a simple little-endian binary format.
+/// Numbers are stored through their bit patterns, so that a simulation resumed from
+/// a checkpoint gives exactly the same results as one that was never interrupted.
+mod checkpoint;
+
 /// For dealing with Cavities, i.e., layers made out of a gas
 /// enclosed within two materials of different emissivities.
 pub mod cavity;
@@ -72,10 +86,24 @@ pub mod glazing;
 /// For HVAC-related calculations.
 pub mod heating_cooling;
 
+/// Shading devices, such as blinds, roller shades and screens.
+pub mod shading;
+/// For estimating the long-wave radiation exchanged between exterior
+/// surfaces and the sky and the ground.
+pub mod sky;
+
+/// For adjusting the weather data to the conditions at the site.
+pub mod site;
+/// For sizing heating and cooling equipment based on design days.
+pub mod sizing;
+
 /// For calculating heat transfer through all kinds of surfaces.
 pub mod surface;
 mod surface_trait;
 
+/// For calculating the position of the sun.
+mod sun;
+
 /// For calculating the temperatures within Spaces.
 pub mod zone;
 
@@ -17,23 +17,40 @@ LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
 OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
 SOFTWARE.
 */
+use crate::audit::{EnergyAudit, SurfaceEnergyBalance, ZoneEnergyBalance};
+use crate::cavity::CavitySide;
+use crate::checkpoint::{CheckpointReader, CheckpointWriter, MAGIC, VERSION};
+use crate::convection::{
+    ConvectionCoefficient, ExteriorConvectionAlgorithm, InteriorConvectionAlgorithm, Roughness,
+};
 use crate::discretization::Discretization;
 use crate::Float;
 use calendar::Date;
 
 use communication_protocols::{ErrorHandling, MetaOptions, SimulationModel};
 use geometry3d::Vector3D;
+use matrix::Matrix;
 use weather::Weather;
 
-use crate::surface::{SurfaceMemory, ThermalFenestration, ThermalSurface, ThermalSurfaceData};
+use crate::surface::{
+    polygon_height, SurfaceMemory, ThermalFenestration, ThermalSurface, ThermalSurfaceData,
+};
 use crate::surface_trait::SurfaceTrait;
 
+use crate::diagnostics::DiagnosticsReport;
+use crate::error::{Element, ThermalError};
 use crate::heating_cooling::ThermalHVAC;
 use crate::luminaire::ThermalLuminaire;
 
+use crate::shading::ShadingPosition;
+use crate::site::WeatherStation;
+use crate::sizing::{ideal_load, DesignDay, DesignDayWeather, SizingOptions, ZoneDesignLoads};
+use crate::sky::SkyModel;
+use crate::sun::sun_direction;
 use crate::zone::ThermalZone;
 use simple_model::{Boundary, SimpleModel, SimulationState, SimulationStateHeader};
 use std::borrow::Borrow;
+use std::io::{Read, Write};
 
 // #[cfg(feature = "parallel")]
 // use rayon::prelude::*;
@@ -41,13 +58,264 @@ use std::borrow::Borrow;
 /// The module name. For debugging purposes
 pub(crate) const MODULE_NAME: &str = "Thermal model";
 
+/// The temperatures of the zones and of the nodes of all surfaces and fenestrations,
+/// kept in memory so that the adaptive sub-timesteps can be undone (see
+/// [`ThermalModelOptions::adaptive`])
+#[derive(Debug, Clone)]
+struct TemperatureSnapshot {
+    zones: Vec<Float>,
+    surfaces: Vec<Matrix>,
+    fenestrations: Vec<Matrix>,
+}
+
+impl TemperatureSnapshot {
+    /// Allocates a snapshot with room for the temperatures of `n_zones` zones
+    /// and of the surfaces and fenestrations in `alloc`
+    fn new(n_zones: usize, alloc: &ThermalModelMemory) -> Self {
+        Self {
+            zones: vec![0.0; n_zones],
+            surfaces: alloc
+                .surfaces
+                .iter()
+                .map(|m| m.temperatures.clone())
+                .collect(),
+            fenestrations: alloc
+                .fenestrations
+                .iter()
+                .map(|m| m.temperatures.clone())
+                .collect(),
+        }
+    }
+
+    /// Calculates the largest absolute difference between the temperatures
+    /// of two snapshots
+    fn max_difference(&self, other: &Self) -> Result<Float, ThermalError> {
+        let mut ret = self
+            .zones
+            .iter()
+            .zip(other.zones.iter())
+            .map(|(a, b)| (a - b).abs())
+            .fold(0.0, Float::max);
+        for (a, b) in self
+            .surfaces
+            .iter()
+            .chain(self.fenestrations.iter())
+            .zip(other.surfaces.iter().chain(other.fenestrations.iter()))
+        {
+            let (rows, ..) = a.size();
+            for i in 0..rows {
+                ret = ret.max((a.get(i, 0)? - b.get(i, 0)?).abs());
+            }
+        }
+        Ok(ret)
+    }
+}
+
 /// The memory that this module requires, so we can allocate only once.
 #[derive(Debug, Clone)]
 pub struct ThermalModelMemory {
     surfaces: Vec<SurfaceMemory>,
     fenestrations: Vec<SurfaceMemory>,
+    audit: Option<EnergyAudit>,
+    /// The number of sub-timesteps per main timestep chosen by the
+    /// last adaptive step (see [`ThermalModelOptions::adaptive`])
+    adaptive_substeps: usize,
+}
+
+impl ThermalModelMemory {
+    /// The number of sub-timesteps per main timestep that the next main timestep will
+    /// start with when [`ThermalModelOptions::adaptive`] is enabled
+    pub fn adaptive_substeps(&self) -> usize {
+        self.adaptive_substeps
+    }
+
+    /// Retrieves the [`EnergyAudit`], if it was enabled
+    /// (see [`ThermalModel::enable_audit`])
+    pub fn audit(&self) -> Option<&EnergyAudit> {
+        self.audit.as_ref()
+    }
+
+    /// Stops auditing the energy balances, returning the [`EnergyAudit`]
+    /// (if it was enabled)
+    pub fn disable_audit(&mut self) -> Option<EnergyAudit> {
+        self.audit.take()
+    }
+}
+
+/// The options of the warm-up period, which is used for initializing the
+/// temperatures of the nodes and zones of a [`ThermalModel`] before the actual
+/// simulation begins (see [`ThermalModel::warmup`]).
+#[derive(Debug, Clone, Copy)]
+pub struct WarmUpOptions {
+    /// The minimum number of days to simulate, even if the temperatures
+    /// converge before that
+    pub min_days: usize,
+
+    /// The maximum number of days to simulate. If the temperatures have not
+    /// converged by then, an error is returned.
+    pub max_days: usize,
+
+    /// The largest difference (in $`C`$) allowed between the temperatures of each zone
+    /// and node at the end of two consecutive days for them to be considered converged.
+    pub tolerance: Float,
+}
+
+impl Default for WarmUpOptions {
+    fn default() -> Self {
+        Self {
+            min_days: 1,
+            max_days: 25,
+            tolerance: 0.04,
+        }
+    }
+}
+
+/// The options of a [`ThermalModel`], which allow trading accuracy for
+/// speed (see [`ThermalModel::new`]).
+#[derive(Debug, Clone, Copy)]
+pub struct ThermalModelOptions {
+    /// The maximum thickness of the elements in which the layers of
+    /// constructions are subdivided, in $`m`$
+    pub max_dx: Float,
+
+    /// The minimum sub-timestep, in seconds. Layers that would require
+    /// smaller sub-timesteps are treated as no-mass layers.
+    pub min_dt: Float,
+
+    /// The ratio between the thicknesses of consecutive elements within each layer of
+    /// the constructions, which grow from the faces of the layer towards its middle.
+    /// A value of 1 produces elements of equal thickness, while larger ones concentrate
+    /// the nodes near the surfaces and the interfaces between layers (see
+    /// [`crate::discretization::Discretization::new`]).
+    pub mesh_grading: Float,
+
+    /// The number by which the sub-timestep required by the constructions
+    /// is divided, just to be on the safe side.
+    pub safety_factor: usize,
+
+    /// The thermal emissivity of the materials that do not specify one
+    pub default_emissivity: Float,
+
+    /// The temperature (in $`C`$) of the zones and nodes when the model is created
+    pub initial_temperature: Float,
+
+    /// The average change in the temperature of the nodes (in $`C`$) between two
+    /// iterations below which the no-mass layers are considered to have converged
+    pub nomass_tolerance: Float,
+
+    /// The tolerance (in $`C`$) used instead of `nomass_tolerance` when the no-mass
+    /// layers have not converged after 100 iterations
+    pub nomass_relaxed_tolerance: Float,
+
+    /// Allows each surface and fenestration to march with its own sub-timestep.
+    ///
+    /// By default, all of them march on every sub-timestep of the model, whose length
+    /// is dictated by the most demanding construction. When this is enabled, the ones
+    /// that can afford it take longer steps spanning several of the model's sub-timesteps
+    /// (see [`ThermalSurfaceData::substep_ratio`]), which saves time in buildings that
+    /// mix lightweight and heavyweight constructions. The zones are still updated on
+    /// every sub-timestep, and all surfaces are synchronised with them at the end of
+    /// each main timestep.
+    pub multirate: bool,
+
+    /// Chooses the length of the sub-timesteps as the simulation goes, based on an
+    /// estimate of the error of the temperatures of the zones and of the nodes of the
+    /// surfaces (see [`ThermalModel::march`]).
+    /// This way, calm periods are simulated with long sub-timesteps and fast transients
+    /// with short ones. It cannot be combined with `multirate`, and it does not affect
+    /// [`ThermalModel::warmup`] and [`ThermalModel::size`].
+    pub adaptive: bool,
+
+    /// The largest error (in $`C`$) allowed in the temperature of the zones and
+    /// nodes on each sub-timestep when `adaptive` is enabled. Marching fails if it
+    /// cannot be met with the shortest sub-timestep allowed by the `safety_factor`.
+    pub adaptive_tolerance: Float,
+}
+
+impl Default for ThermalModelOptions {
+    fn default() -> Self {
+        Self {
+            max_dx: 0.04,
+            min_dt: 60.,
+            mesh_grading: 1.,
+            safety_factor: 2,
+            default_emissivity: 0.84,
+            initial_temperature: 22.,
+            nomass_tolerance: 0.01,
+            nomass_relaxed_tolerance: 0.5,
+            multirate: false,
+            adaptive: false,
+            adaptive_tolerance: 0.01,
+        }
+    }
 }
 
+impl ThermalModelOptions {
+    /// Checks that the options make sense
+    pub fn validate(&self) -> Result<(), ThermalError> {
+        let invalid = |msg: String| {
+            Err(ThermalError::model_definition(
+                Element::Model,
+                format!("invalid thermal model options... {}", msg),
+            ))
+        };
+        if !self.max_dx.is_finite() || self.max_dx <= 0. {
+            return invalid(format!(
+                "max_dx ({}) has to be a finite number greater than zero",
+                self.max_dx
+            ));
+        }
+        if !self.min_dt.is_finite() || self.min_dt <= 0. {
+            return invalid(format!(
+                "min_dt ({}) has to be a finite number greater than zero",
+                self.min_dt
+            ));
+        }
+        if !self.mesh_grading.is_finite() || self.mesh_grading < 1. {
+            return invalid(format!(
+                "mesh_grading ({}) has to be a finite number greater than or equal to 1",
+                self.mesh_grading
+            ));
+        }
+        if self.safety_factor == 0 {
+            return invalid("safety_factor has to be greater than zero".to_string());
+        }
+        if !(0. ..=1.).contains(&self.default_emissivity) {
+            return invalid(format!(
+                "default_emissivity ({}) has to be between 0 and 1",
+                self.default_emissivity
+            ));
+        }
+        if !self.initial_temperature.is_finite() {
+            return invalid(format!(
+                "initial_temperature ({}) has to be a finite number",
+                self.initial_temperature
+            ));
+        }
+        if !self.nomass_tolerance.is_finite()
+            || !self.nomass_relaxed_tolerance.is_finite()
+            || self.nomass_tolerance <= 0.
+            || self.nomass_relaxed_tolerance < self.nomass_tolerance
+        {
+            return invalid(format!(
+                "nomass_tolerance ({}) has to be a finite number greater than zero and smaller than or equal to nomass_relaxed_tolerance ({}), which has to be finite too",
+                self.nomass_tolerance, self.nomass_relaxed_tolerance
+            ));
+        }
+        if !self.adaptive_tolerance.is_finite() || self.adaptive_tolerance <= 0. {
+            return invalid(format!(
+                "adaptive_tolerance ({}) has to be a finite number greater than zero",
+                self.adaptive_tolerance
+            ));
+        }
+        if self.adaptive && self.multirate {
+            return invalid(
+                "adaptive and multirate cannot be enabled at the same time".to_string(),
+            );
+        }
+        Ok(())
+    }
+}
 
 /// A structure containing all the thermal representation of the whole
 /// [`SimpleModel`]
@@ -66,7 +334,7 @@ pub struct ThermalModel {
 
     /// Luminaires
     pub luminaires: Vec<ThermalLuminaire>,
-    
+
     /// The number of steps that this model needs
     /// to take in order to advance one step of the main
     /// simulation.
@@ -74,6 +342,88 @@ pub struct ThermalModel {
 
     /// The model's dt (i.e., main_dt / self.dt_subdivisions)
     pub dt: Float,
+
+    /// The latitude of the site, in degrees (North is positive)
+    pub latitude: Float,
+
+    /// The longitude of the site, in degrees (East is positive)
+    pub longitude: Float,
+
+    /// The standard meridian of the site's time zone, in degrees (East is positive)
+    pub standard_meridian: Float,
+
+    /// The elevation of the site above the sea level, in $`m`$
+    pub elevation: Float,
+
+    /// The model used for estimating the sky temperature, used by the exterior
+    /// surfaces whose incident infrared irradiance was not calculated by another module
+    pub sky_model: SkyModel,
+
+    /// The warnings (and other non-fatal findings) of the validation of the
+    /// model performed when building it (see [`ThermalModel::diagnose`])
+    pub diagnostics: DiagnosticsReport,
+
+    /// The options with which the model was built
+    pub options: ThermalModelOptions,
+}
+
+/// The terms that add up to the $`A`$ and $`B`$ coefficients of a zone's heat
+/// balance (see [`ThermalModel::calculate_zones_abc`]), grouped by their source
+#[derive(Debug, Default, Clone, Copy)]
+struct ZoneTerms {
+    /// Surfaces and fenestrations (including shading devices and ventilated cavities)
+    surfaces: (Float, Float),
+    /// Infiltration
+    infiltration: (Float, Float),
+    /// Ventilation
+    ventilation: (Float, Float),
+    /// Luminaires
+    luminaires: (Float, Float),
+    /// HVAC systems
+    hvac: (Float, Float),
+}
+
+impl ZoneTerms {
+    /// The terms, in the order in which they are added up
+    fn all(&self) -> [(Float, Float); 5] {
+        [
+            self.surfaces,
+            self.infiltration,
+            self.ventilation,
+            self.luminaires,
+            self.hvac,
+        ]
+    }
+
+    /// The $`A`$ coefficient
+    fn a(&self) -> Float {
+        self.all().iter().map(|(a, _)| a).sum()
+    }
+
+    /// The $`B`$ coefficient
+    fn b(&self) -> Float {
+        self.all().iter().map(|(_, b)| b).sum()
+    }
+
+    /// Calculates the energy balance of a zone whose mean temperature during the
+    /// timestep was `t_mean`, given the `ideal_load` delivered into it and the change
+    /// of its stored energy per second (`storage`)
+    fn energy_balance(
+        &self,
+        t_mean: Float,
+        ideal_load: Float,
+        storage: Float,
+    ) -> ZoneEnergyBalance {
+        let flow = |(a, b): (Float, Float)| a - b * t_mean;
+        ZoneEnergyBalance {
+            surfaces: flow(self.surfaces),
+            infiltration: flow(self.infiltration),
+            ventilation: flow(self.ventilation),
+            luminaires: flow(self.luminaires),
+            hvac: flow(self.hvac) + ideal_load,
+            storage,
+        }
+    }
 }
 
 fn get_boundary_temperature(
@@ -81,59 +431,113 @@ fn get_boundary_temperature(
     t_out: Float,
     model: &SimpleModel,
     state: &SimulationState,
-) -> Result<Float, String> {
+) -> Result<Float, ThermalError> {
     match b {
         Boundary::Space { space } => {
             let space = model.get_space(space)?;
-            space
-                .dry_bulb_temperature(state)
-                .ok_or_else(|| "Space at the back of surface has no temperature!".into())
+            space.dry_bulb_temperature(state).ok_or_else(|| {
+                ThermalError::missing_data(
+                    Element::Space(space.name().clone()),
+                    "it has no temperature",
+                )
+            })
         }
-        Boundary::Ground => unimplemented!(),
+        Boundary::Ground => Err(ThermalError::model_definition(
+            Element::Model,
+            "Ground boundaries are not yet supported",
+        )),
         Boundary::AmbientTemperature { temperature } => Ok(*temperature),
         Boundary::Outdoor => Ok(t_out),
     }
 }
 
-
+/// The largest number of the model's `n_substeps` sub-timesteps that a surface
+/// requiring at least `required` sub-timesteps per main timestep can span on each
+/// of its steps. It divides `n_substeps`, so the surface and the zones are
+/// synchronised at the end of every main timestep.
+fn substep_ratio(n_substeps: usize, required: usize) -> usize {
+    (1..=n_substeps)
+        .rev()
+        .find(|ratio| n_substeps % ratio == 0 && n_substeps / ratio >= required)
+        .unwrap_or(1)
+}
 
 // #[cfg(not(feature = "parallel"))]
 #[allow(clippy::too_many_arguments)]
 pub(crate) fn iterate_surfaces<T: SurfaceTrait + Send>(
     surfaces: &[ThermalSurfaceData<T>],
     alloc: &mut [SurfaceMemory],
+    date: Date,
+    air_changes: &[Float],
     wind_direction: Float,
     wind_speed: Float,
+    sky_temperature: Float,
+    sun: Option<(Vector3D, Float)>,
     t_out: Float,
     dt: Float,
+    substep: usize,
+    options: &ThermalModelOptions,
     model: &SimpleModel,
     state: &mut SimulationState,
-) -> Result<(), String> {
-
+    mut audit: Option<&mut [SurfaceEnergyBalance]>,
+) -> Result<(), ThermalError> {
     // #[cfg(not(feature = "parallel"))]
-    let surface_iter = surfaces.iter().zip(alloc.iter_mut());
+    let surface_iter = surfaces.iter().zip(alloc.iter_mut()).enumerate();
     // #[cfg(feature = "parallel")]
     // let surface_iter = surfaces.into_par_iter().zip(alloc.par_iter_mut());
 
-    
-
     let results = surface_iter.map(
-        |d: (&ThermalSurfaceData<T>, &mut SurfaceMemory)| -> Result<(), String> {
+        |(i, d): (usize, (&ThermalSurfaceData<T>, &mut SurfaceMemory))| -> Result<(), ThermalError> {
             let (thermal_surface, memory) = d;
 
+            // Surfaces that take longer steps only march at the end of them, so
+            // they are synchronised with the zones (see ThermalModelOptions::multirate)
+            let ratio = thermal_surface.substep_ratio;
+            if (substep + 1) % ratio != 0 {
+                if let Some(audit) = audit.as_deref_mut() {
+                    audit[i] = SurfaceEnergyBalance::default();
+                }
+                return Ok(());
+            }
+            let dt = dt * ratio as Float;
+
+            // The outdoor air temperature at the height of the surface
+            let t_out = t_out + thermal_surface.air_temperature_offset;
             let t_front =
                 get_boundary_temperature(&thermal_surface.front_boundary, t_out, model, state)?;
             let t_back =
                 get_boundary_temperature(&thermal_surface.back_boundary, t_out, model, state)?;
+            let front_air_changes = thermal_surface
+                .front_space_index
+                .map_or(0.0, |i| air_changes[i]);
+            let back_air_changes = thermal_surface
+                .back_space_index
+                .map_or(0.0, |i| air_changes[i]);
+            // The temperatures before marching, for auditing the energy balance
+            let old_temperatures = if audit.is_some() {
+                let mut m = memory.temperatures.clone();
+                thermal_surface.parent.get_node_temperatures(state, &mut m)?;
+                Some(m)
+            } else {
+                None
+            };
+
             //= d;
             // Update temperatures
+            thermal_surface.update_glazing_state(state)?;
             thermal_surface.march(
                 state,
+                date,
                 t_front,
                 t_back,
+                front_air_changes,
+                back_air_changes,
                 wind_direction,
                 wind_speed,
+                sky_temperature,
+                sun,
                 dt,
+                options,
                 memory,
             )?;
 
@@ -144,13 +548,23 @@ pub(crate) fn iterate_surfaces<T: SurfaceTrait + Send>(
 
             thermal_surface
                 .parent
-                .set_node_temperatures(state, &memory.temperatures);
+                .set_node_temperatures(state, &memory.temperatures)?;
 
             // Calc heat flow
             let ts_front = memory.temperatures.get(0, 0)?;
             let ts_back = memory.temperatures.get(rows - 1, 0)?;
-            let (_front_env, _back_env, front_hs, back_hs) = thermal_surface
-                .calc_border_conditions(state, t_front, t_back, wind_direction, wind_speed);
+            let (front_env, back_env, front_hs, back_hs) = thermal_surface
+                .calc_border_conditions(
+                    state,
+                    date,
+                    t_front,
+                    t_back,
+                    front_air_changes,
+                    back_air_changes,
+                    wind_direction,
+                    wind_speed,
+                    sky_temperature,
+                )?;
             thermal_surface
                 .parent
                 .set_front_convection_coefficient(state, front_hs)?;
@@ -167,6 +581,25 @@ pub(crate) fn iterate_surfaces<T: SurfaceTrait + Send>(
             thermal_surface
                 .parent
                 .set_back_convective_heat_flow(state, flow_back)?;
+
+            // Air leaving ventilated cavities, if any
+            memory.cavity_outflow = thermal_surface.discretization.ventilated_cavities_outflow(
+                &memory.temperatures,
+                &front_env,
+                &back_env,
+            )?;
+
+            if let (Some(audit), Some(old_temperatures)) = (audit.as_deref_mut(), old_temperatures) {
+                audit[i] = thermal_surface.energy_balance(
+                    &old_temperatures,
+                    memory,
+                    &front_env,
+                    &back_env,
+                    front_hs,
+                    back_hs,
+                    dt,
+                )?;
+            }
             Ok(())
         },
     );
@@ -187,7 +620,7 @@ impl ErrorHandling for ThermalModel {
 
 impl SimulationModel for ThermalModel {
     type OutputType = Self;
-    type OptionType = (); // No options
+    type OptionType = ThermalModelOptions;
     type AllocType = ThermalModelMemory;
 
     fn allocate_memory(&self) -> Result<Self::AllocType, String> {
@@ -202,6 +635,8 @@ impl SimulationModel for ThermalModel {
         let ret = ThermalModelMemory {
             surfaces,
             fenestrations,
+            audit: None,
+            adaptive_substeps: self.dt_subdivisions,
         };
         Ok(ret)
     }
@@ -209,33 +644,49 @@ impl SimulationModel for ThermalModel {
     /// Creates a new ThermalModel from a SimpleModel.
     ///    
     /// # Inputs:
+    /// * options: the [`ThermalModelOptions`] (validated before using them)
     /// * model: the `SimpleModel` that the model represents
     /// * state: the `SimulationStateHeader` attached to the SimpleModel
     /// * n: the number of timesteps per hour taken by the main simulation.
+    ///
+    /// Fails if [`ThermalModel::diagnose`] finds any [`crate::diagnostics::Severity::Fatal`]
+    /// problem in the model.
     fn new<M: Borrow<SimpleModel>>(
-        _meta_options: &MetaOptions,
-        _options: Self::OptionType,
+        meta_options: &MetaOptions,
+        options: Self::OptionType,
         model: M,
         state: &mut SimulationStateHeader,
         n: usize,
     ) -> Result<Self, String> {
         let model = model.borrow();
+        options.validate()?;
+
+        let diagnostics = Self::diagnose(model, n, &options);
+        if diagnostics.is_fatal() {
+            let fatal: Vec<String> = diagnostics.fatal().map(|d| d.to_string()).collect();
+            return Err(format!(
+                "The model cannot be simulated... {}",
+                fatal.join(" | ")
+            ));
+        }
 
         /* CREATE ALL ZONES, ONE PER SPACE */
         let mut zones: Vec<ThermalZone> = Vec::with_capacity(model.spaces.len());
         for (i, space) in model.spaces.iter().enumerate() {
             // Add the zone to the model... this pushes it to the sate
             // as well
-            zones.push(ThermalZone::from_space(space, state, i)?);
+            zones.push(ThermalZone::from_space(
+                space,
+                state,
+                i,
+                options.initial_temperature,
+            )?);
         }
-        
+
         /* CREATE ALL SURFACES AND FENESTRATIONS, AND IDENTIFY MODEL TIMESTEP  */
 
         // choose the smallest timestep in all constructions
 
-        let max_dx = 0.04; // 4cm
-        let min_dt = 60.; // 60 seconds
-
         let mut dt_subdivisions: usize = 1;
         let main_dt = 60. * 60. / n as Float;
 
@@ -247,16 +698,17 @@ impl SimulationModel for ThermalModel {
 
             let normal = surf.vertices.normal();
             let cos_tilt = normal * Vector3D::new(0., 0., 1.);
-            #[cfg(debug_assertions)]
-            dbg!("height is 1");
-            let height = 1.;
+            let height = polygon_height(&surf.vertices);
             let angle = cos_tilt.acos();
             let area = surf.area();
-            let perimeter = surf.vertices.outer().perimeter().unwrap();
-            let centroid = surf.vertices.outer().centroid().unwrap();
+            let perimeter = surf.vertices.outer().perimeter().map_err(|e| {
+                ThermalError::model_definition(Element::Surface(surf.name().clone()), e)
+            })?;
+            let centroid = surf.vertices.outer().centroid().map_err(|e| {
+                ThermalError::model_definition(Element::Surface(surf.name().clone()), e)
+            })?;
 
-            let d =
-                Discretization::new(&construction, model, main_dt, max_dx, min_dt, height, angle)?;
+            let d = Discretization::new(&construction, model, main_dt, &options, height, angle)?;
 
             if d.tstep_subdivision > dt_subdivisions {
                 dt_subdivisions = d.tstep_subdivision;
@@ -273,10 +725,12 @@ impl SimulationModel for ThermalModel {
                 normal,
                 &construction,
                 d,
+                &options,
             )?;
-            // Match surface and zones            
-            tsurf.set_front_boundary(surf.front_boundary.clone(), model);            
-            tsurf.set_back_boundary(surf.back_boundary.clone(), model);
+            tsurf.height = height;
+            // Match surface and zones
+            tsurf.set_front_boundary(surf.front_boundary.clone(), model)?;
+            tsurf.set_back_boundary(surf.back_boundary.clone(), model)?;
 
             surfaces.push(tsurf);
         }
@@ -289,15 +743,16 @@ impl SimulationModel for ThermalModel {
             let cos_tilt = normal * Vector3D::new(0., 0., 1.);
             let angle = cos_tilt.acos();
             let area = surf.area();
-            let perimeter = surf.vertices.outer().perimeter().unwrap();
-            let centroid = surf.vertices.outer().centroid().unwrap();
+            let perimeter = surf.vertices.outer().perimeter().map_err(|e| {
+                ThermalError::model_definition(Element::Fenestration(surf.name().clone()), e)
+            })?;
+            let centroid = surf.vertices.outer().centroid().map_err(|e| {
+                ThermalError::model_definition(Element::Fenestration(surf.name().clone()), e)
+            })?;
 
-            #[cfg(debug_assertions)]
-            dbg!("height is 1");
-            let height = 1.;
+            let height = polygon_height(&surf.vertices);
 
-            let d =
-                Discretization::new(&construction, model, main_dt, max_dx, min_dt, height, angle)?;
+            let d = Discretization::new(&construction, model, main_dt, &options, height, angle)?;
 
             if d.tstep_subdivision > dt_subdivisions {
                 dt_subdivisions = d.tstep_subdivision;
@@ -314,11 +769,18 @@ impl SimulationModel for ThermalModel {
                 normal,
                 &construction,
                 d,
+                &options,
             )?;
+            tsurf.height = height;
+            // Fenestrations are very smooth, regardless of their materials
+            tsurf.front_roughness = Roughness::VerySmooth;
+            tsurf.back_roughness = Roughness::VerySmooth;
+            // ... and their interior convection follows ISO15099
+            tsurf.interior_convection = InteriorConvectionAlgorithm::Iso15099Window;
             // Match surface and zones
-            tsurf.set_front_boundary(surf.front_boundary.clone(), model);            
-            tsurf.set_back_boundary(surf.back_boundary.clone(), model);
-            
+            tsurf.set_front_boundary(surf.front_boundary.clone(), model)?;
+            tsurf.set_back_boundary(surf.back_boundary.clone(), model)?;
+
             fenestrations.push(tsurf);
         }
 
@@ -326,9 +788,19 @@ impl SimulationModel for ThermalModel {
         let mut dt = 60. * 60. / (n as Float * dt_subdivisions as Float);
 
         // safety.
-        const SAFETY: usize = 2;
-        dt /= SAFETY as Float;
-        dt_subdivisions *= SAFETY;
+        dt /= options.safety_factor as Float;
+        dt_subdivisions *= options.safety_factor;
+
+        if options.multirate {
+            for s in surfaces.iter_mut() {
+                let required = s.discretization.tstep_subdivision * options.safety_factor;
+                s.substep_ratio = substep_ratio(dt_subdivisions, required);
+            }
+            for s in fenestrations.iter_mut() {
+                let required = s.discretization.tstep_subdivision * options.safety_factor;
+                s.substep_ratio = substep_ratio(dt_subdivisions, required);
+            }
+        }
 
         let mut hvacs: Vec<ThermalHVAC> = Vec::with_capacity(model.hvacs.len());
         for hvac in model.hvacs.iter() {
@@ -342,7 +814,7 @@ impl SimulationModel for ThermalModel {
             luminaires.push(l)
         }
 
-        Ok(ThermalModel {
+        let mut ret = ThermalModel {
             zones,
             surfaces,
             luminaires,
@@ -350,12 +822,23 @@ impl SimulationModel for ThermalModel {
             dt_subdivisions,
             hvacs,
             dt,
-        })
+            latitude: meta_options.latitude,
+            longitude: meta_options.longitude,
+            standard_meridian: meta_options.standard_meridian,
+            sky_model: SkyModel::default(),
+            elevation: meta_options.elevation,
+            diagnostics,
+            options,
+        };
+        ret.set_weather_station(&WeatherStation::default(), model);
+        Ok(ret)
     }
 
     /// Advances one main_timestep through time. That is,
     /// it performs `self.dt_subdivisions` steps, advancing
-    /// `self.dt` seconds in each of them.
+    /// `self.dt` seconds in each of them... unless
+    /// [`ThermalModelOptions::adaptive`] is enabled, in which case
+    /// the sub-timesteps are chosen as it goes.
     fn march<W: Weather, M: Borrow<SimpleModel>>(
         &self,
         mut date: Date,
@@ -365,62 +848,15 @@ impl SimulationModel for ThermalModel {
         alloc: &mut ThermalModelMemory,
     ) -> Result<(), String> {
         let model = model.borrow();
+        if self.options.adaptive {
+            self.march_adaptive(date, weather, model, state, alloc)?;
+            return Ok(());
+        }
         // Iterate through all the sub-subdivitions
-        for _ in 0..self.dt_subdivisions {
+        for substep in 0..self.dt_subdivisions {
             // advance in time
             date.add_seconds(self.dt);
-            let current_weather = weather.get_weather_data(date);
-            let wind_direction = current_weather.wind_direction.unwrap().to_radians();
-            let wind_speed = current_weather.wind_speed.unwrap();
-
-            let t_out = match current_weather.dry_bulb_temperature {
-                Some(v) => v,
-                None => return Err(
-                    "Trying to march on Thermal Model, but dry bulb temperature was not provided"
-                        .to_string(),
-                ),
-            };
-
-            // Gather spaces temperatures
-            let t_current = self.get_current_zones_temperatures(state);
-
-            
-            iterate_surfaces(
-                &self.surfaces,
-                &mut alloc.surfaces,
-                wind_direction,
-                wind_speed,
-                t_out,
-                self.dt,
-                model,
-                state,
-            )?;
-            
-            iterate_surfaces(
-                &self.fenestrations,
-                &mut alloc.fenestrations,
-                wind_direction,
-                wind_speed,
-                t_out,
-                self.dt,
-                model,
-                state,
-            )?;
-
-            /* UPDATE ZONES' TEMPERATURE */
-            // This is done analytically.
-            let (a, b, c) = self.calculate_zones_abc(model, state)?;
-
-            let future_temperatures =
-                self.estimate_zones_future_temperatures(&t_current, &a, &b, &c, self.dt);
-            for (i, zone) in self.zones.iter().enumerate() {
-                assert!(
-                    !future_temperatures[i].is_nan(),
-                    "Future temperatures is NaN"
-                );
-                zone.reference_space
-                    .set_dry_bulb_temperature(state, future_temperatures[i])?;
-            }
+            self.march_step(date, substep, self.dt, weather, model, state, alloc, None)?;
         } // End of 'in each sub-timestep-subdivision'
 
         Ok(())
@@ -428,192 +864,1191 @@ impl SimulationModel for ThermalModel {
 }
 
 impl ThermalModel {
-    /// Retrieves the dt_subdivisions (i.e. the
-    /// number of substimesteps per timestep of this
-    /// model)
-    pub fn dt_subdivisions(&self) -> usize {
-        self.dt_subdivisions
-    }
-
-    /// Retrieves a ThermalZone
-    pub fn get_thermal_zone(&self, index: usize) -> Result<&ThermalZone, String> {        
-        if index >= self.zones.len() {
-            return ThermalModel::internal_error(format!(
-                "Ouf of bounds: Thermal Zone number {} does not exist",
-                index
-            ));
-        }
-        Ok(&self.zones[index])
+    /// Validates a `model` before simulating it with `n` timesteps per hour and the
+    /// given `options`, without building anything. The report lists—among other
+    /// things—the layers that will be treated as no-mass, the sub-timesteps required
+    /// by each construction, the boundaries that refer to Spaces that do not exist
+    /// and the default properties that will be used. [`ThermalModel::new`] fails if any of them is
+    /// [`crate::diagnostics::Severity::Fatal`].
+    pub fn diagnose(
+        model: &SimpleModel,
+        n: usize,
+        options: &ThermalModelOptions,
+    ) -> DiagnosticsReport {
+        let main_dt = 60. * 60. / n as Float;
+        DiagnosticsReport::new(model, main_dt, options)
     }
 
-    /// This estimation assumes nothing changes during this time.
-    /// This is self evidently wrong, as we know that, for example, the surface temperatures
-    /// will change together with the zone air temperature. However, in short periods of time
-    /// this can actually work.
-    ///
-    /// Everything starts from the following equation, representing a heat balance over
-    /// the air and the contents of the Thermal zone.
-    ///
-    /// ```math
-    /// C_{zone}\frac{dT(t)}{dt} = \displaystyle\sum_{i=loads}{Q_i} + \displaystyle\sum_{i=surf.}{h_iA_i(T_i-T)}+\displaystyle\sum_{i=otherzones}{\dot{m_i}C_p(T_i-T)}+\dot{m}_{inf}C_p(T_{out}-T)+\dot{m}_{supplied}C_p(T_{sup}-T)
-    /// ```
-    /// Which can be expanded into the following
-    ///
-    /// ```math
-    /// C_{zone}\frac{dT(t)}{dt} = A - B T
-    /// ```
-    ///
-    /// Where $`A`$ and $`B`$ are constant terms (at least they can be assumed to be during this brief period of time).
-    ///
-    /// ```math
-    /// A = \displaystyle\sum_{i=loads}{Q_i} + \displaystyle\sum_{i=surf.}{h_iA_i T_i}+\displaystyle\sum_{i=otherzones}{\dot{m_i}C_pT_i}+\dot{m}_{inf}C_pT_{out}+\dot{m}_{supplied}C_pT_{sup}
-    /// ```
-    ///
-    /// ```math
-    /// B= \displaystyle\sum_{i=surf.}{h_iA_i}+\displaystyle\sum_{i=otherzones}{\dot{m_i}C_p}+\dot{m}_{inf}C_p +\dot{m}_{supplied}C_p
-    /// ```
-    ///
-    /// And so, (solving the differential equation) the Temperature $`T`$ at a time $`t`$ into the future
-    /// can be estimated based on the current Temperature of the zone ($`T_{current}`$) and the following
-    /// equation:
+    /// Advances one main timestep through time, choosing the length of each
+    /// sub-timestep by step doubling (see [`ThermalModelOptions::adaptive`]).
     ///
-    /// ```math
-    ///  T(t) = \frac{A}{B} + \left( T_{current} - \frac{A}{B} \right)e^{-\frac{B}{C_{zone}}t}
-    /// ```
+    /// Each sub-timestep is marched once with its full length and—starting again
+    /// from the same temperatures—twice with half of it. The largest difference between
+    /// the resulting temperatures of the zones and of the nodes of the surfaces estimates
+    /// the error of the sub-timestep. If it is larger than `adaptive_tolerance`, the
+    /// sub-timestep is rejected and retried with half the length; otherwise, the (more
+    /// accurate) result of the two halves is kept, and the next sub-timesteps are made
+    /// longer if the error was small. The number of sub-timesteps per main timestep ranges
+    /// from `self.dt_subdivisions` divided by the `safety_factor`—the most that the
+    /// constructions allow—to `self.dt_subdivisions` multiplied by it, and it is
+    /// remembered in the `alloc`.
     ///
-    /// And the average temperature during the same periood is:
-    /// ```math
-    /// \frac{\displaystyle\int_{0}^t{T(t)dt}}{t} = \frac{A}{B}+\frac{C_{zone}\left(T_{current}-\frac{A}{B}\right)}{Bt}\left(1-e^{-\frac{Bt}{C_{zone}}} \right)
-    /// ```
-    #[allow(clippy::type_complexity)]
-    fn calculate_zones_abc(
+    /// The [`EnergyAudit`] (if enabled) only registers the two halves of the accepted
+    /// sub-timesteps.
+    fn march_adaptive<W: Weather>(
         &self,
+        mut date: Date,
+        weather: &W,
         model: &SimpleModel,
+        state: &mut SimulationState,
+        alloc: &mut ThermalModelMemory,
+    ) -> Result<(), ThermalError> {
+        let main_dt = self.dt * self.dt_subdivisions as Float;
+        let min_substeps = (self.dt_subdivisions / self.options.safety_factor).max(1);
+        let max_substeps = self.dt_subdivisions * self.options.safety_factor;
+        let tolerance = self.options.adaptive_tolerance;
+
+        let mut start = TemperatureSnapshot::new(self.zones.len(), alloc);
+        let mut coarse = start.clone();
+        let mut fine = start.clone();
+        let mut elapsed = 0.0;
+        while main_dt - elapsed > 1e-9 * main_dt {
+            let substeps = alloc.adaptive_substeps.clamp(min_substeps, max_substeps);
+            let dt = (main_dt / substeps as Float).min(main_dt - elapsed);
+            let mut half = date;
+            half.add_seconds(dt / 2.);
+            let mut end = date;
+            end.add_seconds(dt);
+
+            self.save_temperatures(state, &mut start)?;
+
+            // One full sub-timestep, which is not audited...
+            let audit = alloc.audit.take();
+            let coarse_result = self.march_step(end, 0, dt, weather, model, state, alloc, None);
+            alloc.audit = audit;
+            coarse_result?;
+            self.save_temperatures(state, &mut coarse)?;
+
+            // ... and two halves
+            self.restore_temperatures(&start, state)?;
+            let mark = alloc.audit.as_ref().map(|a| a.mark());
+            self.march_step(half, 0, dt / 2., weather, model, state, alloc, None)?;
+            self.march_step(end, 0, dt / 2., weather, model, state, alloc, None)?;
+            self.save_temperatures(state, &mut fine)?;
+
+            let error = coarse.max_difference(&fine)?;
+            if error > tolerance {
+                if substeps >= max_substeps {
+                    return Err(ThermalError::numerical(
+                        Element::Model,
+                        format!("the error of the adaptive sub-timesteps ({error} C) is above the adaptive_tolerance ({tolerance} C) even when using the shortest sub-timestep ({} seconds)", main_dt / max_substeps as Float),
+                    ));
+                }
+                // Reject, and try again with shorter sub-timesteps
+                self.restore_temperatures(&start, state)?;
+                if let (Some(audit), Some(mark)) = (alloc.audit.as_mut(), mark) {
+                    audit.rollback(mark);
+                }
+                alloc.adaptive_substeps = (2 * substeps).min(max_substeps);
+                continue;
+            }
+
+            date = end;
+            elapsed += dt;
+            alloc.adaptive_substeps = if error < tolerance / 4. {
+                // quiet... go faster
+                (substeps / 2).max(min_substeps)
+            } else {
+                substeps
+            };
+        }
+        Ok(())
+    }
+
+    /// Copies the temperatures of the zones and of the nodes of all surfaces
+    /// and fenestrations from the `state` into a `snapshot`
+    fn save_temperatures(
+        &self,
         state: &SimulationState,
-    ) -> Result<(Vec<Float>, Vec<Float>, Vec<Float>), String> {
-        let nzones = self.zones.len();
-        // Initialize vectors containing a and b
-        let mut a = vec![0.0; nzones];
-        let mut b = vec![0.0; nzones];
-        let mut c = vec![0.0; nzones];
+        snapshot: &mut TemperatureSnapshot,
+    ) -> Result<(), ThermalError> {
+        for (t, zone) in snapshot.zones.iter_mut().zip(self.zones.iter()) {
+            let space = &zone.reference_space;
+            *t = space.dry_bulb_temperature(state).ok_or_else(|| {
+                ThermalError::missing_data(
+                    Element::Space(space.name().clone()),
+                    "it has no temperature",
+                )
+            })?;
+        }
+        for (m, s) in snapshot.surfaces.iter_mut().zip(self.surfaces.iter()) {
+            s.parent.get_node_temperatures(state, m)?;
+        }
+        for (m, s) in snapshot
+            .fenestrations
+            .iter_mut()
+            .zip(self.fenestrations.iter())
+        {
+            s.parent.get_node_temperatures(state, m)?;
+        }
+        Ok(())
+    }
 
-        /* Qi */
-        // Heating/Cooling
-        for hvac in self.hvacs.iter() {
-            for (target_space_index, heating_cooling) in hvac.calc_cooling_heating_power(state)? {
-                a[target_space_index] += heating_cooling;
-            }
-            // heating through air supply?
+    /// Writes the temperatures in a `snapshot` back into the `state`
+    /// (see [`ThermalModel::save_temperatures`])
+    fn restore_temperatures(
+        &self,
+        snapshot: &TemperatureSnapshot,
+        state: &mut SimulationState,
+    ) -> Result<(), ThermalError> {
+        for (t, zone) in snapshot.zones.iter().zip(self.zones.iter()) {
+            zone.reference_space.set_dry_bulb_temperature(state, *t)?;
         }
-        // Luminaires
-        for luminaire in self.luminaires.iter() {
-            let index = luminaire.target_space_index;
-            let consumption = luminaire
-                .parent
-                .power_consumption(state)
-                .expect("Luminaire has no Power Consumption state");
-            a[index] += consumption;
+        for (m, s) in snapshot.surfaces.iter().zip(self.surfaces.iter()) {
+            s.parent.set_node_temperatures(state, m)?;
+        }
+        for (m, s) in snapshot.fenestrations.iter().zip(self.fenestrations.iter()) {
+            s.parent.set_node_temperatures(state, m)?;
         }
+        Ok(())
+    }
 
-        let air = crate::gas::AIR;
-        // Other
-        for (i, zone) in self.zones.iter().enumerate() {
-            let space = &model.spaces[i];
-            /* INFILTRATION AND VENTILATION */
-            // infiltration from outside
-            if let Some(t_inf_inwards) = space.infiltration_temperature(state) {
-                let v_inf = space
-                    .infiltration_volume(state)
-                    .expect("Space has infiltration temperature but not volume");
+    /// Sets the convection algorithms of all the surfaces and fenestrations
+    /// in the model. These can then be overriden for individual surfaces.
+    ///
+    /// Note that this replaces the default interior convection algorithm of
+    /// fenestrations, which is [`InteriorConvectionAlgorithm::Iso15099Window`].
+    pub fn set_convection_algorithms(
+        &mut self,
+        interior: InteriorConvectionAlgorithm,
+        exterior: ExteriorConvectionAlgorithm,
+    ) {
+        for s in self.surfaces.iter_mut() {
+            s.interior_convection = interior;
+            s.exterior_convection = exterior;
+        }
+        for s in self.fenestrations.iter_mut() {
+            s.interior_convection = interior;
+            s.exterior_convection = exterior;
+        }
+    }
 
-                let cp_inf_inwards = air.heat_capacity(t_inf_inwards + 273.15);
-                let rho_inf_inwards = air.density(t_inf_inwards + 273.15);
-                a[i] += rho_inf_inwards * v_inf * cp_inf_inwards * t_inf_inwards;
-                b[i] += rho_inf_inwards * v_inf * cp_inf_inwards;
+    /// Sets the roughness of the front and back faces of all the surfaces and
+    /// fenestrations built with the construction called `construction`.
+    ///
+    /// The materials in the [`SimpleModel`] carry no roughness, so by default it
+    /// is guessed from the outer layers of each construction (see
+    /// [`Roughness::from_substance`]). This replaces that guess with a known value.
+    /// Individual surfaces can still be changed through their `front_roughness` and
+    /// `back_roughness`.
+    pub fn set_construction_roughness(
+        &mut self,
+        model: &SimpleModel,
+        construction: &str,
+        front: Roughness,
+        back: Roughness,
+    ) -> Result<(), ThermalError> {
+        model.get_construction(construction).map_err(|e| {
+            ThermalError::model_definition(Element::Construction(construction.to_string()), e)
+        })?;
+        for s in self.surfaces.iter_mut() {
+            if s.parent.construction == construction {
+                s.front_roughness = front;
+                s.back_roughness = back;
             }
-
-            // ventilation
-            if let Some(t_vent_inwards) = space.ventilation_temperature(state) {
-                let v_vent = space
-                    .ventilation_volume(state)
-                    .expect("Space has ventilation temperature but not volume");
-                let cp_vent_inwards = air.heat_capacity(t_vent_inwards + 273.15);
-                let rho_vent_inwards = air.density(t_vent_inwards + 273.15);
-                a[i] += rho_vent_inwards * v_vent * cp_vent_inwards * t_vent_inwards;
-                b[i] += rho_vent_inwards * v_vent * cp_vent_inwards;
+        }
+        for s in self.fenestrations.iter_mut() {
+            if s.parent.construction == construction {
+                s.front_roughness = front;
+                s.back_roughness = back;
             }
-
-            // Mixing with other zones
-
-            /* CAPACITANCE */
-            let temp = space
-                .dry_bulb_temperature(state)
-                .expect("Zone has no Temperature!");
-            c[i] = zone.mcp(temp);
+        }
+        Ok(())
+    }
+
+    /// Deploys or retracts the shading device of a fenestration, when
+    /// it uses [`crate::shading::ShadingControl::Manual`], by setting its
+    /// element of the `state`
+    pub fn set_fenestration_shading(
+        &self,
+        state: &mut SimulationState,
+        index: usize,
+        deployed: bool,
+    ) -> Result<(), ThermalError> {
+        match self.fenestrations.get(index) {
+            Some(f) => match f.shading_deployment_index {
+                Some(i) => {
+                    state[i] = if deployed { 1.0 } else { 0.0 };
+                    Ok(())
+                }
+                None => Err(ThermalError::model_definition(
+                    f.parent.element(),
+                    "trying to deploy its shading, but it has none",
+                )),
+            },
+            None => Err(ThermalError::model_definition(
+                Element::Model,
+                format!("there is no fenestration with index {}", index),
+            )),
+        }
+    }
+
+    /// Sets the state of the switchable glazing of a fenestration, when
+    /// it uses [`crate::glazing::SwitchingControl::Manual`], by setting its
+    /// element of the `state`
+    pub fn set_fenestration_glazing_state(
+        &self,
+        state: &mut SimulationState,
+        index: usize,
+        glazing_state: usize,
+    ) -> Result<(), ThermalError> {
+        match self.fenestrations.get(index) {
+            Some(f) => match f.glazing_state_index {
+                Some(i) => {
+                    state[i] = glazing_state as Float;
+                    Ok(())
+                }
+                None => Err(ThermalError::model_definition(
+                    f.parent.element(),
+                    "trying to set the state of its glazing, but it is not switchable",
+                )),
+            },
+            None => Err(ThermalError::model_definition(
+                Element::Model,
+                format!("there is no fenestration with index {}", index),
+            )),
+        }
+    }
+
+    /// Deploys or retracts the shading device of a surface, when
+    /// it uses [`crate::shading::ShadingControl::Manual`], by setting its
+    /// element of the `state`
+    pub fn set_surface_shading(
+        &self,
+        state: &mut SimulationState,
+        index: usize,
+        deployed: bool,
+    ) -> Result<(), ThermalError> {
+        match self.surfaces.get(index) {
+            Some(s) => match s.shading_deployment_index {
+                Some(i) => {
+                    state[i] = if deployed { 1.0 } else { 0.0 };
+                    Ok(())
+                }
+                None => Err(ThermalError::model_definition(
+                    s.parent.element(),
+                    "trying to deploy its shading, but it has none",
+                )),
+            },
+            None => Err(ThermalError::model_definition(
+                Element::Model,
+                format!("there is no surface with index {}", index),
+            )),
+        }
+    }
+
+    /// Sets the characteristics of the [`WeatherStation`] where the weather data
+    /// was measured. This updates the outdoor air temperature and wind speed at
+    /// the height of each surface, and the outdoor air temperature at the height of
+    /// each zone (i.e., the average height of the surfaces around it), which is
+    /// used for infiltration.
+    pub fn set_weather_station(&mut self, station: &WeatherStation, model: &SimpleModel) {
+        let mut zone_heights = vec![(0.0, 0); self.zones.len()];
+        for s in self.surfaces.iter_mut() {
+            s.set_weather_station(station, &model.site_details, self.elevation);
+            for i in [s.front_space_index, s.back_space_index]
+                .into_iter()
+                .flatten()
+            {
+                zone_heights[i].0 += s.centroid_height;
+                zone_heights[i].1 += 1;
+            }
+        }
+        for s in self.fenestrations.iter_mut() {
+            s.set_weather_station(station, &model.site_details, self.elevation);
+        }
+        for (zone, (sum, n)) in self.zones.iter_mut().zip(zone_heights.into_iter()) {
+            let height = if n > 0 { sum / n as Float } else { 0.0 };
+            zone.air_temperature_offset = station.air_temperature_offset(self.elevation, height);
+        }
+    }
+
+    /// Sets user-specified convection coefficients on all the surfaces and
+    /// fenestrations in the model (see [`ThermalSurfaceData::set_convection_coefficients`]).
+    /// These can then be overriden for individual surfaces.
+    pub fn set_convection_coefficients(
+        &mut self,
+        interior: Option<ConvectionCoefficient>,
+        exterior: Option<ConvectionCoefficient>,
+    ) {
+        for s in self.surfaces.iter_mut() {
+            s.set_convection_coefficients(interior.clone(), exterior.clone());
+        }
+        for s in self.fenestrations.iter_mut() {
+            s.set_convection_coefficients(interior.clone(), exterior.clone());
+        }
+    }
+
+    /// Starts auditing the energy balances of all zones, surfaces and fenestrations
+    /// on every sub-timestep (see [`EnergyAudit`]). Residuals larger than `tolerance`
+    /// (in $`W`$) are registered as imbalances.
+    ///
+    /// This makes the simulation slower, so it is meant for diagnostics.
+    pub fn enable_audit(&self, alloc: &mut ThermalModelMemory, tolerance: Float) {
+        alloc.audit = Some(EnergyAudit::new(
+            tolerance,
+            self.zones.len(),
+            self.surfaces.len(),
+            self.fenestrations.len(),
+        ));
+    }
+
+    /// Advances `dt` seconds (i.e., one sub-timestep, usually `self.dt`) through time,
+    /// until `date`. `substep` is the index of this sub-timestep within the main timestep, which
+    /// tells which surfaces need to march (see [`ThermalModelOptions::multirate`]).
+    ///
+    /// If `setpoints` (heating and cooling) are given, the zones are kept within
+    /// them by ideal loads, which are returned for each zone (positive for heating and
+    /// negative for cooling, in $`W`$). Otherwise, the zones float freely and the
+    /// returned loads are zero.
+    #[allow(clippy::too_many_arguments)]
+    fn march_step<W: Weather>(
+        &self,
+        date: Date,
+        substep: usize,
+        dt: Float,
+        weather: &W,
+        model: &SimpleModel,
+        state: &mut SimulationState,
+        alloc: &mut ThermalModelMemory,
+        setpoints: Option<(Float, Float)>,
+    ) -> Result<Vec<Float>, ThermalError> {
+        let current_weather = weather.get_weather_data(date);
+        let wind_direction = match current_weather.wind_direction {
+            Some(v) => v.to_radians(),
+            None => {
+                return Err(ThermalError::missing_data(
+                    Element::Weather,
+                    "wind direction was not provided",
+                ))
+            }
+        };
+        let wind_speed = match current_weather.wind_speed {
+            Some(v) => v,
+            None => {
+                return Err(ThermalError::missing_data(
+                    Element::Weather,
+                    "wind speed was not provided",
+                ))
+            }
+        };
+        let sun = match (
+            sun_direction(date, self.latitude, self.longitude, self.standard_meridian),
+            current_weather.direct_normal_radiation,
+        ) {
+            (Some(direction), Some(direct_normal)) => Some((direction, direct_normal)),
+            _ => None,
+        };
+
+        let t_out = match current_weather.dry_bulb_temperature {
+            Some(v) => v,
+            None => {
+                return Err(ThermalError::missing_data(
+                    Element::Weather,
+                    "dry bulb temperature was not provided",
+                ))
+            }
+        };
+        let sky_temperature = self.sky_model.sky_temperature(
+            t_out,
+            current_weather.dew_point_temperature,
+            current_weather.opaque_sky_cover,
+            current_weather.horizontal_infrared_radiation_intensity,
+        )?;
+
+        // Gather spaces temperatures
+        let t_current = self.get_current_zones_temperatures(state)?;
+        let air_changes: Vec<Float> = self
+            .zones
+            .iter()
+            .map(|z| z.air_changes_per_hour(state))
+            .collect();
+
+        iterate_surfaces(
+            &self.surfaces,
+            &mut alloc.surfaces,
+            date,
+            &air_changes,
+            wind_direction,
+            wind_speed,
+            sky_temperature,
+            sun,
+            t_out,
+            dt,
+            substep,
+            &self.options,
+            model,
+            state,
+            alloc.audit.as_mut().map(|a| a.surfaces.as_mut_slice()),
+        )?;
+
+        iterate_surfaces(
+            &self.fenestrations,
+            &mut alloc.fenestrations,
+            date,
+            &air_changes,
+            wind_direction,
+            wind_speed,
+            sky_temperature,
+            sun,
+            t_out,
+            dt,
+            substep,
+            &self.options,
+            model,
+            state,
+            alloc.audit.as_mut().map(|a| a.fenestrations.as_mut_slice()),
+        )?;
+
+        /* UPDATE ZONES' TEMPERATURE */
+        // This is done analytically.
+        let (a, b, c) = self.calculate_zones_abc(model, state, alloc)?;
+        let audit_terms = match alloc.audit {
+            Some(_) => Some(self.calculate_zones_terms(model, state, alloc)?.0),
+            None => None,
+        };
+
+        let mut future_temperatures =
+            self.estimate_zones_future_temperatures(&t_current, &a, &b, &c, dt);
+
+        // Ideal loads, keeping the zones within the setpoints
+        let mut loads = vec![0.0; self.zones.len()];
+        if let Some((heating_setpoint, cooling_setpoint)) = setpoints {
+            for (i, t) in future_temperatures.iter_mut().enumerate() {
+                let target = if *t < heating_setpoint {
+                    heating_setpoint
+                } else if *t > cooling_setpoint {
+                    cooling_setpoint
+                } else {
+                    continue;
+                };
+                loads[i] = ideal_load(t_current[i], a[i], b[i], c[i], target, dt);
+                *t = target;
+            }
+        }
+
+        for (i, zone) in self.zones.iter().enumerate() {
+            if future_temperatures[i].is_nan() {
+                return Err(ThermalError::numerical(
+                    Element::Space(zone.reference_space.name().clone()),
+                    "its temperature became NaN",
+                ));
+            }
+            zone.reference_space
+                .set_dry_bulb_temperature(state, future_temperatures[i])?;
+        }
+
+        if let (Some(audit), Some(terms)) = (alloc.audit.as_mut(), audit_terms) {
+            let a_total: Vec<Float> = a.iter().zip(loads.iter()).map(|(a, q)| a + q).collect();
+            let t_mean =
+                self.estimate_zones_mean_future_temperatures(&t_current, &a_total, &b, &c, dt);
+            for (i, balance) in audit.zones.iter_mut().enumerate() {
+                let storage = c[i] * (future_temperatures[i] - t_current[i]) / dt;
+                *balance = terms[i].energy_balance(t_mean[i], loads[i], storage);
+            }
+            audit.check(date);
+        }
+        Ok(loads)
+    }
+
+    /// Initializes the temperatures of the zones and of the nodes of all surfaces and
+    /// fenestrations by simulating the day of `date` over and over again, until the
+    /// temperatures at the end of a day are within `options.tolerance` of those at the
+    /// end of the previous day. This way, the simulation does not depend on the
+    /// (arbitrary) initial temperatures, which massive constructions take days
+    /// to forget.
+    ///
+    /// The `state` and `alloc` are the same ones used for the actual simulation
+    /// afterwards. Returns the number of days that were simulated.
+    pub fn warmup<W: Weather, M: Borrow<SimpleModel>>(
+        &self,
+        date: Date,
+        weather: &W,
+        model: M,
+        state: &mut SimulationState,
+        alloc: &mut ThermalModelMemory,
+        options: &WarmUpOptions,
+    ) -> Result<usize, ThermalError> {
+        let model = model.borrow();
+        if options.max_days == 0 || options.min_days > options.max_days {
+            return Err(ThermalError::model_definition(
+                Element::Model,
+                format!(
+                    "invalid warm-up options... min_days ({}) has to be smaller than or equal to max_days ({}), which has to be greater than zero",
+                    options.min_days, options.max_days
+                ),
+            ));
+        }
+
+        let main_dt = self.dt * self.dt_subdivisions as Float;
+        let steps_per_day = (24. * 60. * 60. / main_dt).round() as usize;
+        let start = Date {
+            month: date.month,
+            day: date.day,
+            hour: 0.0,
+        };
+
+        let mut previous = self.get_all_temperatures(state, alloc)?;
+        let mut max_difference = Float::MAX;
+        for day in 1..=options.max_days {
+            let mut date = start;
+            for _ in 0..steps_per_day {
+                let mut sub_date = date;
+                for substep in 0..self.dt_subdivisions {
+                    sub_date.add_seconds(self.dt);
+                    self.march_step(
+                        sub_date, substep, self.dt, weather, model, state, alloc, None,
+                    )?;
+                }
+                date.add_seconds(main_dt);
+            }
+
+            let current = self.get_all_temperatures(state, alloc)?;
+            max_difference = previous
+                .iter()
+                .zip(current.iter())
+                .map(|(a, b)| (a - b).abs())
+                .fold(0.0, Float::max);
+            if day >= options.min_days && max_difference <= options.tolerance {
+                return Ok(day);
+            }
+            previous = current;
+        }
+
+        Err(ThermalError::numerical(
+            Element::Model,
+            format!(
+                "warm-up did not converge after {} days... the largest temperature difference between the last two days was {:.4} C (tolerance is {} C)",
+                options.max_days, max_difference, options.tolerance
+            ),
+        ))
+    }
+
+    /// Calculates the peak heating and cooling loads of each zone during a [`DesignDay`].
+    ///
+    /// The design day is simulated over and over again—with ideal loads keeping the
+    /// zones within the setpoints in `options`—until a periodic steady state is reached
+    /// (see [`ThermalModel::warmup`]). The peaks (and the time at which they occur) are
+    /// those of the last simulated day, averaged over each main timestep.
+    ///
+    /// The solar irradiance on the surfaces is not read from the `state`. Instead, the
+    /// clear-sky irradiance of the design day is calculated for the exterior sides of
+    /// surfaces and fenestrations—and the radiation transmitted by the latter is spread
+    /// over the surfaces of the zones behind them—and written into the `state` on every
+    /// sub-timestep.
+    pub fn size<M: Borrow<SimpleModel>>(
+        &self,
+        design_day: &DesignDay,
+        model: M,
+        state: &mut SimulationState,
+        alloc: &mut ThermalModelMemory,
+        options: &SizingOptions,
+    ) -> Result<Vec<ZoneDesignLoads>, ThermalError> {
+        let model = model.borrow();
+        let convergence = &options.convergence;
+        if convergence.max_days == 0 || convergence.min_days > convergence.max_days {
+            return Err(ThermalError::model_definition(
+                Element::Model,
+                format!(
+                    "invalid sizing options... min_days ({}) has to be smaller than or equal to max_days ({}), which has to be greater than zero",
+                    convergence.min_days, convergence.max_days
+                ),
+            ));
+        }
+        if options.heating_setpoint > options.cooling_setpoint {
+            return Err(ThermalError::model_definition(
+                Element::Model,
+                format!(
+                    "invalid sizing options... heating setpoint ({} C) is higher than cooling setpoint ({} C)",
+                    options.heating_setpoint, options.cooling_setpoint
+                ),
+            ));
+        }
+
+        let weather = DesignDayWeather {
+            design_day,
+            latitude: self.latitude,
+            longitude: self.longitude,
+            standard_meridian: self.standard_meridian,
+        };
+        let setpoints = Some((options.heating_setpoint, options.cooling_setpoint));
+        let main_dt = self.dt * self.dt_subdivisions as Float;
+        let steps_per_day = (24. * 60. * 60. / main_dt).round() as usize;
+
+        let mut previous = self.get_all_temperatures(state, alloc)?;
+        let mut max_difference = Float::MAX;
+        for day in 1..=convergence.max_days {
+            let mut peaks = vec![ZoneDesignLoads::default(); self.zones.len()];
+            let mut date = Date {
+                month: design_day.month,
+                day: design_day.day,
+                hour: 0.0,
+            };
+            for _ in 0..steps_per_day {
+                let mut average_loads = vec![0.0; self.zones.len()];
+                for substep in 0..self.dt_subdivisions {
+                    date.add_seconds(self.dt);
+                    self.set_design_day_solar(design_day, date, state)?;
+                    let loads = self.march_step(
+                        date, substep, self.dt, &weather, model, state, alloc, setpoints,
+                    )?;
+                    for (average, load) in average_loads.iter_mut().zip(loads.iter()) {
+                        *average += load / self.dt_subdivisions as Float;
+                    }
+                }
+                for (peak, load) in peaks.iter_mut().zip(average_loads.into_iter()) {
+                    peak.register(load, date);
+                }
+            }
+
+            let current = self.get_all_temperatures(state, alloc)?;
+            max_difference = previous
+                .iter()
+                .zip(current.iter())
+                .map(|(a, b)| (a - b).abs())
+                .fold(0.0, Float::max);
+            if day >= convergence.min_days && max_difference <= convergence.tolerance {
+                return Ok(peaks);
+            }
+            previous = current;
+        }
+
+        Err(ThermalError::numerical(
+            Element::Model,
+            format!(
+                "sizing did not reach a periodic steady state after {} days... the largest temperature difference between the last two days was {:.4} C (tolerance is {} C)",
+                convergence.max_days, max_difference, convergence.tolerance
+            ),
+        ))
+    }
+
+    /// Writes the solar irradiance of a [`DesignDay`] at `date` into the `state`.
+    ///
+    /// The sides of the surfaces and fenestrations facing the exterior get the beam and
+    /// diffuse irradiance on their plane (see [`DesignDay::irradiance`]). The radiation
+    /// transmitted by the fenestrations is then spread evenly over the sides of the
+    /// surfaces facing the zones behind them, and all other sides get nothing.
+    fn set_design_day_solar(
+        &self,
+        design_day: &DesignDay,
+        date: Date,
+        state: &mut SimulationState,
+    ) -> Result<(), ThermalError> {
+        let sun = sun_direction(date, self.latitude, self.longitude, self.standard_meridian);
+        let flip = |n: Vector3D| Vector3D::new(-n.x, -n.y, -n.z);
+
+        // Exterior sides, and the radiation transmitted into each zone (W)
+        let mut transmitted = vec![0.0; self.zones.len()];
+        for s in self.surfaces.iter() {
+            let front = match s.front_boundary {
+                Boundary::Outdoor => {
+                    let (beam, diffuse) = design_day.irradiance(sun, s.normal);
+                    beam + diffuse
+                }
+                _ => 0.0,
+            };
+            let back = match s.back_boundary {
+                Boundary::Outdoor => {
+                    let (beam, diffuse) = design_day.irradiance(sun, flip(s.normal));
+                    beam + diffuse
+                }
+                _ => 0.0,
+            };
+            s.parent.set_front_solar_irradiance(state, front)?;
+            s.parent.set_back_solar_irradiance(state, back)?;
+        }
+        for s in self.fenestrations.iter() {
+            let front = match s.front_boundary {
+                Boundary::Outdoor => design_day.irradiance(sun, s.normal),
+                _ => (0.0, 0.0),
+            };
+            let back = match s.back_boundary {
+                Boundary::Outdoor => design_day.irradiance(sun, flip(s.normal)),
+                _ => (0.0, 0.0),
+            };
+            s.parent
+                .set_front_solar_irradiance(state, front.0 + front.1)?;
+            s.parent.set_back_solar_irradiance(state, back.0 + back.1)?;
+
+            // Switchable glazing lets through what the state it switches to allows
+            s.update_glazing_state(state)?;
+            if let (Boundary::Outdoor, Some(i)) = (&s.front_boundary, s.back_space_index) {
+                let (beam, diffuse) = front;
+                let cos_theta = sun.map_or(0.0, |dir| dir * s.normal);
+                transmitted[i] += s.area
+                    * (beam * s.solar_transmittance(state, true, Some(cos_theta))
+                        + diffuse * s.solar_transmittance(state, true, None));
+            }
+            if let (Boundary::Outdoor, Some(i)) = (&s.back_boundary, s.front_space_index) {
+                let (beam, diffuse) = back;
+                let cos_theta = sun.map_or(0.0, |dir| -(dir * s.normal));
+                transmitted[i] += s.area
+                    * (beam * s.solar_transmittance(state, false, Some(cos_theta))
+                        + diffuse * s.solar_transmittance(state, false, None));
+            }
+        }
+
+        // Interior sides
+        let mut interior_area = vec![0.0; self.zones.len()];
+        for s in self.surfaces.iter() {
+            for i in [s.front_space_index, s.back_space_index]
+                .into_iter()
+                .flatten()
+            {
+                interior_area[i] += s.area;
+            }
+        }
+        let interior = |i: Option<usize>| match i {
+            Some(i) if interior_area[i] > 0.0 => transmitted[i] / interior_area[i],
+            _ => 0.0,
+        };
+        for s in self.surfaces.iter() {
+            if s.front_space_index.is_some() {
+                s.parent
+                    .set_front_solar_irradiance(state, interior(s.front_space_index))?;
+            }
+            if s.back_space_index.is_some() {
+                s.parent
+                    .set_back_solar_irradiance(state, interior(s.back_space_index))?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Writes the whole thermal state of a simulation into a checkpoint, so that it can
+    /// later be resumed—with exactly the same results—by [`ThermalModel::load_checkpoint`].
+    ///
+    /// The checkpoint contains the `date` (i.e., the one that would be passed to the next
+    /// call to `march`), the temperatures of the zones, the temperatures of the nodes and
+    /// the convection coefficients of all surfaces and fenestrations (which are stored in the
+    /// `state`), and the whole `alloc` (including the length of the adaptive sub-timesteps). The other elements of the `state` (e.g., the
+    /// convective heat flows, which are recalculated on every timestep, or whatever other
+    /// modules store there) are not included.
+    pub fn save_checkpoint<W: Write>(
+        &self,
+        writer: &mut W,
+        date: Date,
+        state: &SimulationState,
+        alloc: &ThermalModelMemory,
+    ) -> Result<(), ThermalError> {
+        let mut w = CheckpointWriter::new(writer);
+        w.bytes(MAGIC)?;
+        w.usize(VERSION)?;
+
+        w.usize(date.month as usize)?;
+        w.usize(date.day as usize)?;
+        w.float(date.hour)?;
+
+        w.usize(self.zones.len())?;
+        for t in self.get_current_zones_temperatures(state)? {
+            w.float(t)?;
+        }
+        w.usize(alloc.adaptive_substeps)?;
+
+        fn write_surfaces<T: SurfaceTrait + Send, W: Write>(
+            w: &mut CheckpointWriter<W>,
+            surfaces: &[ThermalSurfaceData<T>],
+            alloc: &[SurfaceMemory],
+            state: &SimulationState,
+        ) -> Result<(), ThermalError> {
+            w.usize(surfaces.len())?;
+            for (surface, memory) in surfaces.iter().zip(alloc.iter()) {
+                let mut temperatures = memory.temperatures.clone();
+                surface
+                    .parent
+                    .get_node_temperatures(state, &mut temperatures)?;
+                w.matrix(&temperatures)?;
+                for hs in [
+                    surface.parent.front_convection_coefficient(state),
+                    surface.parent.back_convection_coefficient(state),
+                ] {
+                    w.bool(hs.is_some())?;
+                    w.float(hs.unwrap_or(0.0))?;
+                }
+                if let Some(i) = surface.shading_deployment_index {
+                    w.float(state[i])?;
+                }
+                if let Some(i) = surface.glazing_state_index {
+                    w.float(state[i])?;
+                }
+                memory.write_checkpoint(w)?;
+            }
+            Ok(())
+        }
+        write_surfaces(&mut w, &self.surfaces, &alloc.surfaces, state)?;
+        write_surfaces(&mut w, &self.fenestrations, &alloc.fenestrations, state)?;
+        Ok(())
+    }
+
+    /// Reads a checkpoint written by [`ThermalModel::save_checkpoint`] into the `state` and
+    /// `alloc`, returning the date at which the simulation should be resumed.
+    ///
+    /// The `ThermalModel`, `state` and `alloc` need to have been created from the same
+    /// [`SimpleModel`] (and number of timesteps per hour) as those that wrote the checkpoint.
+    /// If reading fails, the `state` and `alloc` may have been partially overwritten.
+    pub fn load_checkpoint<R: Read>(
+        &self,
+        reader: &mut R,
+        state: &mut SimulationState,
+        alloc: &mut ThermalModelMemory,
+    ) -> Result<Date, ThermalError> {
+        let mut r = CheckpointReader::new(reader);
+        let mut magic = [0u8; 8];
+        r.bytes(&mut magic)?;
+        if &magic != MAGIC {
+            return Err(ThermalError::checkpoint(
+                "this is not a thermal model checkpoint",
+            ));
+        }
+        r.expect_usize(VERSION, "checkpoint version")?;
+
+        let month = r.usize()? as u8;
+        let day = r.usize()? as u8;
+        let hour = r.float()?;
+        let date = Date { month, day, hour };
+
+        r.expect_usize(self.zones.len(), "zones")?;
+        for zone in self.zones.iter() {
+            let t = r.float()?;
+            zone.reference_space.set_dry_bulb_temperature(state, t)?;
+        }
+        alloc.adaptive_substeps = r.usize()?;
+
+        fn read_surfaces<T: SurfaceTrait + Send, R: Read>(
+            r: &mut CheckpointReader<R>,
+            surfaces: &[ThermalSurfaceData<T>],
+            alloc: &mut [SurfaceMemory],
+            state: &mut SimulationState,
+        ) -> Result<(), ThermalError> {
+            r.expect_usize(surfaces.len(), "surfaces")?;
+            for (surface, memory) in surfaces.iter().zip(alloc.iter_mut()) {
+                let mut temperatures = memory.temperatures.clone();
+                r.matrix(&mut temperatures)?;
+                surface.parent.set_node_temperatures(state, &temperatures)?;
+                let mut hs = [None; 2];
+                for h in hs.iter_mut() {
+                    let is_some = r.bool()?;
+                    let v = r.float()?;
+                    if is_some {
+                        *h = Some(v);
+                    }
+                }
+                if let Some(h) = hs[0] {
+                    surface.parent.set_front_convection_coefficient(state, h)?;
+                }
+                if let Some(h) = hs[1] {
+                    surface.parent.set_back_convection_coefficient(state, h)?;
+                }
+                if let Some(i) = surface.shading_deployment_index {
+                    state[i] = r.float()?;
+                }
+                if let Some(i) = surface.glazing_state_index {
+                    state[i] = r.float()?;
+                }
+                memory.read_checkpoint(r)?;
+            }
+            Ok(())
+        }
+        read_surfaces(&mut r, &self.surfaces, &mut alloc.surfaces, state)?;
+        read_surfaces(&mut r, &self.fenestrations, &mut alloc.fenestrations, state)?;
+        Ok(date)
+    }
+
+    /// Retrieves the temperatures of all the zones, followed by those of the nodes
+    /// of all the surfaces and fenestrations
+    fn get_all_temperatures(
+        &self,
+        state: &SimulationState,
+        alloc: &ThermalModelMemory,
+    ) -> Result<Vec<Float>, ThermalError> {
+        let mut ret = self.get_current_zones_temperatures(state)?;
+        for memory in alloc.surfaces.iter().chain(alloc.fenestrations.iter()) {
+            let (rows, ..) = memory.temperatures.size();
+            for i in 0..rows {
+                ret.push(memory.temperatures.get(i, 0)?);
+            }
+        }
+        Ok(ret)
+    }
+
+    /// Retrieves the dt_subdivisions (i.e. the
+    /// number of substimesteps per timestep of this
+    /// model)
+    pub fn dt_subdivisions(&self) -> usize {
+        self.dt_subdivisions
+    }
+
+    /// Retrieves a ThermalZone
+    pub fn get_thermal_zone(&self, index: usize) -> Result<&ThermalZone, String> {
+        if index >= self.zones.len() {
+            return ThermalModel::internal_error(format!(
+                "Ouf of bounds: Thermal Zone number {} does not exist",
+                index
+            ));
+        }
+        Ok(&self.zones[index])
+    }
+
+    /// This estimation assumes nothing changes during this time.
+    /// This is self evidently wrong, as we know that, for example, the surface temperatures
+    /// will change together with the zone air temperature. However, in short periods of time
+    /// this can actually work.
+    ///
+    /// Everything starts from the following equation, representing a heat balance over
+    /// the air and the contents of the Thermal zone.
+    ///
+    /// ```math
+    /// C_{zone}\frac{dT(t)}{dt} = \displaystyle\sum_{i=loads}{Q_i} + \displaystyle\sum_{i=surf.}{h_iA_i(T_i-T)}+\displaystyle\sum_{i=otherzones}{\dot{m_i}C_p(T_i-T)}+\dot{m}_{inf}C_p(T_{out}-T)+\dot{m}_{supplied}C_p(T_{sup}-T)
+    /// ```
+    /// Which can be expanded into the following
+    ///
+    /// ```math
+    /// C_{zone}\frac{dT(t)}{dt} = A - B T
+    /// ```
+    ///
+    /// Where $`A`$ and $`B`$ are constant terms (at least they can be assumed to be during this brief period of time).
+    ///
+    /// ```math
+    /// A = \displaystyle\sum_{i=loads}{Q_i} + \displaystyle\sum_{i=surf.}{h_iA_i T_i}+\displaystyle\sum_{i=otherzones}{\dot{m_i}C_pT_i}+\dot{m}_{inf}C_pT_{out}+\dot{m}_{supplied}C_pT_{sup}
+    /// ```
+    ///
+    /// ```math
+    /// B= \displaystyle\sum_{i=surf.}{h_iA_i}+\displaystyle\sum_{i=otherzones}{\dot{m_i}C_p}+\dot{m}_{inf}C_p +\dot{m}_{supplied}C_p
+    /// ```
+    ///
+    /// And so, (solving the differential equation) the Temperature $`T`$ at a time $`t`$ into the future
+    /// can be estimated based on the current Temperature of the zone ($`T_{current}`$) and the following
+    /// equation:
+    ///
+    /// ```math
+    ///  T(t) = \frac{A}{B} + \left( T_{current} - \frac{A}{B} \right)e^{-\frac{B}{C_{zone}}t}
+    /// ```
+    ///
+    /// And the average temperature during the same periood is:
+    /// ```math
+    /// \frac{\displaystyle\int_{0}^t{T(t)dt}}{t} = \frac{A}{B}+\frac{C_{zone}\left(T_{current}-\frac{A}{B}\right)}{Bt}\left(1-e^{-\frac{Bt}{C_{zone}}} \right)
+    /// ```
+    ///
+    /// The air leaving ventilated cavities (e.g., the vents of a Trombe wall) towards a zone
+    /// is treated like supplied air, at the temperature it leaves the cavity.
+    #[allow(clippy::type_complexity)]
+    fn calculate_zones_abc(
+        &self,
+        model: &SimpleModel,
+        state: &SimulationState,
+        alloc: &ThermalModelMemory,
+    ) -> Result<(Vec<Float>, Vec<Float>, Vec<Float>), ThermalError> {
+        let (terms, c) = self.calculate_zones_terms(model, state, alloc)?;
+        let a = terms.iter().map(|t| t.a()).collect();
+        let b = terms.iter().map(|t| t.b()).collect();
+        Ok((a, b, c))
+    }
+
+    /// Calculates each of the terms that add up to the $`A`$ and $`B`$ coefficients
+    /// of each zone, as well as their $`C`$ (see [`ThermalModel::calculate_zones_abc`])
+    fn calculate_zones_terms(
+        &self,
+        model: &SimpleModel,
+        state: &SimulationState,
+        alloc: &ThermalModelMemory,
+    ) -> Result<(Vec<ZoneTerms>, Vec<Float>), ThermalError> {
+        let nzones = self.zones.len();
+        // Initialize vectors containing the terms of a and b
+        let mut terms = vec![ZoneTerms::default(); nzones];
+        let mut c = vec![0.0; nzones];
+
+        /* Qi */
+        // Heating/Cooling
+        for hvac in self.hvacs.iter() {
+            for (target_space_index, heating_cooling) in hvac.calc_cooling_heating_power(state)? {
+                terms[target_space_index].hvac.0 += heating_cooling;
+            }
+            // heating through air supply?
+        }
+        // Luminaires
+        for luminaire in self.luminaires.iter() {
+            let index = luminaire.target_space_index;
+            let consumption = luminaire.parent.power_consumption(state).ok_or_else(|| {
+                ThermalError::missing_data(
+                    Element::Luminaire(luminaire.parent.name().clone()),
+                    "it has no power consumption",
+                )
+            })?;
+            terms[index].luminaires.0 += consumption;
+        }
+
+        let air = crate::gas::AIR;
+        // Other
+        for (i, zone) in self.zones.iter().enumerate() {
+            let space = &model.spaces[i];
+            /* INFILTRATION AND VENTILATION */
+            // infiltration from outside
+            if let Some(t_inf_inwards) = space.infiltration_temperature(state) {
+                let t_inf_inwards = t_inf_inwards + zone.air_temperature_offset;
+                let v_inf = space.infiltration_volume(state).ok_or_else(|| {
+                    ThermalError::missing_data(
+                        Element::Space(space.name().clone()),
+                        "it has an infiltration temperature but not an infiltration volume",
+                    )
+                })?;
+
+                let cp_inf_inwards = air.heat_capacity(t_inf_inwards + 273.15);
+                let rho_inf_inwards = air.density(t_inf_inwards + 273.15);
+                let g_inf = rho_inf_inwards * v_inf * cp_inf_inwards;
+                terms[i].infiltration.0 += g_inf * t_inf_inwards;
+                terms[i].infiltration.1 += g_inf;
+            }
+
+            // ventilation
+            if let Some(t_vent_inwards) = space.ventilation_temperature(state) {
+                let v_vent = space.ventilation_volume(state).ok_or_else(|| {
+                    ThermalError::missing_data(
+                        Element::Space(space.name().clone()),
+                        "it has a ventilation temperature but not a ventilation volume",
+                    )
+                })?;
+                let cp_vent_inwards = air.heat_capacity(t_vent_inwards + 273.15);
+                let rho_vent_inwards = air.density(t_vent_inwards + 273.15);
+                let g_vent = rho_vent_inwards * v_vent * cp_vent_inwards;
+                terms[i].ventilation.0 += g_vent * t_vent_inwards;
+                terms[i].ventilation.1 += g_vent;
+            }
+
+            // Mixing with other zones
+
+            /* CAPACITANCE */
+            let temp = space.dry_bulb_temperature(state).ok_or_else(|| {
+                ThermalError::missing_data(
+                    Element::Space(space.name().clone()),
+                    "it has no temperature",
+                )
+            })?;
+            c[i] = zone.mcp(temp);
         }
 
         /* SURFACES */
         fn iterate_surfaces<T: SurfaceTrait + Send>(
             surfaces: &[ThermalSurfaceData<T>],
+            alloc: &[SurfaceMemory],
             state: &SimulationState,
-            a: &mut [Float],
-            b: &mut [Float],
-        ) -> Result<(), String> {
-            for surface in surfaces {
+            terms: &mut [ZoneTerms],
+        ) -> Result<(), ThermalError> {
+            for (surface, memory) in surfaces.iter().zip(alloc.iter()) {
                 let parent = &surface.parent;
-                let h_front = parent.front_convection_coefficient(state).unwrap();
-                let h_back = parent.back_convection_coefficient(state).unwrap();
+                let h_front = parent.front_convection_coefficient(state).ok_or_else(|| {
+                    ThermalError::missing_data(
+                        parent.element(),
+                        "it has no front convection coefficient",
+                    )
+                })?;
+                let h_back = parent.back_convection_coefficient(state).ok_or_else(|| {
+                    ThermalError::missing_data(
+                        parent.element(),
+                        "it has no back convection coefficient",
+                    )
+                })?;
+                let unmatched = |side: &str| {
+                    ThermalError::model_definition(
+                        parent.element(),
+                        format!("its {} boundary is a Space that is not in the model", side),
+                    )
+                };
 
                 let ai = surface.area;
                 // if front leads to a Zone
                 if let Boundary::Space { .. } = &surface.front_boundary {
-                    let z_index = surface.front_space_index.unwrap(); // Should have one of these if boundary is Space
+                    let z_index = surface
+                        .front_space_index
+                        .ok_or_else(|| unmatched("front"))?;
 
-                    let temp = surface.parent.front_temperature(state);
-                    a[z_index] += h_front * ai * temp;
-                    b[z_index] += h_front * ai;
+                    let temp = surface.parent.front_temperature(state)?;
+                    terms[z_index].surfaces.0 += h_front * ai * temp;
+                    terms[z_index].surfaces.1 += h_front * ai;
                 }
 
                 // if back leads to a Zone
                 if let Boundary::Space { .. } = &surface.back_boundary {
-                    let z_index = surface.back_space_index.unwrap(); // Should have one of these if boundary is Space
+                    let z_index = surface.back_space_index.ok_or_else(|| unmatched("back"))?;
+
+                    let temp = surface.parent.back_temperature(state)?;
+                    terms[z_index].surfaces.0 += h_back * ai * temp;
+                    terms[z_index].surfaces.1 += h_back * ai;
+                }
 
-                    let temp = surface.parent.back_temperature(state);
-                    a[z_index] += h_back * ai * temp;
-                    b[z_index] += h_back * ai;
+                // if a shading device delivers the solar radiation it absorbs into a Zone
+                if memory.shading_heat > 0.0 {
+                    if let Some(shading) = surface.shading() {
+                        let z_index = match shading.position {
+                            ShadingPosition::Front => surface.front_space_index,
+                            ShadingPosition::Back => surface.back_space_index,
+                        };
+                        if let Some(z_index) = z_index {
+                            terms[z_index].surfaces.0 += memory.shading_heat * ai;
+                        }
+                    }
+                }
+
+                // if ventilated cavities deliver air into a Zone
+                for (side, m_cp, t_out) in memory.cavity_outflow.iter() {
+                    let z_index = match side {
+                        CavitySide::Front => surface.front_space_index,
+                        CavitySide::Back => surface.back_space_index,
+                    };
+                    if let Some(z_index) = z_index {
+                        terms[z_index].surfaces.0 += m_cp * ai * t_out;
+                        terms[z_index].surfaces.1 += m_cp * ai;
+                    }
                 }
             }
             Ok(())
         }
 
-        iterate_surfaces(&self.surfaces, state, &mut a, &mut b)?;
-        iterate_surfaces(&self.fenestrations, state, &mut a, &mut b)?;
+        iterate_surfaces(&self.surfaces, &alloc.surfaces, state, &mut terms)?;
+        iterate_surfaces(&self.fenestrations, &alloc.fenestrations, state, &mut terms)?;
 
         /* AIR MIXTURE WITH OTHER ZONES */
         // unimplemented();
 
         // RETURN
-        Ok((a, b, c))
+        Ok((terms, c))
     }
 
     /// Retrieves a vector of the current temperatures of all the Zones as
     /// registered in the Simulation State
-    fn get_current_zones_temperatures(&self, state: &SimulationState) -> Vec<Float> {
+    fn get_current_zones_temperatures(
+        &self,
+        state: &SimulationState,
+    ) -> Result<Vec<Float>, ThermalError> {
         let nzones = self.zones.len();
         // Initialize return
         let mut ret: Vec<Float> = Vec::with_capacity(nzones);
         for zone in self.zones.iter() {
-            let t_current = zone.reference_space.dry_bulb_temperature(state).unwrap();
+            let space = &zone.reference_space;
+            let t_current = space.dry_bulb_temperature(state).ok_or_else(|| {
+                ThermalError::missing_data(
+                    Element::Space(space.name().clone()),
+                    "it has no temperature",
+                )
+            })?;
             ret.push(t_current);
         }
-        ret
+        Ok(ret)
     }
 
     /// Uses an analytical solution to estimate an average temperature for each Zone
     /// for the near future. Uses the coefficients $`A`$, $`B`$ and $`C`$
     /// calculated by `calculate_zones_abc` and the Zones' current temperatures
     /// `t_current` as calculated by `get_current_temperatures`.
-    #[allow(dead_code)]
     fn estimate_zones_mean_future_temperatures(
         &self,
         t_current: &[Float],
@@ -683,7 +2118,10 @@ mod testing {
     use super::*;
     // use crate::construction::*;
 
+    use crate::audit::AuditedElement;
+    use schedule::ScheduleConstant;
     use simple_test_models::*;
+    use weather::SyntheticWeather;
 
     const META_OPTIONS: MetaOptions = MetaOptions {
         latitude: 0.,
@@ -707,15 +2145,22 @@ mod testing {
         );
 
         let n: usize = 1;
-        let thermal_model =
-            ThermalModel::new(&META_OPTIONS, (), &simple_model, &mut state_header, n).unwrap();
+        let thermal_model = ThermalModel::new(
+            &META_OPTIONS,
+            ThermalModelOptions::default(),
+            &simple_model,
+            &mut state_header,
+            n,
+        )
+        .unwrap();
         let state = state_header.take_values().unwrap();
+        let memory = thermal_model.allocate_memory().unwrap();
         // MAP THE STATE
         // model.map_simulation_state(&mut state).unwrap();
 
         // Test
         let (a, b, c) = thermal_model
-            .calculate_zones_abc(&simple_model, &state)
+            .calculate_zones_abc(&simple_model, &state, &memory)
             .unwrap();
         assert_eq!(a.len(), 1);
         assert_eq!(c.len(), 1);
@@ -725,9 +2170,792 @@ mod testing {
             .front_convection_coefficient(&state)
             .unwrap();
 
-        let temp = &thermal_model.surfaces[0].parent.front_temperature(&state);
+        let temp = &thermal_model.surfaces[0]
+            .parent
+            .front_temperature(&state)
+            .unwrap();
         let area = &thermal_model.surfaces[0].area;
         assert_eq!(a[0], area * hi * temp);
         assert_eq!(b[0], area * hi);
     }
+
+    #[test]
+    fn test_options() {
+        assert!(ThermalModelOptions::default().validate().is_ok());
+        for bad in [
+            ThermalModelOptions {
+                max_dx: 0.,
+                ..Default::default()
+            },
+            ThermalModelOptions {
+                safety_factor: 0,
+                ..Default::default()
+            },
+            ThermalModelOptions {
+                mesh_grading: 0.5,
+                ..Default::default()
+            },
+            ThermalModelOptions {
+                default_emissivity: 1.2,
+                ..Default::default()
+            },
+            ThermalModelOptions {
+                nomass_tolerance: 1.,
+                nomass_relaxed_tolerance: 0.5,
+                ..Default::default()
+            },
+            ThermalModelOptions {
+                max_dx: Float::INFINITY,
+                ..Default::default()
+            },
+            ThermalModelOptions {
+                min_dt: Float::INFINITY,
+                ..Default::default()
+            },
+            ThermalModelOptions {
+                nomass_relaxed_tolerance: Float::INFINITY,
+                ..Default::default()
+            },
+            ThermalModelOptions {
+                adaptive_tolerance: Float::INFINITY,
+                ..Default::default()
+            },
+        ] {
+            assert!(bad.validate().is_err());
+        }
+
+        let (simple_model, mut state_header) =
+            get_single_zone_test_building(&SingleZoneTestBuildingOptions {
+                zone_volume: 40.,
+                surface_height: 2.,
+                surface_width: 2.,
+                construction: vec![TestMat::Concrete(0.2)],
+                ..Default::default()
+            });
+        let options = ThermalModelOptions {
+            initial_temperature: 15.,
+            safety_factor: 3,
+            ..Default::default()
+        };
+        let thermal_model =
+            ThermalModel::new(&META_OPTIONS, options, &simple_model, &mut state_header, 4).unwrap();
+        assert_eq!(thermal_model.dt_subdivisions() % 3, 0);
+        let state = state_header.take_values().unwrap();
+        assert_eq!(
+            thermal_model
+                .get_current_zones_temperatures(&state)
+                .unwrap(),
+            vec![15.]
+        );
+        let temp = thermal_model.surfaces[0]
+            .parent
+            .front_temperature(&state)
+            .unwrap();
+        assert_eq!(temp, 15.);
+    }
+
+    #[test]
+    fn test_construction_roughness() {
+        let (simple_model, mut state_header) =
+            get_single_zone_test_building(&SingleZoneTestBuildingOptions {
+                zone_volume: 40.,
+                surface_height: 2.,
+                surface_width: 2.,
+                construction: vec![TestMat::Concrete(0.2)],
+                ..Default::default()
+            });
+        let mut thermal_model = ThermalModel::new(
+            &META_OPTIONS,
+            ThermalModelOptions::default(),
+            &simple_model,
+            &mut state_header,
+            4,
+        )
+        .unwrap();
+        // Concrete is assumed to be rough
+        assert_eq!(thermal_model.surfaces[0].front_roughness, Roughness::Rough);
+
+        let construction = simple_model.surfaces[0].construction.clone();
+        thermal_model
+            .set_construction_roughness(
+                &simple_model,
+                &construction,
+                Roughness::MediumRough,
+                Roughness::Smooth,
+            )
+            .unwrap();
+        for s in thermal_model.surfaces.iter() {
+            assert_eq!(s.front_roughness, Roughness::MediumRough);
+            assert_eq!(s.back_roughness, Roughness::Smooth);
+        }
+
+        // Unknown constructions are an error
+        assert!(thermal_model
+            .set_construction_roughness(
+                &simple_model,
+                "not a construction",
+                Roughness::Smooth,
+                Roughness::Smooth
+            )
+            .is_err());
+    }
+
+    #[test]
+    fn test_substep_ratio() {
+        assert_eq!(substep_ratio(12, 12), 1);
+        assert_eq!(substep_ratio(12, 1), 12);
+        assert_eq!(substep_ratio(12, 2), 6);
+        assert_eq!(substep_ratio(12, 5), 2); // 6 sub-timesteps of the surface
+        assert_eq!(substep_ratio(7, 2), 1); // 7 is prime
+    }
+
+    #[test]
+    fn test_multirate() {
+        let mut weather = SyntheticWeather::default();
+        weather.dry_bulb_temperature = Box::new(ScheduleConstant::new(10.));
+        weather.wind_direction = Box::new(ScheduleConstant::new(0.0));
+        weather.wind_speed = Box::new(ScheduleConstant::new(0.0));
+
+        let n: usize = 4;
+        let simulate = |multirate: bool| -> (ThermalModel, Float) {
+            let (simple_model, mut state_header) =
+                get_single_zone_test_building(&SingleZoneTestBuildingOptions {
+                    zone_volume: 40.,
+                    surface_height: 2.,
+                    surface_width: 2.,
+                    window_height: 1.,
+                    window_width: 1.,
+                    construction: vec![TestMat::Concrete(0.2)],
+                    ..Default::default()
+                });
+            let options = ThermalModelOptions {
+                multirate,
+                ..ThermalModelOptions::default()
+            };
+            let thermal_model =
+                ThermalModel::new(&META_OPTIONS, options, &simple_model, &mut state_header, n)
+                    .unwrap();
+            let mut state = state_header.take_values().unwrap();
+            let mut memory = thermal_model.allocate_memory().unwrap();
+            let mut date = Date {
+                month: 1,
+                day: 1,
+                hour: 0.0,
+            };
+            for _ in 0..24 * n {
+                thermal_model
+                    .march(date, &weather, &simple_model, &mut state, &mut memory)
+                    .unwrap();
+                date.add_seconds(3600. / n as Float);
+            }
+            let t = simple_model.spaces[0].dry_bulb_temperature(&state).unwrap();
+            (thermal_model, t)
+        };
+
+        let (uniform, t_uniform) = simulate(false);
+        let (multirate, t_multirate) = simulate(true);
+        assert_eq!(uniform.dt_subdivisions, multirate.dt_subdivisions);
+
+        let ratios: Vec<(usize, usize)> = multirate
+            .surfaces
+            .iter()
+            .map(|s| (s.substep_ratio, s.discretization.tstep_subdivision))
+            .chain(
+                multirate
+                    .fenestrations
+                    .iter()
+                    .map(|s| (s.substep_ratio, s.discretization.tstep_subdivision)),
+            )
+            .collect();
+        for (ratio, required) in ratios {
+            // Surfaces are synchronised at the end of each main timestep, and
+            // they never march with a sub-timestep longer than they can handle
+            assert_eq!(multirate.dt_subdivisions % ratio, 0);
+            assert!(multirate.dt_subdivisions / ratio >= required * 2);
+        }
+        assert!(uniform.surfaces.iter().all(|s| s.substep_ratio == 1));
+        assert!(
+            (t_uniform - t_multirate).abs() < 0.2,
+            "uniform = {} | multirate = {}",
+            t_uniform,
+            t_multirate
+        );
+    }
+
+    #[test]
+    fn test_adaptive() {
+        let both = ThermalModelOptions {
+            adaptive: true,
+            multirate: true,
+            ..ThermalModelOptions::default()
+        };
+        assert!(both.validate().is_err());
+
+        let weather = |t_out: Float| {
+            let mut weather = SyntheticWeather::default();
+            weather.dry_bulb_temperature = Box::new(ScheduleConstant::new(t_out));
+            weather.wind_direction = Box::new(ScheduleConstant::new(0.0));
+            weather.wind_speed = Box::new(ScheduleConstant::new(0.0));
+            weather
+        };
+
+        // Marches a day, whose outdoor temperature goes from 10C to `t_after` at noon.
+        // Returns the model, its memory, the final zone temperature and the sub-timesteps
+        // the adaptive control chose before and after noon.
+        let n: usize = 4;
+        let simulate = |adaptive: bool,
+                        adaptive_tolerance: Float,
+                        t_after: Float|
+         -> Result<
+            (ThermalModel, ThermalModelMemory, Float, usize, usize),
+            ThermalError,
+        > {
+            let (simple_model, mut state_header) =
+                get_single_zone_test_building(&SingleZoneTestBuildingOptions {
+                    zone_volume: 40.,
+                    surface_height: 2.,
+                    surface_width: 2.,
+                    construction: vec![TestMat::Concrete(0.2)],
+                    ..Default::default()
+                });
+            let options = ThermalModelOptions {
+                adaptive,
+                adaptive_tolerance,
+                ..ThermalModelOptions::default()
+            };
+            let thermal_model =
+                ThermalModel::new(&META_OPTIONS, options, &simple_model, &mut state_header, n)
+                    .unwrap();
+            let mut state = state_header.take_values().unwrap();
+            let mut memory = thermal_model.allocate_memory().unwrap();
+            let mut date = Date {
+                month: 1,
+                day: 1,
+                hour: 0.0,
+            };
+            let mut before = 0;
+            let mut after = 0;
+            for i in 0..24 * n {
+                let t_out = if i < 12 * n { 10. } else { t_after };
+                thermal_model.march(
+                    date,
+                    &weather(t_out),
+                    &simple_model,
+                    &mut state,
+                    &mut memory,
+                )?;
+                date.add_seconds(3600. / n as Float);
+                if i == 12 * n - 1 {
+                    before = memory.adaptive_substeps();
+                } else if i == 12 * n {
+                    after = memory.adaptive_substeps();
+                }
+            }
+            let t = simple_model.spaces[0].dry_bulb_temperature(&state).unwrap();
+            Ok((thermal_model, memory, t, before, after))
+        };
+
+        let tolerance = ThermalModelOptions::default().adaptive_tolerance;
+        for t_after in [10., 30.] {
+            let (_, _, t_fixed, ..) = simulate(false, tolerance, t_after).unwrap();
+            let (adaptive, memory, t_adaptive, before, after) =
+                simulate(true, tolerance, t_after).unwrap();
+
+            // The sub-timesteps stay within the bounds set by the safety factor...
+            let safety_factor = adaptive.options.safety_factor;
+            let min_substeps = (adaptive.dt_subdivisions / safety_factor).max(1);
+            let max_substeps = adaptive.dt_subdivisions * safety_factor;
+            for substeps in [memory.adaptive_substeps(), before, after] {
+                assert!(substeps >= min_substeps, "{} substeps", substeps);
+                assert!(substeps <= max_substeps, "{} substeps", substeps);
+            }
+
+            // ... get shorter when the weather changes suddenly...
+            if t_after > 10. {
+                assert!(after >= before, "before = {} | after = {}", before, after);
+            }
+
+            // ... and the results are close to those of fixed sub-timesteps
+            assert!(
+                (t_fixed - t_adaptive).abs() < 0.2,
+                "fixed = {} | adaptive = {}",
+                t_fixed,
+                t_adaptive
+            );
+        }
+
+        // A tolerance that cannot be met is an error
+        assert!(simulate(true, 1e-12, 30.).is_err());
+    }
+
+    #[test]
+    fn test_temperature_snapshot() {
+        let mut weather = SyntheticWeather::default();
+        weather.dry_bulb_temperature = Box::new(ScheduleConstant::new(10.));
+        weather.wind_direction = Box::new(ScheduleConstant::new(0.0));
+        weather.wind_speed = Box::new(ScheduleConstant::new(0.0));
+
+        let (simple_model, mut state_header) =
+            get_single_zone_test_building(&SingleZoneTestBuildingOptions {
+                zone_volume: 40.,
+                surface_height: 2.,
+                surface_width: 2.,
+                window_height: 1.,
+                window_width: 1.,
+                construction: vec![TestMat::Concrete(0.2)],
+                ..Default::default()
+            });
+        let thermal_model = ThermalModel::new(
+            &META_OPTIONS,
+            ThermalModelOptions::default(),
+            &simple_model,
+            &mut state_header,
+            4,
+        )
+        .unwrap();
+        let mut state = state_header.take_values().unwrap();
+        let mut memory = thermal_model.allocate_memory().unwrap();
+        let date = Date {
+            month: 1,
+            day: 1,
+            hour: 0.0,
+        };
+
+        let mut before = TemperatureSnapshot::new(thermal_model.zones.len(), &memory);
+        let mut after = before.clone();
+        thermal_model
+            .save_temperatures(&state, &mut before)
+            .unwrap();
+        thermal_model
+            .march(date, &weather, &simple_model, &mut state, &mut memory)
+            .unwrap();
+        thermal_model.save_temperatures(&state, &mut after).unwrap();
+        assert!(before.max_difference(&after).unwrap() > 0.0);
+
+        // Undo
+        thermal_model
+            .restore_temperatures(&before, &mut state)
+            .unwrap();
+        thermal_model.save_temperatures(&state, &mut after).unwrap();
+        assert_eq!(before.max_difference(&after).unwrap(), 0.0);
+    }
+
+    #[test]
+    fn test_warmup() {
+        let (simple_model, mut state_header) =
+            get_single_zone_test_building(&SingleZoneTestBuildingOptions {
+                zone_volume: 40.,
+                surface_height: 2.,
+                surface_width: 2.,
+                construction: vec![TestMat::Concrete(0.2)],
+                ..Default::default()
+            });
+
+        let n: usize = 4;
+        let thermal_model = ThermalModel::new(
+            &META_OPTIONS,
+            ThermalModelOptions::default(),
+            &simple_model,
+            &mut state_header,
+            n,
+        )
+        .unwrap();
+        let mut state = state_header.take_values().unwrap();
+        let mut memory = thermal_model.allocate_memory().unwrap();
+
+        let mut weather = SyntheticWeather::default();
+        weather.dry_bulb_temperature = Box::new(ScheduleConstant::new(10.));
+        weather.wind_direction = Box::new(ScheduleConstant::new(0.0));
+        weather.wind_speed = Box::new(ScheduleConstant::new(0.0));
+
+        let date = Date {
+            month: 1,
+            day: 1,
+            hour: 0.0,
+        };
+
+        // Invalid options
+        let options = WarmUpOptions {
+            min_days: 3,
+            max_days: 2,
+            ..WarmUpOptions::default()
+        };
+        assert!(thermal_model
+            .warmup(
+                date,
+                &weather,
+                &simple_model,
+                &mut state,
+                &mut memory,
+                &options
+            )
+            .is_err());
+
+        // Constant weather... it should converge towards the outdoor temperature
+        let options = WarmUpOptions {
+            min_days: 2,
+            max_days: 60,
+            tolerance: 0.01,
+        };
+        let days = thermal_model
+            .warmup(
+                date,
+                &weather,
+                &simple_model,
+                &mut state,
+                &mut memory,
+                &options,
+            )
+            .unwrap();
+        assert!(days >= 2);
+        assert!(days <= 60);
+        let t_zone = simple_model.spaces[0].dry_bulb_temperature(&state).unwrap();
+        assert!(t_zone < 22.);
+        assert!(t_zone > 9.9);
+
+        // Once warmed up, it converges right away
+        let options = WarmUpOptions {
+            min_days: 1,
+            max_days: 60,
+            tolerance: 0.01,
+        };
+        let days = thermal_model
+            .warmup(
+                date,
+                &weather,
+                &simple_model,
+                &mut state,
+                &mut memory,
+                &options,
+            )
+            .unwrap();
+        assert_eq!(days, 1);
+    }
+
+    #[test]
+    fn test_audit() {
+        let (simple_model, mut state_header) =
+            get_single_zone_test_building(&SingleZoneTestBuildingOptions {
+                zone_volume: 40.,
+                surface_height: 2.,
+                surface_width: 2.,
+                construction: vec![TestMat::Concrete(0.1)],
+                infiltration_rate: 0.1,
+                ..Default::default()
+            });
+
+        let n: usize = 4;
+        let thermal_model = ThermalModel::new(
+            &META_OPTIONS,
+            ThermalModelOptions::default(),
+            &simple_model,
+            &mut state_header,
+            n,
+        )
+        .unwrap();
+        let mut state = state_header.take_values().unwrap();
+        let mut memory = thermal_model.allocate_memory().unwrap();
+        assert!(memory.audit().is_none());
+        thermal_model.enable_audit(&mut memory, 1e-6);
+
+        let mut weather = SyntheticWeather::default();
+        weather.dry_bulb_temperature = Box::new(ScheduleConstant::new(5.));
+        weather.wind_direction = Box::new(ScheduleConstant::new(0.0));
+        weather.wind_speed = Box::new(ScheduleConstant::new(2.0));
+
+        let mut date = Date {
+            month: 1,
+            day: 1,
+            hour: 0.0,
+        };
+        for _ in 0..4 {
+            thermal_model
+                .march(date, &weather, &simple_model, &mut state, &mut memory)
+                .unwrap();
+            date.add_seconds(60. * 60. / n as Float);
+        }
+
+        let audit = memory.audit().unwrap();
+        assert_eq!(audit.zones.len(), 1);
+        assert_eq!(audit.surfaces.len(), simple_model.surfaces.len());
+
+        // Zones close their balance
+        let zone = &audit.zones[0];
+        assert!(zone.residual().abs() < 1e-6);
+        // ... which is losing heat through the walls and infiltration
+        assert!(zone.surfaces < 0.0);
+        assert!(zone.infiltration < 0.0);
+        assert!(zone.storage < 0.0);
+        for imbalance in audit.imbalances.iter() {
+            assert!(!matches!(imbalance.element, AuditedElement::Zone(_)));
+        }
+
+        // The wall receives heat from the inside, and loses it outside
+        let wall = &audit.surfaces[0];
+        assert!(wall.storage < 0.0);
+        assert!(wall.front_conduction.abs() > 0.0);
+
+        let audit = memory.disable_audit().unwrap();
+        assert!(audit.max_residual >= 0.0);
+        assert!(memory.audit().is_none());
+    }
+
+    #[test]
+    fn test_checkpoint() {
+        // A window and an insulation layer, so there are no-mass chunks too
+        let options = SingleZoneTestBuildingOptions {
+            zone_volume: 40.,
+            surface_height: 2.,
+            surface_width: 2.,
+            window_height: 1.,
+            window_width: 1.,
+            construction: vec![TestMat::Concrete(0.1), TestMat::Polyurethane(0.02)],
+            ..Default::default()
+        };
+        let thermal_options = ThermalModelOptions {
+            adaptive: true,
+            ..ThermalModelOptions::default()
+        };
+        let n: usize = 4;
+
+        let mut weather = SyntheticWeather::default();
+        weather.dry_bulb_temperature = Box::new(ScheduleConstant::new(5.));
+        weather.wind_direction = Box::new(ScheduleConstant::new(0.0));
+        weather.wind_speed = Box::new(ScheduleConstant::new(2.0));
+
+        fn node_temperatures<T: SurfaceTrait + Send>(
+            surfaces: &[ThermalSurfaceData<T>],
+            alloc: &[SurfaceMemory],
+            state: &SimulationState,
+            ret: &mut Vec<Float>,
+        ) {
+            for (surface, memory) in surfaces.iter().zip(alloc.iter()) {
+                let mut nodes = memory.temperatures.clone();
+                surface
+                    .parent
+                    .get_node_temperatures(state, &mut nodes)
+                    .unwrap();
+                let (rows, ..) = nodes.size();
+                for i in 0..rows {
+                    ret.push(nodes.get(i, 0).unwrap());
+                }
+            }
+        }
+
+        let march = |thermal_model: &ThermalModel,
+                     simple_model: &SimpleModel,
+                     mut date: Date,
+                     state: &mut SimulationState,
+                     memory: &mut ThermalModelMemory|
+         -> (Vec<Float>, Vec<usize>) {
+            let mut temperatures = Vec::new();
+            let mut substeps = Vec::new();
+            for _ in 0..10 {
+                thermal_model
+                    .march(date, &weather, simple_model, state, memory)
+                    .unwrap();
+                date.add_seconds(60. * 60. / n as Float);
+                temperatures.push(simple_model.spaces[0].dry_bulb_temperature(state).unwrap());
+                node_temperatures(
+                    &thermal_model.surfaces,
+                    &memory.surfaces,
+                    state,
+                    &mut temperatures,
+                );
+                node_temperatures(
+                    &thermal_model.fenestrations,
+                    &memory.fenestrations,
+                    state,
+                    &mut temperatures,
+                );
+                substeps.push(memory.adaptive_substeps());
+            }
+            (temperatures, substeps)
+        };
+
+        // Original simulation
+        let (simple_model, mut state_header) = get_single_zone_test_building(&options);
+        let thermal_model = ThermalModel::new(
+            &META_OPTIONS,
+            thermal_options,
+            &simple_model,
+            &mut state_header,
+            n,
+        )
+        .unwrap();
+        let mut state = state_header.take_values().unwrap();
+        let mut memory = thermal_model.allocate_memory().unwrap();
+        assert!(memory
+            .surfaces
+            .iter()
+            .chain(memory.fenestrations.iter())
+            .any(|m| !m.nomass_chunks.is_empty()));
+        let mut date = Date {
+            month: 1,
+            day: 1,
+            hour: 0.0,
+        };
+        march(&thermal_model, &simple_model, date, &mut state, &mut memory);
+        date.add_seconds(10. * 60. * 60. / n as Float);
+
+        let mut checkpoint: Vec<u8> = Vec::new();
+        thermal_model
+            .save_checkpoint(&mut checkpoint, date, &state, &memory)
+            .unwrap();
+        let substeps = memory.adaptive_substeps();
+        let expected = march(&thermal_model, &simple_model, date, &mut state, &mut memory);
+
+        // Resumed simulation
+        let (simple_model, mut state_header) = get_single_zone_test_building(&options);
+        let thermal_model = ThermalModel::new(
+            &META_OPTIONS,
+            thermal_options,
+            &simple_model,
+            &mut state_header,
+            n,
+        )
+        .unwrap();
+        let mut state = state_header.take_values().unwrap();
+        let mut memory = thermal_model.allocate_memory().unwrap();
+        let resume_date = thermal_model
+            .load_checkpoint(&mut checkpoint.as_slice(), &mut state, &mut memory)
+            .unwrap();
+        assert_eq!(memory.adaptive_substeps(), substeps);
+        assert_eq!(resume_date.month, date.month);
+        assert_eq!(resume_date.day, date.day);
+        assert_eq!(resume_date.hour.to_bits(), date.hour.to_bits());
+        let found = march(
+            &thermal_model,
+            &simple_model,
+            resume_date,
+            &mut state,
+            &mut memory,
+        );
+        assert_eq!(expected.0.len(), found.0.len());
+        for (e, f) in expected.0.iter().zip(found.0.iter()) {
+            assert_eq!(e.to_bits(), f.to_bits());
+        }
+        assert_eq!(expected.1, found.1);
+
+        // Not a checkpoint
+        assert!(matches!(
+            thermal_model.load_checkpoint(&mut [0u8; 64].as_slice(), &mut state, &mut memory),
+            Err(ThermalError::Checkpoint(_))
+        ));
+
+        // Truncated
+        assert!(matches!(
+            thermal_model.load_checkpoint(&mut &checkpoint[..40], &mut state, &mut memory),
+            Err(ThermalError::Checkpoint(_))
+        ));
+    }
+
+    #[test]
+    fn test_size() {
+        let (simple_model, mut state_header) =
+            get_single_zone_test_building(&SingleZoneTestBuildingOptions {
+                zone_volume: 40.,
+                surface_height: 2.,
+                surface_width: 2.,
+                construction: vec![TestMat::Polyurethane(0.02)],
+                ..Default::default()
+            });
+
+        let n: usize = 4;
+        let thermal_model = ThermalModel::new(
+            &META_OPTIONS,
+            ThermalModelOptions::default(),
+            &simple_model,
+            &mut state_header,
+            n,
+        )
+        .unwrap();
+        let mut state = state_header.take_values().unwrap();
+        let mut memory = thermal_model.allocate_memory().unwrap();
+
+        let options = SizingOptions {
+            heating_setpoint: 24.,
+            cooling_setpoint: 20.,
+            ..SizingOptions::default()
+        };
+        let day = DesignDay::heating(7, 21, -5., 5.);
+        assert!(thermal_model
+            .size(&day, &simple_model, &mut state, &mut memory, &options)
+            .is_err());
+
+        // Cold day: only heating, all day long
+        let options = SizingOptions::default();
+        let loads = thermal_model
+            .size(&day, &simple_model, &mut state, &mut memory, &options)
+            .unwrap();
+        assert_eq!(loads.len(), 1);
+        assert!(loads[0].heating > 0.0);
+        assert!(loads[0].heating_date.is_some());
+        assert!(loads[0].cooling_date.is_none());
+        let t_zone = simple_model.spaces[0].dry_bulb_temperature(&state).unwrap();
+        assert!((t_zone - options.heating_setpoint).abs() < 1e-6);
+
+        // Hot day: only cooling, with the peak in the afternoon
+        let day = DesignDay::cooling(1, 21, 35., 10., 15., 3.);
+        let loads = thermal_model
+            .size(&day, &simple_model, &mut state, &mut memory, &options)
+            .unwrap();
+        assert!(loads[0].cooling > 0.0);
+        assert!(loads[0].heating_date.is_none());
+        let peak_hour = loads[0].cooling_date.unwrap().hour;
+        assert!(peak_hour > 12. && peak_hour < 20.);
+    }
+
+    #[test]
+    fn test_design_day_solar() {
+        let (simple_model, mut state_header) =
+            get_single_zone_test_building(&SingleZoneTestBuildingOptions {
+                zone_volume: 40.,
+                surface_height: 2.,
+                surface_width: 2.,
+                window_height: 1.,
+                window_width: 1.,
+                construction: vec![TestMat::Concrete(0.2)],
+                ..Default::default()
+            });
+        let thermal_model = ThermalModel::new(
+            &META_OPTIONS,
+            ThermalModelOptions::default(),
+            &simple_model,
+            &mut state_header,
+            4,
+        )
+        .unwrap();
+        let mut state = state_header.take_values().unwrap();
+        let day = DesignDay::cooling(1, 21, 35., 10., 15., 3.);
+        let wall = &thermal_model.surfaces[0];
+        let window = &thermal_model.fenestrations[0];
+
+        // Noon: the exterior gets sun, and the window lets some of it in
+        let noon = Date {
+            month: 1,
+            day: 21,
+            hour: 12.0,
+        };
+        thermal_model
+            .set_design_day_solar(&day, noon, &mut state)
+            .unwrap();
+        let outside = wall.parent.front_solar_irradiance(&state).unwrap();
+        let inside = wall.parent.back_solar_irradiance(&state).unwrap();
+        assert!(outside > 0.0);
+        assert!(window.parent.front_solar_irradiance(&state).unwrap() > 0.0);
+        assert!(inside > 0.0 && inside < outside);
+
+        // Midnight: nothing
+        let midnight = Date {
+            month: 1,
+            day: 21,
+            hour: 0.0,
+        };
+        thermal_model
+            .set_design_day_solar(&day, midnight, &mut state)
+            .unwrap();
+        assert!(wall.parent.front_solar_irradiance(&state).unwrap().abs() < 1e-9);
+        assert!(wall.parent.back_solar_irradiance(&state).unwrap().abs() < 1e-9);
+    }
 }
@@ -0,0 +1,346 @@
+/*
+MIT License
+Copyright (c) 2021 Germán Molina
+Permission is hereby granted, free of charge, to any person obtaining a copy
+of this software and associated documentation files (the "Software"), to deal
+in the Software without restriction, including without limitation the rights
+to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+copies of the Software, and to permit persons to whom the Software is
+furnished to do so, subject to the following conditions:
+The above copyright notice and this permission notice shall be included in all
+copies or substantial portions of the Software.
+THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+SOFTWARE.
+*/
+
+use crate::error::ThermalError;
+use crate::glazing::{AngularGlazing, Glazing};
+use crate::Float;
+
+/// A shading device that can be part of the layers of a
+/// glazing system.
+#[derive(Debug, Clone, Copy)]
+pub enum ShadingDevice {
+    /// A fabric shade (e.g., a roller shade), characterized by its
+    /// solar transmittance and reflectance.
+    Shade {
+        /// The solar transmittance
+        transmittance: Float,
+        /// The solar reflectance
+        reflectance: Float,
+    },
+
+    /// An insect or solar screen, characterized by its openness factor
+    /// (i.e., the fraction of open area) and the reflectance of its material.
+    Screen {
+        /// The fraction of the screen that is open
+        openness: Float,
+        /// The solar reflectance of the screen material
+        reflectance: Float,
+    },
+
+    /// A venetian blind made out of opaque slats
+    VenetianBlind {
+        /// The width of the slats, in $`m`$
+        slat_width: Float,
+        /// The vertical distance between slats, in $`m`$
+        slat_spacing: Float,
+        /// The angle of the slats in radians. $`0`$ means horizontal (i.e., open)
+        /// and $`\pi/2`$ means vertical (i.e., closed)
+        slat_angle: Float,
+        /// The solar reflectance of the slats
+        slat_reflectance: Float,
+    },
+}
+
+impl ShadingDevice {
+    /// Gets the fraction of the area of the device that is open, seen from the
+    /// front. Shades are closed, screens are open as much as their openness
+    /// factor and venetian blinds are open as described in [`ShadingDevice::glazing`].
+    pub fn open_fraction(&self) -> Float {
+        match self {
+            Self::Shade { .. } => 0.0,
+            Self::Screen { openness, .. } => *openness,
+            Self::VenetianBlind {
+                slat_width,
+                slat_spacing,
+                slat_angle,
+                ..
+            } => (1. - slat_width * slat_angle.sin().abs() / slat_spacing).max(0.0),
+        }
+    }
+
+    /// Gets the optical properties of the device, which are assumed to
+    /// be the same on both sides and independent of the angle of incidence.
+    ///
+    /// Venetian blinds are represented by the fraction $`f`$ of their
+    /// area that is open, seen from the front:
+    ///
+    /// ```math
+    /// f = \max\left(0, 1 - \frac{w \sin(\beta)}{s} \right)
+    /// ```
+    ///
+    /// where $`w`$ is the slat width, $`s`$ is the slat spacing and $`\beta`$
+    /// is the slat angle. Half of the radiation reflected by the slats is
+    /// assumed to be transmitted, and the other half reflected.
+    ///
+    /// Fails if the properties of the device are not physically possible.
+    pub fn glazing(&self) -> Result<Glazing, ThermalError> {
+        match self {
+            Self::Shade {
+                transmittance,
+                reflectance,
+            } => Glazing::new(*transmittance, *reflectance, *reflectance),
+            Self::Screen { reflectance, .. } => {
+                let open = self.open_fraction();
+                let rho = (1. - open) * reflectance;
+                Glazing::new(open, rho, rho)
+            }
+            Self::VenetianBlind {
+                slat_reflectance, ..
+            } => {
+                let open = self.open_fraction();
+                let reflected = (1. - open) * slat_reflectance / 2.;
+                Glazing::new(open + reflected, reflected, reflected)
+            }
+        }
+    }
+
+    /// Gets the device as an [`AngularGlazing`] whose properties do not
+    /// depend on the angle of incidence
+    pub fn angular_glazing(&self) -> Result<AngularGlazing, ThermalError> {
+        Ok(AngularGlazing::Table(vec![(0.0, self.glazing()?)]))
+    }
+}
+
+/// The side of a surface on which a [`Shading`] is placed
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ShadingPosition {
+    /// In front of the first layer of the construction
+    Front,
+    /// Behind the last layer of the construction
+    Back,
+}
+
+/// Defines when a [`Shading`] is deployed
+#[derive(Debug, Clone, Copy)]
+pub enum ShadingControl {
+    /// The device is always deployed
+    AlwaysDeployed,
+
+    /// The device is deployed when the solar irradiance incident on
+    /// its side of the surface exceeds a threshold, in $`W/m^2`$
+    Solar(Float),
+
+    /// The device is deployed or retracted at runtime, by setting the
+    /// corresponding element of the simulation state (see
+    /// [`crate::model::ThermalModel::set_fenestration_shading`])
+    Manual,
+}
+
+/// A shading device attached to a surface.
+///
+/// The device is assumed to be thin and well ventilated, so it has no thermal
+/// mass. The solar radiation it absorbs is delivered to the air on its side of
+/// the surface when that side faces a space, and to the surface itself otherwise.
+/// When deployed, it also reduces the long-wave radiation exchanged between the
+/// surface and its surroundings (see [`Shading::radiative_coefficient`]).
+#[derive(Debug, Clone, Copy)]
+pub struct Shading {
+    /// The device
+    pub device: ShadingDevice,
+
+    /// Where it is
+    pub position: ShadingPosition,
+
+    /// When it is deployed
+    pub control: ShadingControl,
+
+    /// The thermal emissivity of the material of the device (from 0 to 1)
+    pub emissivity: Float,
+
+    /// The thermal transmittance of the material of the device (from 0 to 1),
+    /// not counting the long-wave radiation going through its openings
+    pub ir_transmittance: Float,
+
+    /// The thickness of the material of the device, in $`m`$
+    pub thickness: Float,
+
+    /// The thermal conductivity of the material of the device, in $`W/m.K`$
+    pub conductivity: Float,
+}
+
+impl Shading {
+    /// Calculates the linearized radiative heat transfer coefficient (in $`W/m^2.K`$)
+    /// between a surface whose emissivity is `surface_emissivity` and its surroundings,
+    /// when the device is deployed between them. `mean_temperature` is the mean between
+    /// the temperatures of the surface and of the surroundings, in $`C`$.
+    ///
+    /// The long-wave radiation that goes through the device, whose transmittance is
+    /// $`\tau = f + (1 - f)\tau_m`$ (where $`f`$ is the [`ShadingDevice::open_fraction`]
+    /// and $`\tau_m`$ is the `ir_transmittance`), is exchanged directly. The rest goes
+    /// through three resistances in series: the radiative exchange between the surface
+    /// and the device, the conduction through the device and the radiative exchange
+    /// between the device and the surroundings:
+    ///
+    /// ```math
+    /// h = \tau \varepsilon_s h_r + \frac{1 - \tau}{\frac{1/\varepsilon_s + 1/\varepsilon - 1}{h_r} + \frac{t}{k} + \frac{1}{\varepsilon h_r}}
+    /// ```
+    ///
+    /// where $`h_r = 4\sigma \bar{T}^3`$.
+    pub fn radiative_coefficient(
+        &self,
+        surface_emissivity: Float,
+        mean_temperature: Float,
+    ) -> Float {
+        let h_r = 4. * crate::SIGMA * (273.15 + mean_temperature).powi(3);
+        let open = self.device.open_fraction();
+        let tau = open + (1. - open) * self.ir_transmittance;
+        let direct = tau * surface_emissivity * h_r;
+        if surface_emissivity <= 0.0 || self.emissivity <= 0.0 {
+            return direct;
+        }
+        let surface_to_device = (1. / surface_emissivity + 1. / self.emissivity - 1.) / h_r;
+        let conduction = self.thickness / self.conductivity;
+        let device_to_surroundings = 1. / (self.emissivity * h_r);
+        direct + (1. - tau) / (surface_to_device + conduction + device_to_surroundings)
+    }
+
+    /// Checks whether the device is deployed, given the solar irradiance incident
+    /// on its side of the surface and the manual control flag
+    pub fn is_deployed(&self, incident_solar: Float, manual: bool) -> bool {
+        match self.control {
+            ShadingControl::AlwaysDeployed => true,
+            ShadingControl::Solar(threshold) => incident_solar > threshold,
+            ShadingControl::Manual => manual,
+        }
+    }
+}
+
+#[cfg(test)]
+mod testing {
+    use super::*;
+    use crate::PI;
+
+    #[test]
+    fn test_shading_glazing() {
+        let shade = ShadingDevice::Shade {
+            transmittance: 0.1,
+            reflectance: 0.6,
+        }
+        .glazing()
+        .unwrap();
+        assert!((shade.alpha_front() - 0.3).abs() < 1e-9);
+
+        let screen = ShadingDevice::Screen {
+            openness: 0.2,
+            reflectance: 0.5,
+        }
+        .glazing()
+        .unwrap();
+        assert!((screen.tau() - 0.2).abs() < 1e-9);
+        assert!((screen.rho_front() - 0.4).abs() < 1e-9);
+
+        // Open blinds let everything through
+        let mut slat_angle = 0.0;
+        let blind = |slat_angle: Float| {
+            ShadingDevice::VenetianBlind {
+                slat_width: 0.025,
+                slat_spacing: 0.02,
+                slat_angle,
+                slat_reflectance: 0.7,
+            }
+            .glazing()
+            .unwrap()
+        };
+        assert!((blind(slat_angle).tau() - 1.0).abs() < 1e-9);
+
+        // and they block more radiation as they close
+        let mut last_tau = 1.0;
+        while slat_angle < PI / 2. {
+            slat_angle += 0.1;
+            let g = blind(slat_angle);
+            assert!(g.tau() <= last_tau);
+            assert!(g.alpha_front() >= 0.0);
+            last_tau = g.tau();
+        }
+        // Closed... only reflected radiation goes through
+        assert!((blind(PI / 2.).tau() - 0.35).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_is_deployed() {
+        let device = ShadingDevice::Shade {
+            transmittance: 0.1,
+            reflectance: 0.6,
+        };
+        let mut shading = Shading {
+            device,
+            position: ShadingPosition::Back,
+            control: ShadingControl::Solar(300.),
+            emissivity: 0.9,
+            ir_transmittance: 0.0,
+            thickness: 0.001,
+            conductivity: 0.1,
+        };
+        assert!(!shading.is_deployed(200., true));
+        assert!(shading.is_deployed(400., false));
+
+        shading.control = ShadingControl::Manual;
+        assert!(shading.is_deployed(0., true));
+        assert!(!shading.is_deployed(1000., false));
+
+        shading.control = ShadingControl::AlwaysDeployed;
+        assert!(shading.is_deployed(0., false));
+    }
+
+    #[test]
+    fn test_radiative_coefficient() {
+        let blind = |slat_angle: Float| Shading {
+            device: ShadingDevice::VenetianBlind {
+                slat_width: 0.025,
+                slat_spacing: 0.02,
+                slat_angle,
+                slat_reflectance: 0.7,
+            },
+            position: ShadingPosition::Back,
+            control: ShadingControl::AlwaysDeployed,
+            emissivity: 0.9,
+            ir_transmittance: 0.0,
+            thickness: 0.001,
+            conductivity: 160.,
+        };
+        let h_r = 4. * crate::SIGMA * (273.15 + 20.0 as Float).powi(3);
+
+        // Open blinds do not change anything
+        let h = blind(0.0).radiative_coefficient(0.84, 20.);
+        assert!((h - 0.84 * h_r).abs() < 1e-9);
+
+        // Closed blinds add resistances in series
+        let h = blind(PI / 2.).radiative_coefficient(0.84, 20.);
+        let expected = 1. / ((1. / 0.84 + 1. / 0.9 - 1.) / h_r + 0.001 / 160. + 1. / (0.9 * h_r));
+        assert!((h - expected).abs() < 1e-9);
+        assert!(h < 0.84 * h_r);
+
+        // and closing them reduces the exchange
+        let mut last_h = 0.84 * h_r;
+        let mut slat_angle = 0.0;
+        while slat_angle < PI / 2. {
+            slat_angle += 0.1;
+            let h = blind(slat_angle).radiative_coefficient(0.84, 20.);
+            assert!(h <= last_h + 1e-9);
+            last_h = h;
+        }
+
+        // A transparent device does not do anything either
+        let mut shade = blind(PI / 2.);
+        shade.ir_transmittance = 1.0;
+        let h = shade.radiative_coefficient(0.84, 20.);
+        assert!((h - 0.84 * h_r).abs() < 1e-9);
+    }
+}
@@ -0,0 +1,137 @@
+/*
+MIT License
+Copyright (c) 2021 Germán Molina
+Permission is hereby granted, free of charge, to any person obtaining a copy
+of this software and associated documentation files (the "Software"), to deal
+in the Software without restriction, including without limitation the rights
+to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+copies of the Software, and to permit persons to whom the Software is
+furnished to do so, subject to the following conditions:
+The above copyright notice and this permission notice shall be included in all
+copies or substantial portions of the Software.
+THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+SOFTWARE.
+*/
+
+use crate::Float;
+
+/// The radius of the earth, in $`m`$
+const EARTH_RADIUS: Float = 6356000.;
+
+/// The rate at which the air temperature changes with the altitude, in $`K/m`$
+const LAPSE_RATE: Float = -0.0065;
+
+/// The characteristics of the meteorological station where the
+/// weather data was measured, analogous to EnergyPlus' `Site:WeatherStation`.
+/// The default values are those of a standard meteorological station.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct WeatherStation {
+    /// The elevation of the station above the sea level, in $`m`$. If `None`,
+    /// it is assumed to be the same as that of the site.
+    pub elevation: Option<Float>,
+
+    /// The height above the ground at which the air temperature was measured, in $`m`$
+    pub temperature_height: Float,
+
+    /// The height above the ground at which the wind speed was measured, in $`m`$
+    pub wind_height: Float,
+
+    /// The wind speed profile exponent at the station
+    pub wind_exponent: Float,
+
+    /// The wind speed profile boundary layer thickness at the station, in $`m`$
+    pub wind_boundary_layer: Float,
+}
+
+impl std::default::Default for WeatherStation {
+    fn default() -> Self {
+        Self {
+            elevation: None,
+            temperature_height: 1.5,
+            wind_height: 10.,
+            wind_exponent: 0.14,
+            wind_boundary_layer: 270.,
+        }
+    }
+}
+
+/// Converts a geometric altitude into a geopotential one, as $`H = E z / (E + z)`$
+fn geopotential_height(z: Float) -> Float {
+    EARTH_RADIUS * z / (EARTH_RADIUS + z)
+}
+
+impl WeatherStation {
+    /// Calculates the difference between the air temperature at a certain `height`
+    /// above the ground of a site at a certain `site_elevation` and the temperature
+    /// measured by the station, following EnergyPlus' Engineering Reference:
+    ///
+    /// ```math
+    /// \Delta T = L \left(H_z - H_{met}\right)
+    /// ```
+    ///
+    /// Where $`L = -0.0065 K/m`$ is the lapse rate and $`H_z`$ and $`H_{met}`$ are the
+    /// geopotential altitudes of the point of interest and of the station's sensor.
+    pub fn air_temperature_offset(&self, site_elevation: Float, height: Float) -> Float {
+        let station_elevation = self.elevation.unwrap_or(site_elevation);
+        let h_z = geopotential_height(site_elevation + height.max(0.0));
+        let h_met = geopotential_height(station_elevation + self.temperature_height);
+        LAPSE_RATE * (h_z - h_met)
+    }
+
+    /// Calculates the factor by which the wind speed measured by the station needs to
+    /// be multiplied in order to estimate the wind speed at a certain `height` of a site
+    /// whose wind speed profile has an exponent `alpha` and a boundary layer thickness `delta`
+    ///
+    /// ```math
+    /// \frac{V_z}{V_{met}} = \left(\frac{\delta_{met}}{z_{met}}\right)^{\alpha_{met}}\left(\frac{z}{\delta} \right)^{\alpha}
+    /// ```
+    pub fn wind_speed_factor(&self, height: Float, alpha: Float, delta: Float) -> Float {
+        (self.wind_boundary_layer / self.wind_height).powf(self.wind_exponent)
+            * (height / delta).powf(alpha)
+    }
+}
+
+#[cfg(test)]
+mod testing {
+    use super::*;
+
+    #[test]
+    fn test_air_temperature_offset() {
+        let station = WeatherStation::default();
+
+        // At the height of the sensor, nothing changes
+        assert!(station.air_temperature_offset(500., 1.5).abs() < 1e-9);
+
+        // 100m above, it is colder
+        let offset = station.air_temperature_offset(500., 101.5);
+        assert!((offset - -0.65).abs() < 1e-3, "offset = {offset}");
+
+        // ... and so are sites above the station
+        let station = WeatherStation {
+            elevation: Some(400.),
+            ..WeatherStation::default()
+        };
+        let offset = station.air_temperature_offset(500., 1.5);
+        assert!((offset - -0.65).abs() < 1e-3, "offset = {offset}");
+    }
+
+    #[test]
+    fn test_wind_speed_factor() {
+        // A site just like the station
+        let station = WeatherStation::default();
+        let f = station.wind_speed_factor(10., 0.14, 270.);
+        assert!((f - 1.).abs() < 1e-9);
+
+        // Higher stations measure stronger winds
+        let high = WeatherStation {
+            wind_height: 20.,
+            ..WeatherStation::default()
+        };
+        assert!(high.wind_speed_factor(10., 0.14, 270.) < 1.);
+    }
+}
@@ -0,0 +1,382 @@
+/*
+MIT License
+Copyright (c) 2021 Germán Molina
+Permission is hereby granted, free of charge, to any person obtaining a copy
+of this software and associated documentation files (the "Software"), to deal
+in the Software without restriction, including without limitation the rights
+to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+copies of the Software, and to permit persons to whom the Software is
+furnished to do so, subject to the following conditions:
+The above copyright notice and this permission notice shall be included in all
+copies or substantial portions of the Software.
+THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+SOFTWARE.
+*/
+
+use crate::model::WarmUpOptions;
+use crate::sun::sun_direction;
+use crate::Float;
+use calendar::Date;
+use geometry3d::Vector3D;
+use weather::{CurrentWeather, Weather};
+
+/// The fraction of the daily temperature range to subtract from the maximum
+/// dry bulb temperature at the end of each hour of a design day (ASHRAE Handbook
+/// of Fundamentals, Chapter 14)
+const DAILY_RANGE_FRACTIONS: [Float; 24] = [
+    0.87, 0.92, 0.96, 0.99, 1.00, 0.98, 0.93, 0.84, 0.71, 0.56, 0.39, 0.23, 0.11, 0.03, 0.00, 0.03,
+    0.10, 0.21, 0.34, 0.47, 0.58, 0.68, 0.76, 0.82,
+];
+
+/// The apparent extraterrestrial solar irradiance ($`A`$, in $`W/m^2`$), the atmospheric
+/// extinction coefficient ($`B`$) and the diffuse radiation factor ($`C`$) of each
+/// month, used by the ASHRAE clear sky model
+const CLEAR_SKY_COEFFICIENTS: [(Float, Float, Float); 12] = [
+    (1230., 0.142, 0.058),
+    (1215., 0.144, 0.060),
+    (1186., 0.156, 0.071),
+    (1136., 0.180, 0.097),
+    (1104., 0.196, 0.121),
+    (1088., 0.205, 0.134),
+    (1085., 0.207, 0.136),
+    (1107., 0.201, 0.122),
+    (1151., 0.177, 0.092),
+    (1192., 0.160, 0.073),
+    (1221., 0.149, 0.063),
+    (1233., 0.142, 0.057),
+];
+
+/// The solar reflectance of the ground, which reflects the global horizontal
+/// irradiance of a [`DesignDay`] onto tilted surfaces
+const GROUND_REFLECTANCE: Float = 0.2;
+
+/// A synthetic day representing the design conditions for sizing heating
+/// and cooling equipment, analogous to EnergyPlus' `SizingPeriod:DesignDay`.
+#[derive(Debug, Clone, Copy)]
+pub struct DesignDay {
+    /// The month of the design day
+    pub month: u8,
+
+    /// The day of the month of the design day
+    pub day: u8,
+
+    /// The maximum dry bulb temperature during the day, in $`C`$
+    pub max_dry_bulb_temperature: Float,
+
+    /// The difference between the maximum and the minimum dry bulb
+    /// temperatures during the day, in $`K`$. The dry bulb temperature
+    /// follows ASHRAE's hourly profile, reaching its minimum at 5:00
+    /// and its maximum at 15:00.
+    pub daily_range: Float,
+
+    /// The dew point temperature, in $`C`$, which is constant during the day
+    pub dew_point_temperature: Float,
+
+    /// The wind speed, in $`m/s`$
+    pub wind_speed: Float,
+
+    /// The wind direction, in degrees (North is 0, East is 90)
+    pub wind_direction: Float,
+
+    /// A factor multiplying the solar radiation of the ASHRAE clear sky
+    /// model. It is 1 for a clear sky, and 0 for a day without sun (e.g.,
+    /// a heating design day).
+    pub sky_clearness: Float,
+}
+
+impl DesignDay {
+    /// Creates a heating design day: a constant dry bulb `temperature`,
+    /// no sun and the given `wind_speed`
+    pub fn heating(month: u8, day: u8, temperature: Float, wind_speed: Float) -> Self {
+        Self {
+            month,
+            day,
+            max_dry_bulb_temperature: temperature,
+            daily_range: 0.0,
+            dew_point_temperature: temperature,
+            wind_speed,
+            wind_direction: 0.0,
+            sky_clearness: 0.0,
+        }
+    }
+
+    /// Creates a cooling design day: a clear sky and a dry bulb temperature
+    /// that reaches `max_temperature` in the afternoon, and is `daily_range`
+    /// lower at dawn
+    pub fn cooling(
+        month: u8,
+        day: u8,
+        max_temperature: Float,
+        daily_range: Float,
+        dew_point_temperature: Float,
+        wind_speed: Float,
+    ) -> Self {
+        Self {
+            month,
+            day,
+            max_dry_bulb_temperature: max_temperature,
+            daily_range,
+            dew_point_temperature,
+            wind_speed,
+            wind_direction: 0.0,
+            sky_clearness: 1.0,
+        }
+    }
+
+    /// Calculates the dry bulb temperature at a certain `hour` of the
+    /// day, by interpolating the ASHRAE hourly profile
+    pub fn dry_bulb_temperature(&self, hour: Float) -> Float {
+        let hour = hour.rem_euclid(24.);
+        // The fractions correspond to the end of each hour; i.e., the
+        // first one is at 1:00 and the last one is at midnight.
+        let i = (hour.floor() as usize + 23) % 24;
+        let next = (i + 1) % 24;
+        let x = hour - hour.floor();
+        let fraction = DAILY_RANGE_FRACTIONS[i] * (1. - x) + DAILY_RANGE_FRACTIONS[next] * x;
+        self.max_dry_bulb_temperature - fraction * self.daily_range
+    }
+
+    /// Calculates the direct normal and diffuse horizontal solar
+    /// radiation (in $`W/m^2`$) when the sine of the solar altitude is
+    /// `sin_altitude`, according to the ASHRAE clear sky model.
+    pub fn solar_radiation(&self, sin_altitude: Float) -> (Float, Float) {
+        if sin_altitude <= 0.0 || self.sky_clearness <= 0.0 {
+            return (0.0, 0.0);
+        }
+        let month = (self.month as usize).clamp(1, 12);
+        let (a, b, c) = CLEAR_SKY_COEFFICIENTS[month - 1];
+        let direct_normal = self.sky_clearness * a * (-b / sin_altitude).exp();
+        (direct_normal, c * direct_normal)
+    }
+
+    /// Calculates the beam and diffuse solar irradiance (in $`W/m^2`$) on a plane
+    /// facing `normal`, when the direction towards the sun is `sun` (`None` if it
+    /// is below the horizon). The sky is considered isotropic, and the ground reflects
+    /// 20% of the global horizontal irradiance.
+    pub fn irradiance(&self, sun: Option<Vector3D>, normal: Vector3D) -> (Float, Float) {
+        let sun = match sun {
+            Some(v) => v,
+            None => return (0.0, 0.0),
+        };
+        let (direct_normal, diffuse_horizontal) = self.solar_radiation(sun.z);
+        let beam = direct_normal * (sun * normal).max(0.0);
+        let global_horizontal = direct_normal * sun.z + diffuse_horizontal;
+        let cos_tilt = normal.z;
+        let diffuse = diffuse_horizontal * (1. + cos_tilt) / 2.
+            + GROUND_REFLECTANCE * global_horizontal * (1. - cos_tilt) / 2.;
+        (beam, diffuse)
+    }
+}
+
+/// The weather of a [`DesignDay`] at a certain location
+pub(crate) struct DesignDayWeather<'a> {
+    /// The design day
+    pub design_day: &'a DesignDay,
+
+    /// The latitude of the site, in degrees (North is positive)
+    pub latitude: Float,
+
+    /// The longitude of the site, in degrees (East is positive)
+    pub longitude: Float,
+
+    /// The standard meridian of the site's time zone, in degrees (East is positive)
+    pub standard_meridian: Float,
+}
+
+impl<'a> Weather for DesignDayWeather<'a> {
+    fn get_weather_data(&self, date: Date) -> CurrentWeather {
+        let day = self.design_day;
+        let sin_altitude =
+            sun_direction(date, self.latitude, self.longitude, self.standard_meridian)
+                .map_or(0.0, |dir| dir.z);
+        let (direct_normal, diffuse_horizontal) = day.solar_radiation(sin_altitude);
+        CurrentWeather {
+            dry_bulb_temperature: Some(day.dry_bulb_temperature(date.hour)),
+            dew_point_temperature: Some(day.dew_point_temperature),
+            wind_speed: Some(day.wind_speed),
+            wind_direction: Some(day.wind_direction),
+            direct_normal_radiation: Some(direct_normal),
+            diffuse_horizontal_radiation: Some(diffuse_horizontal),
+            opaque_sky_cover: Some(10. * (1. - day.sky_clearness.clamp(0., 1.))),
+            ..CurrentWeather::default()
+        }
+    }
+}
+
+/// The options of a sizing run (see [`crate::model::ThermalModel::size`])
+#[derive(Debug, Clone, Copy)]
+pub struct SizingOptions {
+    /// The temperature below which the zones are heated, in $`C`$
+    pub heating_setpoint: Float,
+
+    /// The temperature above which the zones are cooled, in $`C`$
+    pub cooling_setpoint: Float,
+
+    /// The number of days the design day is repeated for, and the
+    /// tolerance for considering that a periodic steady state has
+    /// been reached
+    pub convergence: WarmUpOptions,
+}
+
+impl Default for SizingOptions {
+    fn default() -> Self {
+        Self {
+            heating_setpoint: 21.,
+            cooling_setpoint: 24.,
+            convergence: WarmUpOptions::default(),
+        }
+    }
+}
+
+/// The peak heating and cooling loads of a zone during a design day
+#[derive(Debug, Default, Clone, Copy)]
+pub struct ZoneDesignLoads {
+    /// The peak heating load, in $`W`$ (zero if the zone was never heated)
+    pub heating: Float,
+
+    /// The time at the end of the timestep when the peak heating load occurred
+    pub heating_date: Option<Date>,
+
+    /// The peak cooling load, in $`W`$ (positive; zero if the zone was never cooled)
+    pub cooling: Float,
+
+    /// The time at the end of the timestep when the peak cooling load occurred
+    pub cooling_date: Option<Date>,
+}
+
+impl ZoneDesignLoads {
+    /// Registers the average `load` (positive for heating, negative for
+    /// cooling) delivered during the timestep ending at `date`
+    pub(crate) fn register(&mut self, load: Float, date: Date) {
+        if load > self.heating {
+            self.heating = load;
+            self.heating_date = Some(date);
+        } else if -load > self.cooling {
+            self.cooling = -load;
+            self.cooling_date = Some(date);
+        }
+    }
+}
+
+/// Calculates the heat that needs to be delivered into a zone (or removed
+/// from it, if negative) so that its temperature is `t_target` after `dt`
+/// seconds. The $`A`$, $`B`$ and $`C`$ coefficients are the ones described in
+/// [`crate::model::ThermalModel`]'s zone heat balance.
+pub(crate) fn ideal_load(
+    t_current: Float,
+    a: Float,
+    b: Float,
+    c: Float,
+    t_target: Float,
+    dt: Float,
+) -> Float {
+    if b.abs() > 1e-9 {
+        let k = (-b * dt / c).exp();
+        b * (t_target - t_current * k) / (1. - k) - a
+    } else {
+        c * (t_target - t_current) / dt - a
+    }
+}
+
+/***********/
+/* TESTING */
+/***********/
+
+#[cfg(test)]
+mod testing {
+    use super::*;
+
+    #[test]
+    fn test_dry_bulb_temperature() {
+        let day = DesignDay::cooling(1, 21, 30., 10., 15., 3.);
+        assert!((day.dry_bulb_temperature(15.) - 30.).abs() < 1e-9);
+        assert!((day.dry_bulb_temperature(5.) - 20.).abs() < 1e-9);
+        assert!((day.dry_bulb_temperature(1.) - (30. - 8.7)).abs() < 1e-9);
+        assert!((day.dry_bulb_temperature(0.) - (30. - 8.2)).abs() < 1e-9);
+        assert!((day.dry_bulb_temperature(24.) - (30. - 8.2)).abs() < 1e-9);
+        // Halfway
+        assert!((day.dry_bulb_temperature(14.5) - (30. - 0.15)).abs() < 1e-9);
+
+        let day = DesignDay::heating(7, 21, -5., 5.);
+        for h in 0..24 {
+            assert!((day.dry_bulb_temperature(h as Float) + 5.).abs() < 1e-9);
+        }
+    }
+
+    #[test]
+    fn test_solar_radiation() {
+        let day = DesignDay::cooling(1, 21, 30., 10., 15., 3.);
+        assert_eq!(day.solar_radiation(-0.2), (0.0, 0.0));
+        let (dn, dh) = day.solar_radiation(1.);
+        assert!((dn - 1230. * (-0.142 as Float).exp()).abs() < 1e-6);
+        assert!((dh - 0.058 * dn).abs() < 1e-6);
+        // Lower sun, less radiation
+        assert!(day.solar_radiation(0.5).0 < dn);
+
+        let day = DesignDay::heating(7, 21, -5., 5.);
+        assert_eq!(day.solar_radiation(1.), (0.0, 0.0));
+    }
+
+    #[test]
+    fn test_irradiance() {
+        let day = DesignDay::cooling(1, 21, 30., 10., 15., 3.);
+        let up = Vector3D::new(0., 0., 1.);
+        let north = Vector3D::new(0., 1., 0.);
+
+        // No sun
+        assert_eq!(day.irradiance(None, up), (0.0, 0.0));
+
+        // Sun at the zenith... a horizontal roof gets it all, a wall gets no beam
+        let (dn, dh) = day.solar_radiation(1.);
+        let (beam, diffuse) = day.irradiance(Some(up), up);
+        assert!((beam - dn).abs() < 1e-6);
+        assert!((diffuse - dh).abs() < 1e-6);
+        let (beam, diffuse) = day.irradiance(Some(up), north);
+        assert!(beam.abs() < 1e-9);
+        assert!((diffuse - dh / 2. - GROUND_REFLECTANCE * (dn + dh) / 2.).abs() < 1e-6);
+
+        // Sun behind the surface
+        let sun = Vector3D::new(0., -0.6, 0.8);
+        let (beam, _) = day.irradiance(Some(sun), north);
+        assert!(beam.abs() < 1e-9);
+        let (beam, _) = day.irradiance(Some(sun), Vector3D::new(0., -1., 0.));
+        assert!((beam - 0.6 * day.solar_radiation(0.8).0).abs() < 1e-6);
+    }
+
+    #[test]
+    fn test_ideal_load() {
+        let (a, b, c, dt) = (100., 10., 5000., 300.);
+        let t_current = 20.;
+        let t_target = 21.;
+        let q = ideal_load(t_current, a, b, c, t_target, dt);
+        let a = a + q;
+        let t = a / b + (t_current - a / b) * (-b * dt / c).exp();
+        assert!((t - t_target).abs() < 1e-6);
+
+        // Disconnected zone
+        let q = ideal_load(t_current, 0.0, 0.0, c, t_target, dt);
+        assert!((q - c / dt).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_register() {
+        let date = Date {
+            month: 1,
+            day: 1,
+            hour: 3.0,
+        };
+        let mut loads = ZoneDesignLoads::default();
+        loads.register(10., date);
+        loads.register(5., date);
+        loads.register(-3., date);
+        assert!((loads.heating - 10.).abs() < 1e-9);
+        assert!((loads.cooling - 3.).abs() < 1e-9);
+        assert!(loads.heating_date.is_some());
+        assert!(loads.cooling_date.is_some());
+    }
+}
@@ -0,0 +1,321 @@
+/*
+MIT License
+Copyright (c) 2021 Germán Molina
+Permission is hereby granted, free of charge, to any person obtaining a copy
+of this software and associated documentation files (the "Software"), to deal
+in the Software without restriction, including without limitation the rights
+to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+copies of the Software, and to permit persons to whom the Software is
+furnished to do so, subject to the following conditions:
+The above copyright notice and this permission notice shall be included in all
+copies or substantial portions of the Software.
+THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+SOFTWARE.
+*/
+
+use crate::error::{Element, ThermalError};
+use crate::sun::hourly_value;
+use crate::surface_trait::SurfaceTrait;
+use crate::Float;
+use calendar::Date;
+use simple_model::{Boundary, SimulationState, Surface};
+use std::sync::Arc;
+
+/// The models available for estimating the temperature of the sky
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum SkyModel {
+    /// Derive the sky temperature from the horizontal infrared radiation
+    /// in the weather file, falling back to [`SkyModel::ClarkAllen`] when
+    /// it is not available. If the dew point temperature is not available either,
+    /// the sky is assumed to be at the dry bulb temperature.
+    #[default]
+    Weather,
+
+    /// The correlation of Berdahl and Martin (1984), as presented in
+    /// EnergyPlus' Engineering Reference
+    ///
+    /// ```math
+    /// \varepsilon_{sky} = \left(0.758 + 0.521\frac{T_{dp}}{100} + 0.625 \left(\frac{T_{dp}}{100}\right)^2 \right)\left(1 + 0.0224N - 0.0035N^2 + 0.00028N^3\right)
+    /// ```
+    ///
+    /// Where $`T_{dp}`$ is the dew point temperature in $`C`$ and $`N`$ is the opaque sky cover (in tenths)
+    BerdahlMartin,
+
+    /// The correlation of Clark and Allen (1978), as presented in
+    /// EnergyPlus' Engineering Reference
+    ///
+    /// ```math
+    /// \varepsilon_{sky} = \left(0.787 + 0.764 \ln\left(\frac{T_{dp}}{273}\right)\right)\left(1 + 0.0224N - 0.0035N^2 + 0.00028N^3\right)
+    /// ```
+    ///
+    /// Where $`T_{dp}`$ is the dew point temperature in $`K`$ and $`N`$ is the opaque sky cover (in tenths)
+    ClarkAllen,
+}
+
+impl SkyModel {
+    /// Calculates the sky temperature, in $`C`$, as $`T_{sky} = \varepsilon_{sky}^{1/4} T_{db}`$
+    /// (or from the horizontal infrared radiation, when using [`SkyModel::Weather`]).
+    ///
+    /// The `opaque_sky_cover` is in tenths, and is assumed to be zero if not known.
+    pub fn sky_temperature(
+        &self,
+        dry_bulb_temperature: Float,
+        dew_point_temperature: Option<Float>,
+        opaque_sky_cover: Option<Float>,
+        horizontal_infrared_radiation: Option<Float>,
+    ) -> Result<Float, ThermalError> {
+        let clear_sky_emissivity: fn(Float) -> Float = match self {
+            Self::BerdahlMartin => {
+                |t_dp| 0.758 + 0.521 * (t_dp / 100.) + 0.625 * (t_dp / 100.).powi(2)
+            }
+            Self::ClarkAllen => |t_dp| 0.787 + 0.764 * ((t_dp + 273.15) / 273.).ln(),
+            Self::Weather => {
+                return match horizontal_infrared_radiation {
+                    Some(ir) if ir > 0.0 => Ok((ir / crate::SIGMA).powf(0.25) - 273.15),
+                    _ if dew_point_temperature.is_none() => Ok(dry_bulb_temperature),
+                    _ => Self::ClarkAllen.sky_temperature(
+                        dry_bulb_temperature,
+                        dew_point_temperature,
+                        opaque_sky_cover,
+                        None,
+                    ),
+                }
+            }
+        };
+
+        let t_dp = match dew_point_temperature {
+            Some(v) => v,
+            None => {
+                return Err(ThermalError::missing_data(
+                    Element::Weather,
+                    format!(
+                        "sky model {:?} requires the dew point temperature, which was not provided",
+                        self
+                    ),
+                ))
+            }
+        };
+        let n = opaque_sky_cover.unwrap_or(0.0).clamp(0.0, 10.);
+        let clouds = 1. + 0.0224 * n - 0.0035 * n.powi(2) + 0.00028 * n.powi(3);
+        let emissivity = (clear_sky_emissivity(t_dp) * clouds).clamp(0.0, 1.0);
+        Ok(emissivity.powf(0.25) * (dry_bulb_temperature + 273.15) - 273.15)
+    }
+}
+
+/// The temperature of the surfaces surrounding an exterior surface (e.g.,
+/// neighbouring buildings)
+#[derive(Debug, Clone)]
+pub enum SurroundingTemperature {
+    /// A constant temperature, in $`C`$
+    Fixed(Float),
+
+    /// Hourly temperatures, in $`C`$, starting at midnight on January 1st.
+    /// The values are repeated cyclically, so 24 values represent a daily profile
+    /// and 8760 represent a whole year.
+    Scheduled(Vec<Float>),
+
+    /// The average temperature of the exterior faces (i.e., those facing a
+    /// [`Boundary::Outdoor`]) of some surfaces in the model
+    Surfaces(Vec<Arc<Surface>>),
+}
+
+/// The surfaces surrounding an exterior surface, which replace
+/// part of its view of the sky and the ground
+#[derive(Debug, Clone)]
+pub struct Surroundings {
+    /// The view factor from the surface to its surroundings (from 0 to 1)
+    pub view_factor: Float,
+
+    /// The temperature of the surroundings
+    pub temperature: SurroundingTemperature,
+}
+
+impl Surroundings {
+    /// Creates new [`Surroundings`], checking that the values are valid
+    pub fn new(
+        view_factor: Float,
+        temperature: SurroundingTemperature,
+    ) -> Result<Self, ThermalError> {
+        let invalid = |message: String| ThermalError::model_definition(Element::Model, message);
+        if !(0.0..=1.0).contains(&view_factor) {
+            return Err(invalid(format!(
+                "the view factor to the surroundings needs to be between 0 and 1... found {view_factor}"
+            )));
+        }
+        match &temperature {
+            SurroundingTemperature::Scheduled(v) if v.is_empty() => {
+                return Err(invalid(
+                    "scheduled surrounding temperatures need at least one value".into(),
+                ))
+            }
+            SurroundingTemperature::Surfaces(v) if v.is_empty() => {
+                return Err(invalid(
+                    "surrounding surfaces need at least one surface".into(),
+                ))
+            }
+            _ => {}
+        }
+        Ok(Self {
+            view_factor,
+            temperature,
+        })
+    }
+
+    /// Gets the temperature of the surroundings, in $`C`$
+    pub fn temperature(&self, state: &SimulationState, date: Date) -> Result<Float, ThermalError> {
+        match &self.temperature {
+            SurroundingTemperature::Fixed(t) => Ok(*t),
+            SurroundingTemperature::Scheduled(values) => Ok(hourly_value(values, date)),
+            SurroundingTemperature::Surfaces(surfaces) => {
+                let mut sum = 0.0;
+                for s in surfaces.iter() {
+                    sum += match s.front_boundary {
+                        Boundary::Outdoor => s.front_temperature(state)?,
+                        _ => s.back_temperature(state)?,
+                    };
+                }
+                Ok(sum / surfaces.len() as Float)
+            }
+        }
+    }
+
+    /// Replaces part of the radiant temperature seen by a surface, in $`C`$,
+    /// with the temperature of these surroundings:
+    ///
+    /// ```math
+    /// T_{rad}^4 = (1 - F_{srd}) T_{rad}^4 + F_{srd} T_{srd}^4
+    /// ```
+    pub fn radiant_temperature(
+        &self,
+        rad_temperature: Float,
+        state: &SimulationState,
+        date: Date,
+    ) -> Result<Float, ThermalError> {
+        let t4 = |t: Float| (t + 273.15).powi(4);
+        let f = self.view_factor;
+        let t_rad4 = (1. - f) * t4(rad_temperature) + f * t4(self.temperature(state, date)?);
+        Ok(t_rad4.powf(0.25) - 273.15)
+    }
+}
+
+/// Calculates the mean radiant temperature, in $`C`$, seen by a surface facing
+/// the exterior, following the approach in EnergyPlus' Engineering Reference.
+///
+/// The `cos_tilt` is the cosine of the angle between the direction the surface
+/// faces and the zenith. The view factors to the sky and the ground are
+/// $`F_{sky} = (1 + \cos{\phi})/2`$ and $`F_{gnd} = (1 - \cos{\phi})/2`$. Also, part
+/// of the sky is replaced by air near the horizon, so
+///
+/// ```math
+/// T_{rad}^4 = F_{sky} \beta T_{sky}^4 + F_{sky} (1 - \beta) T_{air}^4 + F_{gnd} T_{gnd}^4
+/// ```
+///
+/// with $`\beta = \sqrt{(1 + \cos{\phi})/2}`$.
+pub fn exterior_radiant_temperature(
+    cos_tilt: Float,
+    sky_temperature: Float,
+    air_temperature: Float,
+    ground_temperature: Float,
+) -> Float {
+    let f_sky = (1. + cos_tilt) / 2.;
+    let f_ground = (1. - cos_tilt) / 2.;
+    let beta = f_sky.max(0.0).sqrt();
+
+    let t4 = |t: Float| (t + 273.15).powi(4);
+    let t_rad4 = f_sky * beta * t4(sky_temperature)
+        + f_sky * (1. - beta) * t4(air_temperature)
+        + f_ground * t4(ground_temperature);
+    t_rad4.powf(0.25) - 273.15
+}
+
+#[cfg(test)]
+mod testing {
+    use super::*;
+
+    #[test]
+    fn test_sky_temperature() {
+        // From the weather file
+        let ir = 300.;
+        let t = SkyModel::Weather
+            .sky_temperature(20., None, None, Some(ir))
+            .unwrap();
+        assert!((t - ((ir / crate::SIGMA).powf(0.25) - 273.15)).abs() < 1e-9);
+
+        // ... which falls back to Clark-Allen
+        let t = SkyModel::Weather
+            .sky_temperature(20., Some(10.), Some(0.), None)
+            .unwrap();
+        let clark_allen = SkyModel::ClarkAllen
+            .sky_temperature(20., Some(10.), Some(0.), None)
+            .unwrap();
+        assert!((t - clark_allen).abs() < 1e-9);
+        assert!(SkyModel::ClarkAllen
+            .sky_temperature(20., None, None, None)
+            .is_err());
+        let t = SkyModel::Weather
+            .sky_temperature(20., None, None, None)
+            .unwrap();
+        assert!((t - 20.).abs() < 1e-9);
+
+        // Clear skies are colder than the air, and clouds make them warmer
+        let berdahl_martin = SkyModel::BerdahlMartin
+            .sky_temperature(20., Some(10.), Some(0.), None)
+            .unwrap();
+        let cloudy = SkyModel::BerdahlMartin
+            .sky_temperature(20., Some(10.), Some(10.), None)
+            .unwrap();
+        assert!(berdahl_martin < 20.);
+        assert!(clark_allen < 20.);
+        assert!(cloudy > berdahl_martin);
+    }
+
+    #[test]
+    fn test_surroundings() {
+        assert!(Surroundings::new(1.5, SurroundingTemperature::Fixed(20.)).is_err());
+        assert!(Surroundings::new(0.5, SurroundingTemperature::Scheduled(vec![])).is_err());
+        assert!(Surroundings::new(0.5, SurroundingTemperature::Surfaces(vec![])).is_err());
+
+        let state = simple_model::SimulationStateHeader::new()
+            .take_values()
+            .unwrap();
+        let date = Date {
+            month: 1,
+            day: 1,
+            hour: 13.5,
+        };
+
+        // Surroundings at the same temperature change nothing
+        let s = Surroundings::new(0.5, SurroundingTemperature::Fixed(20.)).unwrap();
+        assert!((s.radiant_temperature(20., &state, date).unwrap() - 20.).abs() < 1e-9);
+
+        // ... and fully surrounded surfaces only see them
+        let daily: Vec<Float> = (0..24).map(|i| i as Float).collect();
+        let s = Surroundings::new(1., SurroundingTemperature::Scheduled(daily)).unwrap();
+        assert!((s.radiant_temperature(-5., &state, date).unwrap() - 13.).abs() < 1e-9);
+
+        // Otherwise, they make the sky look warmer
+        let s = Surroundings::new(0.3, SurroundingTemperature::Fixed(20.)).unwrap();
+        let t = s.radiant_temperature(-5., &state, date).unwrap();
+        assert!(t > -5. && t < 20.);
+    }
+
+    #[test]
+    fn test_exterior_radiant_temperature() {
+        // A roof only sees the sky
+        let t = exterior_radiant_temperature(1., -5., 20., 15.);
+        assert!((t - -5.).abs() < 1e-9);
+
+        // ... and a floor only sees the ground
+        let t = exterior_radiant_temperature(-1., -5., 20., 15.);
+        assert!((t - 15.).abs() < 1e-9);
+
+        // Walls see a bit of everything
+        let t = exterior_radiant_temperature(0., -5., 20., 20.);
+        assert!(t > -5. && t < 20.);
+    }
+}
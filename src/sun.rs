@@ -0,0 +1,125 @@
+/*
+MIT License
+Copyright (c) 2021 Germán Molina
+Permission is hereby granted, free of charge, to any person obtaining a copy
+of this software and associated documentation files (the "Software"), to deal
+in the Software without restriction, including without limitation the rights
+to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+copies of the Software, and to permit persons to whom the Software is
+furnished to do so, subject to the following conditions:
+The above copyright notice and this permission notice shall be included in all
+copies or substantial portions of the Software.
+THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+SOFTWARE.
+*/
+
+use crate::{Float, PI};
+use calendar::Date;
+use geometry3d::Vector3D;
+
+/// Cumulative number of days at the beginning of each month (non-leap years)
+const CUMULATIVE_DAYS: [usize; 12] = [0, 31, 59, 90, 120, 151, 181, 212, 243, 273, 304, 334];
+
+/// Calculates the number of hours elapsed since midnight on January 1st
+/// (leap days are ignored)
+pub(crate) fn hour_of_year(date: Date) -> Float {
+    let month = (date.month as usize).clamp(1, 12);
+    let day = CUMULATIVE_DAYS[month - 1] + date.day as usize - 1;
+    24. * day as Float + date.hour
+}
+
+/// Gets the value of an hourly schedule starting at midnight on January 1st. The
+/// values are repeated cyclically, so 24 values represent a daily profile and 8760
+/// represent a whole year.
+pub(crate) fn hourly_value(values: &[Float], date: Date) -> Float {
+    let hour = hour_of_year(date).floor() as usize;
+    values[hour % values.len()]
+}
+
+/// Calculates the direction towards the sun (X is East, Y is North and
+/// Z is Up), or `None` if the sun is below the horizon.
+///
+/// The `date` is in local standard time, and `latitude`, `longitude` and
+/// `standard_meridian` are in degrees (North and East being positive).
+/// The declination and equation of time follow Spencer (1971), as presented
+/// by Duffie and Beckman's *Solar Engineering of Thermal Processes*.
+pub(crate) fn sun_direction(
+    date: Date,
+    latitude: Float,
+    longitude: Float,
+    standard_meridian: Float,
+) -> Option<Vector3D> {
+    let month = (date.month as usize).clamp(1, 12);
+    let n = CUMULATIVE_DAYS[month - 1] + date.day as usize;
+    let b = 2. * PI * (n as Float - 1.) / 365.;
+
+    // Equation of time, in minutes
+    let e = 229.2
+        * (0.000075 + 0.001868 * b.cos()
+            - 0.032077 * b.sin()
+            - 0.014615 * (2. * b).cos()
+            - 0.04089 * (2. * b).sin());
+
+    // Declination, in radians
+    let declination = 0.006918 - 0.399912 * b.cos() + 0.070257 * b.sin()
+        - 0.006758 * (2. * b).cos()
+        + 0.000907 * (2. * b).sin()
+        - 0.002697 * (3. * b).cos()
+        + 0.00148 * (3. * b).sin();
+
+    let solar_time = date.hour + (4. * (longitude - standard_meridian) + e) / 60.;
+    let hour_angle = (15. * (solar_time - 12.)).to_radians();
+    let latitude = latitude.to_radians();
+
+    let z =
+        latitude.cos() * declination.cos() * hour_angle.cos() + latitude.sin() * declination.sin();
+    if z <= 0.0 {
+        return None;
+    }
+    let x = -declination.cos() * hour_angle.sin();
+    let y =
+        declination.sin() * latitude.cos() - declination.cos() * latitude.sin() * hour_angle.cos();
+    Some(Vector3D::new(x, y, z))
+}
+
+#[cfg(test)]
+mod testing {
+    use super::*;
+
+    #[test]
+    fn test_sun_direction() {
+        // Night
+        let date = Date {
+            month: 3,
+            day: 21,
+            hour: 0.0,
+        };
+        assert!(sun_direction(date, -33., -70., -75.).is_none());
+
+        // Equinox, at noon on the equator... the sun is up there
+        let date = Date {
+            month: 3,
+            day: 21,
+            hour: 12.0,
+        };
+        let dir = sun_direction(date, 0., 0., 0.).unwrap();
+        assert!(dir.z > 0.99);
+        let length = (dir.x * dir.x + dir.y * dir.y + dir.z * dir.z).sqrt();
+        assert!((length - 1.).abs() < 1e-9);
+
+        // Morning sun is in the East; southern-hemisphere sun is in the North
+        let date = Date {
+            month: 6,
+            day: 21,
+            hour: 9.0,
+        };
+        let dir = sun_direction(date, -33., -75., -75.).unwrap();
+        assert!(dir.x > 0.0);
+        assert!(dir.y > 0.0);
+    }
+}
@@ -1,3 +1,4 @@
+use crate::error::{Element, ThermalError};
 use crate::Float;
 use matrix::Matrix;
 use simple_model::{
@@ -6,7 +7,10 @@ use simple_model::{
 
 /// A trait for defining shared behaviour between [`Surface`] and
 /// [`Fenestration`] objects
-pub trait SurfaceTrait : Clone + Send  {
+pub trait SurfaceTrait: Clone + Send {
+    /// The [`Element`] this object is, for reporting errors
+    fn element(&self) -> Element;
+
     /// Adds the front-convection state element
     fn add_front_convection_state(
         &self,
@@ -62,31 +66,51 @@ pub trait SurfaceTrait : Clone + Send  {
         ref_surface_index: usize,
     ) -> Result<(), String>;
 
+    /// Adds the state element that deploys (i.e., when it is 1) or retracts (i.e.,
+    /// when it is 0) the shading device of the surface, returning its index
+    fn add_shading_deployment_state(
+        &self,
+        state: &mut SimulationStateHeader,
+        ref_surface_index: usize,
+    ) -> Result<usize, String>;
+
+    /// Adds the state element that holds the active state of the switchable
+    /// glazing of the surface (i.e., 0 for the first one, 1 for the second one,
+    /// and so on), returning its index
+    fn add_switchable_glazing_state(
+        &self,
+        state: &mut SimulationStateHeader,
+        ref_surface_index: usize,
+    ) -> Result<usize, String>;
+
     /// Adds the temperature state elements for all the nodes in
-    /// the [`Surface`] or [`Fenestration`]
+    /// the [`Surface`] or [`Fenestration`], starting at `initial_temperature`
     fn add_node_temperature_states(
         &self,
         state: &mut SimulationStateHeader,
         ref_surface_index: usize,
         n_nodes: usize,
+        initial_temperature: Float,
     ) -> Result<(), String>;
 
-    /// Gets the index (in the simulation state) of the temperature first (i.e., front) node
-    fn first_node_temperature_index(&self) -> usize;
+    /// Gets the index (in the simulation state) of the temperature first (i.e., front) node,
+    /// failing if the node temperatures have not been added to the state
+    fn first_node_temperature_index(&self) -> Result<usize, ThermalError>;
 
-    /// Gets the index (in the simulation state) of the temperature last (i.e., back) node
-    fn last_node_temperature_index(&self) -> usize;
+    /// Gets the index (in the simulation state) of the temperature last (i.e., back) node,
+    /// failing if the node temperatures have not been added to the state
+    fn last_node_temperature_index(&self) -> Result<usize, ThermalError>;
 
     /// Gets the  temperature first (i.e., front) node
-    fn front_temperature(&self, state: &SimulationState) -> Float {
-        let i = self.first_node_temperature_index();
-        state[i]
+    fn front_temperature(&self, state: &SimulationState) -> Result<Float, ThermalError> {
+        let i = self.first_node_temperature_index()?;
+        Ok(state[i])
     }
 
     /// Gets the  temperature last (i.e., back) node
-    fn back_temperature(&self, state: &SimulationState) -> Float {
-        let i = self.last_node_temperature_index();
-        state[i]
+    fn back_temperature(&self, state: &SimulationState) -> Result<Float, ThermalError> {
+        let i = self.last_node_temperature_index()?;
+        Ok(state[i])
     }
 
     /// Retrieves a matrix with the temperatures in all the nodes
@@ -94,9 +118,9 @@ pub trait SurfaceTrait : Clone + Send  {
         &self,
         state: &SimulationState,
         temperature_matrix: &mut Matrix,
-    ) -> Result<(), String> {
-        let ini = self.first_node_temperature_index();
-        let fin = self.last_node_temperature_index() + 1;
+    ) -> Result<(), ThermalError> {
+        let ini = self.first_node_temperature_index()?;
+        let fin = self.last_node_temperature_index()? + 1;
         #[cfg(debug_assertions)]
         {
             let n_nodes = fin - ini;
@@ -114,14 +138,19 @@ pub trait SurfaceTrait : Clone + Send  {
     }
 
     /// Sets the temperatures in all the nodes, based on a matrix
-    fn set_node_temperatures(&self, state: &mut SimulationState, matrix: &Matrix) {
-        let ini = self.first_node_temperature_index();
-        let fin = self.last_node_temperature_index() + 1;
+    fn set_node_temperatures(
+        &self,
+        state: &mut SimulationState,
+        matrix: &Matrix,
+    ) -> Result<(), ThermalError> {
+        let ini = self.first_node_temperature_index()?;
+        let fin = self.last_node_temperature_index()? + 1;
 
         for (node_index, i) in (ini..fin).enumerate() {
-            let new_t = matrix.get(node_index, 0).unwrap();
+            let new_t = matrix.get(node_index, 0)?;
             state[i] = new_t;
         }
+        Ok(())
     }
 
     /// Gets the front convection coefficient     
@@ -138,10 +167,18 @@ pub trait SurfaceTrait : Clone + Send  {
     ) -> Result<(), String>;
 
     /// Sets the convective heat flow
-    fn set_front_convective_heat_flow(&self, state: &mut SimulationState, v: Float)->Result<(),String>;
+    fn set_front_convective_heat_flow(
+        &self,
+        state: &mut SimulationState,
+        v: Float,
+    ) -> Result<(), String>;
 
     /// Sets the convective heat flow
-    fn set_back_convective_heat_flow(&self, state: &mut SimulationState, v: Float)->Result<(),String>;
+    fn set_back_convective_heat_flow(
+        &self,
+        state: &mut SimulationState,
+        v: Float,
+    ) -> Result<(), String>;
 
     /// Sets the back convection coefficient
     fn set_back_convection_coefficient(
@@ -151,42 +188,101 @@ pub trait SurfaceTrait : Clone + Send  {
     ) -> Result<(), String>;
 
     /// Gets the front solar irradiance
-    fn front_solar_irradiance(&self, state: &SimulationState) -> Float;
+    fn front_solar_irradiance(&self, state: &SimulationState) -> Result<Float, ThermalError>;
 
     /// Gets the back solar irradiance
-    fn back_solar_irradiance(&self, state: &SimulationState) -> Float;
+    fn back_solar_irradiance(&self, state: &SimulationState) -> Result<Float, ThermalError>;
+
+    /// Sets the front solar irradiance
+    fn set_front_solar_irradiance(
+        &self,
+        state: &mut SimulationState,
+        v: Float,
+    ) -> Result<(), String>;
+
+    /// Sets the back solar irradiance
+    fn set_back_solar_irradiance(
+        &self,
+        state: &mut SimulationState,
+        v: Float,
+    ) -> Result<(), String>;
 
     /// Gets the front IR irradiance
-    fn front_infrared_irradiance(&self, state: &SimulationState) -> Float;
+    fn front_infrared_irradiance(&self, state: &SimulationState) -> Result<Float, ThermalError>;
 
     /// Gets the back IR irradiance
-    fn back_infrared_irradiance(&self, state: &SimulationState) -> Float;
+    fn back_infrared_irradiance(&self, state: &SimulationState) -> Result<Float, ThermalError>;
 }
 
 impl SurfaceTrait for Surface {
+    fn element(&self) -> Element {
+        Element::Surface(self.name().clone())
+    }
 
-    fn set_front_convective_heat_flow(&self, state: &mut SimulationState, v: Float)->Result<(),String>{
+    fn set_front_convective_heat_flow(
+        &self,
+        state: &mut SimulationState,
+        v: Float,
+    ) -> Result<(), String> {
         self.set_front_convective_heat_flow(state, v)
     }
 
-    fn set_back_convective_heat_flow(&self, state: &mut SimulationState, v: Float)->Result<(),String>{
+    fn set_back_convective_heat_flow(
+        &self,
+        state: &mut SimulationState,
+        v: Float,
+    ) -> Result<(), String> {
         self.set_back_convective_heat_flow(state, v)
     }
-    
-
 
-    fn front_infrared_irradiance(&self, state: &SimulationState) -> Float {
-        self.front_ir_irradiance(state).unwrap()
-    }
-    fn back_infrared_irradiance(&self, state: &SimulationState) -> Float {
-        self.back_ir_irradiance(state).unwrap()
+    fn set_front_solar_irradiance(
+        &self,
+        state: &mut SimulationState,
+        v: Float,
+    ) -> Result<(), String> {
+        self.set_front_incident_solar_irradiance(state, v)
     }
 
-    fn front_solar_irradiance(&self, state: &SimulationState) -> Float {
-        self.front_incident_solar_irradiance(state).unwrap()
-    }
-    fn back_solar_irradiance(&self, state: &SimulationState) -> Float {
-        self.back_incident_solar_irradiance(state).unwrap()
+    fn set_back_solar_irradiance(
+        &self,
+        state: &mut SimulationState,
+        v: Float,
+    ) -> Result<(), String> {
+        self.set_back_incident_solar_irradiance(state, v)
+    }
+
+    fn front_infrared_irradiance(&self, state: &SimulationState) -> Result<Float, ThermalError> {
+        self.front_ir_irradiance(state).ok_or_else(|| {
+            ThermalError::missing_data(
+                SurfaceTrait::element(self),
+                "its front infrared irradiance is not in the simulation state",
+            )
+        })
+    }
+    fn back_infrared_irradiance(&self, state: &SimulationState) -> Result<Float, ThermalError> {
+        self.back_ir_irradiance(state).ok_or_else(|| {
+            ThermalError::missing_data(
+                SurfaceTrait::element(self),
+                "its back infrared irradiance is not in the simulation state",
+            )
+        })
+    }
+
+    fn front_solar_irradiance(&self, state: &SimulationState) -> Result<Float, ThermalError> {
+        self.front_incident_solar_irradiance(state).ok_or_else(|| {
+            ThermalError::missing_data(
+                SurfaceTrait::element(self),
+                "its front solar irradiance is not in the simulation state",
+            )
+        })
+    }
+    fn back_solar_irradiance(&self, state: &SimulationState) -> Result<Float, ThermalError> {
+        self.back_incident_solar_irradiance(state).ok_or_else(|| {
+            ThermalError::missing_data(
+                SurfaceTrait::element(self),
+                "its back solar irradiance is not in the simulation state",
+            )
+        })
     }
 
     fn set_front_convection_coefficient(
@@ -211,13 +307,21 @@ impl SurfaceTrait for Surface {
         self.back_convection_coefficient(_state)
     }
 
-    fn first_node_temperature_index(&self) -> usize {
-        self.first_node_temperature_index()
-            .expect("Could not get first node index in surface")
+    fn first_node_temperature_index(&self) -> Result<usize, ThermalError> {
+        self.first_node_temperature_index().ok_or_else(|| {
+            ThermalError::missing_data(
+                SurfaceTrait::element(self),
+                "the index of its first node temperature is not in the simulation state",
+            )
+        })
     }
-    fn last_node_temperature_index(&self) -> usize {
-        self.last_node_temperature_index()
-            .expect("Could not get last node index in surface")
+    fn last_node_temperature_index(&self) -> Result<usize, ThermalError> {
+        self.last_node_temperature_index().ok_or_else(|| {
+            ThermalError::missing_data(
+                SurfaceTrait::element(self),
+                "the index of its last node temperature is not in the simulation state",
+            )
+        })
     }
 
     fn add_front_convection_state(
@@ -353,11 +457,34 @@ impl SurfaceTrait for Surface {
         }
     }
 
+    fn add_shading_deployment_state(
+        &self,
+        state: &mut SimulationStateHeader,
+        ref_surface_index: usize,
+    ) -> Result<usize, String> {
+        state.push(
+            SimulationStateElement::SurfaceShadingDeployment(ref_surface_index),
+            0.0,
+        )
+    }
+
+    fn add_switchable_glazing_state(
+        &self,
+        state: &mut SimulationStateHeader,
+        ref_surface_index: usize,
+    ) -> Result<usize, String> {
+        state.push(
+            SimulationStateElement::SurfaceGlazingState(ref_surface_index),
+            0.0,
+        )
+    }
+
     fn add_node_temperature_states(
         &self,
         state: &mut SimulationStateHeader,
         ref_surface_index: usize,
         n_nodes: usize,
+        initial_temperature: Float,
     ) -> Result<(), String> {
         if self.first_node_temperature_index().is_none() {
             // let n_nodes = d.segments.len();
@@ -365,7 +492,7 @@ impl SurfaceTrait for Surface {
             for node_index in 0..n_nodes {
                 state.push(
                     SimulationStateElement::SurfaceNodeTemperature(ref_surface_index, node_index),
-                    22.0,
+                    initial_temperature,
                 )?;
             }
             let last_node = state.len();
@@ -379,26 +506,73 @@ impl SurfaceTrait for Surface {
 }
 
 impl SurfaceTrait for Fenestration {
-    
-    fn set_front_convective_heat_flow(&self, state: &mut SimulationState, v: Float)->Result<(),String>{
+    fn element(&self) -> Element {
+        Element::Fenestration(self.name().clone())
+    }
+
+    fn set_front_convective_heat_flow(
+        &self,
+        state: &mut SimulationState,
+        v: Float,
+    ) -> Result<(), String> {
         self.set_front_convective_heat_flow(state, v)
     }
 
-    fn set_back_convective_heat_flow(&self, state: &mut SimulationState, v: Float)->Result<(),String>{
+    fn set_back_convective_heat_flow(
+        &self,
+        state: &mut SimulationState,
+        v: Float,
+    ) -> Result<(), String> {
         self.set_back_convective_heat_flow(state, v)
     }
 
-    fn front_infrared_irradiance(&self, state: &SimulationState) -> Float {
-        self.front_ir_irradiance(state).unwrap()
-    }
-    fn back_infrared_irradiance(&self, state: &SimulationState) -> Float {
-        self.back_ir_irradiance(state).unwrap()
-    }
-    fn front_solar_irradiance(&self, state: &SimulationState) -> Float {
-        self.front_incident_solar_irradiance(state).unwrap()
+    fn set_front_solar_irradiance(
+        &self,
+        state: &mut SimulationState,
+        v: Float,
+    ) -> Result<(), String> {
+        self.set_front_incident_solar_irradiance(state, v)
     }
-    fn back_solar_irradiance(&self, state: &SimulationState) -> Float {
-        self.back_incident_solar_irradiance(state).unwrap()
+
+    fn set_back_solar_irradiance(
+        &self,
+        state: &mut SimulationState,
+        v: Float,
+    ) -> Result<(), String> {
+        self.set_back_incident_solar_irradiance(state, v)
+    }
+
+    fn front_infrared_irradiance(&self, state: &SimulationState) -> Result<Float, ThermalError> {
+        self.front_ir_irradiance(state).ok_or_else(|| {
+            ThermalError::missing_data(
+                SurfaceTrait::element(self),
+                "its front infrared irradiance is not in the simulation state",
+            )
+        })
+    }
+    fn back_infrared_irradiance(&self, state: &SimulationState) -> Result<Float, ThermalError> {
+        self.back_ir_irradiance(state).ok_or_else(|| {
+            ThermalError::missing_data(
+                SurfaceTrait::element(self),
+                "its back infrared irradiance is not in the simulation state",
+            )
+        })
+    }
+    fn front_solar_irradiance(&self, state: &SimulationState) -> Result<Float, ThermalError> {
+        self.front_incident_solar_irradiance(state).ok_or_else(|| {
+            ThermalError::missing_data(
+                SurfaceTrait::element(self),
+                "its front solar irradiance is not in the simulation state",
+            )
+        })
+    }
+    fn back_solar_irradiance(&self, state: &SimulationState) -> Result<Float, ThermalError> {
+        self.back_incident_solar_irradiance(state).ok_or_else(|| {
+            ThermalError::missing_data(
+                SurfaceTrait::element(self),
+                "its back solar irradiance is not in the simulation state",
+            )
+        })
     }
     fn set_front_convection_coefficient(
         &self,
@@ -423,13 +597,21 @@ impl SurfaceTrait for Fenestration {
         self.back_convection_coefficient(state)
     }
 
-    fn first_node_temperature_index(&self) -> usize {
-        self.first_node_temperature_index()
-            .expect("Could not get first node index in surface")
+    fn first_node_temperature_index(&self) -> Result<usize, ThermalError> {
+        self.first_node_temperature_index().ok_or_else(|| {
+            ThermalError::missing_data(
+                SurfaceTrait::element(self),
+                "the index of its first node temperature is not in the simulation state",
+            )
+        })
     }
-    fn last_node_temperature_index(&self) -> usize {
-        self.last_node_temperature_index()
-            .expect("Could not get last node index in surface")
+    fn last_node_temperature_index(&self) -> Result<usize, ThermalError> {
+        self.last_node_temperature_index().ok_or_else(|| {
+            ThermalError::missing_data(
+                SurfaceTrait::element(self),
+                "the index of its last node temperature is not in the simulation state",
+            )
+        })
     }
 
     fn add_front_convection_state(
@@ -557,11 +739,34 @@ impl SurfaceTrait for Fenestration {
         Ok(())
     }
 
+    fn add_shading_deployment_state(
+        &self,
+        state: &mut SimulationStateHeader,
+        ref_surface_index: usize,
+    ) -> Result<usize, String> {
+        state.push(
+            SimulationStateElement::FenestrationShadingDeployment(ref_surface_index),
+            0.0,
+        )
+    }
+
+    fn add_switchable_glazing_state(
+        &self,
+        state: &mut SimulationStateHeader,
+        ref_surface_index: usize,
+    ) -> Result<usize, String> {
+        state.push(
+            SimulationStateElement::FenestrationGlazingState(ref_surface_index),
+            0.0,
+        )
+    }
+
     fn add_node_temperature_states(
         &self,
         state: &mut SimulationStateHeader,
         ref_surface_index: usize,
         n_nodes: usize,
+        initial_temperature: Float,
     ) -> Result<(), String> {
         if self.first_node_temperature_index().is_none() {
             let first_node = state.len();
@@ -571,7 +776,7 @@ impl SurfaceTrait for Fenestration {
                         ref_surface_index,
                         node_index,
                     ),
-                    22.0,
+                    initial_temperature,
                 )?;
             }
             let last_node = state.len();
@@ -18,8 +18,9 @@ OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
 SOFTWARE.
 */
 
+use crate::error::{Element, ThermalError};
 use crate::Float;
-use simple_model::{SimulationStateElement, SimulationStateHeader, Space};
+use simple_model::{SimulationState, SimulationStateElement, SimulationStateHeader, Space};
 use std::sync::Arc;
 
 /// A thermal representation of a [`Space`]
@@ -29,29 +30,45 @@ pub struct ThermalZone {
 
     /// volume of the zone
     volume: Float,
+
+    /// The difference between the outdoor air temperature at the height of
+    /// the zone and the one measured at the weather station (see
+    /// [`crate::site::WeatherStation::air_temperature_offset`])
+    pub air_temperature_offset: Float,
 }
 
 impl ThermalZone {
     /// This function creates a new ThermalZone from a Space.
     /// It will copy the index of the space, so it should be used
     /// by iterating the spaces in a model (so there is no mismatch).
+    ///
+    /// The temperature of the zone starts at `initial_temperature`.
     pub fn from_space(
         space: &Arc<Space>,
         state: &mut SimulationStateHeader,
         space_index: usize,
-    ) -> Result<Self, String> {
-        let volume = *space.volume().unwrap();
+        initial_temperature: Float,
+    ) -> Result<Self, ThermalError> {
+        let volume = match space.volume() {
+            Ok(v) => *v,
+            Err(_) => {
+                return Err(ThermalError::missing_data(
+                    Element::Space(space.name().clone()),
+                    "it has no volume",
+                ))
+            }
+        };
         // Add Space Temperature state
         let state_index = state.push(
-            // start, by default, at 22.0 C
             SimulationStateElement::SpaceDryBulbTemperature(space_index),
-            22.0,
+            initial_temperature,
         )?;
         space.set_dry_bulb_temperature_index(state_index)?;
 
         Ok(ThermalZone {
             reference_space: Arc::clone(space),
             volume,
+            air_temperature_offset: 0.0,
         })
     }
 
@@ -63,4 +80,13 @@ impl ThermalZone {
 
         self.volume * air_density * air_specific_heat / 1.
     }
+
+    /// Retrieves the number of air changes per hour supplied to the
+    /// ThermalZone by its ventilation
+    pub fn air_changes_per_hour(&self, state: &SimulationState) -> Float {
+        match self.reference_space.ventilation_volume(state) {
+            Some(v) => v * 3600. / self.volume,
+            None => 0.0,
+        }
+    }
 }
@@ -12,6 +12,13 @@ do
         echo Running sim on $dir
         energyplus -w $EPW -x -r $idf
     done
+
+    # Macro files (e.g., including ../convection_base.idf) are expanded first
+    for imf in $(ls | grep .imf)
+    do
+        echo Running sim on $dir
+        energyplus -w $EPW -x -m -r $imf
+    done
     
     cd ..
 done
@@ -1,16 +1,26 @@
 use geometry3d::Vector3D;
-use heat::convection::ConvectionParams;
+use heat::convection::{
+    ConvectionParams, ExteriorConvectionAlgorithm, InteriorConvectionAlgorithm, Roughness,
+};
 use heat::surface::is_windward;
 use heat::Float;
 use validate::*;
 
 fn get_validator(expected: Vec<f64>, found: Vec<f64>) -> Box<dyn Validate> {
+    get_algorithm_validator("EnergyPlus (TARP)", expected, found)
+}
+
+fn get_algorithm_validator(
+    expected_legend: &'static str,
+    expected: Vec<f64>,
+    found: Vec<f64>,
+) -> Box<dyn Validate> {
     Box::new(SeriesValidator {
         x_label: Some("time step"),
         y_label: Some("Convection Coefficient"),
         y_units: Some("W/m2.K"),
 
-        expected_legend: Some("EnergyPlus (TARP)"),
+        expected_legend: Some(expected_legend),
         found_legend: Some("SIMPLE"),
         expected,
         found,
@@ -35,6 +45,26 @@ fn calc_convection(
     area: Float,
     perimeter: Float,
     normal: Vector3D,
+) -> (Vec<Float>, Vec<Float>, Vec<Float>, Vec<Float>) {
+    calc_algorithm_convection(
+        dir,
+        HEIGHT,
+        area,
+        perimeter,
+        normal,
+        InteriorConvectionAlgorithm::Tarp,
+        ExteriorConvectionAlgorithm::Tarp,
+    )
+}
+
+fn calc_algorithm_convection(
+    dir: &'static str,
+    height: Float,
+    area: Float,
+    perimeter: Float,
+    normal: Vector3D,
+    interior: InteriorConvectionAlgorithm,
+    exterior: ExteriorConvectionAlgorithm,
 ) -> (Vec<Float>, Vec<Float>, Vec<Float>, Vec<Float>) {
     let path_string = format!("./tests/{}/eplusout.csv", dir);
     let path = path_string.as_str();
@@ -54,6 +84,7 @@ fn calc_convection(
     let zone_air_temp = &cols[11]; // 12	INTERIOR SPACE:Zone Mean Air Temperature [C](TimeStep)
 
     let cos_tilt = normal * Vector3D::new(0., 0., 1.);
+    let hydraulic_diameter = 4. * area / perimeter;
     let n = outdoor_temp.len();
     let mut found_hs_in = Vec::with_capacity(n);
     let mut found_hs_out = Vec::with_capacity(n);
@@ -63,7 +94,7 @@ fn calc_convection(
             air_speed: surface_wind_speed[i], //0.,
             rad_temperature: 0.0,             // not used for convection purposes
             surface_temperature: inside_surface_temp[i],
-            roughness_index: 1,
+            roughness: Roughness::Rough,
             cos_surface_tilt: cos_tilt,
         };
         let env_out = ConvectionParams {
@@ -71,14 +102,20 @@ fn calc_convection(
             air_speed: surface_wind_speed[i],
             rad_temperature: 0.0, // not used for convection purposes
             surface_temperature: outside_surface_temp[i],
-            roughness_index: 1,
+            roughness: Roughness::Rough,
             cos_surface_tilt: -cos_tilt,
         };
 
         let windward = is_windward(site_wind_direction[i].to_radians(), cos_tilt, normal);
 
-        found_hs_in.push(env_in.get_tarp_natural_convection_coefficient());
-        found_hs_out.push(env_out.get_tarp_convection_coefficient(area, perimeter, windward))
+        found_hs_in.push(env_in.get_interior_convection_coefficient(
+            interior,
+            height,
+            hydraulic_diameter,
+            0.0,
+        ));
+        found_hs_out
+            .push(env_out.get_exterior_convection_coefficient(exterior, area, perimeter, windward))
     }
 
     (
@@ -89,8 +126,10 @@ fn calc_convection(
     )
 }
 
-const AREA: Float = 20. * 3.;
-const PERIMETER: Float = (20. + 3.) * 2.; //30.9838667697;
+/// Height of the walls in the IDF files (m)
+const HEIGHT: Float = 3.;
+const AREA: Float = 20. * HEIGHT;
+const PERIMETER: Float = (20. + HEIGHT) * 2.; //30.9838667697;
 fn vertical(validations: &mut Validator) {
     /// Heat Transfer Coefficients calculated in SIMPLE, compared to those calculated by the TARP model in EnergyPlus
     #[valid(Vertical Wall - Natural (i.e., Interior) Convection Coefficient )]
@@ -178,6 +217,73 @@ fn horizontal(validations: &mut Validator) {
     validations.push(forced());
 }
 
+fn algorithms(validations: &mut Validator) {
+    /// Interior Heat Transfer Coefficients calculated in SIMPLE, compared to those calculated by the ASHRAE Simple model in EnergyPlus
+    #[valid(Vertical Wall - ASHRAE Simple Interior Convection Coefficient )]
+    fn ashrae_simple() -> Box<dyn Validate> {
+        let (expected_in, found_in, ..) = calc_algorithm_convection(
+            "convection_ashrae_simple",
+            HEIGHT,
+            AREA,
+            PERIMETER,
+            Vector3D::new(0., -1., 0.), // South
+            InteriorConvectionAlgorithm::AshraeSimple,
+            ExteriorConvectionAlgorithm::Tarp,
+        );
+        get_algorithm_validator("EnergyPlus (ASHRAE Simple)", expected_in, found_in)
+    }
+
+    /// Interior Heat Transfer Coefficients calculated in SIMPLE, compared to those calculated by the Adaptive Convection Algorithm in EnergyPlus
+    #[valid(Vertical Wall - Adaptive Interior Convection Coefficient )]
+    fn adaptive() -> Box<dyn Validate> {
+        let (expected_in, found_in, ..) = calc_algorithm_convection(
+            "convection_adaptive",
+            HEIGHT,
+            AREA,
+            PERIMETER,
+            Vector3D::new(0., -1., 0.), // South
+            InteriorConvectionAlgorithm::Adaptive,
+            ExteriorConvectionAlgorithm::Tarp,
+        );
+        get_algorithm_validator("EnergyPlus (Adaptive)", expected_in, found_in)
+    }
+
+    /// Exterior Heat Transfer Coefficients calculated in SIMPLE, compared to those calculated by the DOE-2 model in EnergyPlus
+    #[valid(Vertical Wall - DOE-2 Exterior Convection Coefficient )]
+    fn doe2() -> Box<dyn Validate> {
+        let (.., expected_out, found_out) = calc_algorithm_convection(
+            "convection_doe2",
+            HEIGHT,
+            AREA,
+            PERIMETER,
+            Vector3D::new(0., -1., 0.), // South
+            InteriorConvectionAlgorithm::Tarp,
+            ExteriorConvectionAlgorithm::Doe2,
+        );
+        get_algorithm_validator("EnergyPlus (DOE-2)", expected_out, found_out)
+    }
+
+    /// Exterior Heat Transfer Coefficients calculated in SIMPLE, compared to those calculated by the MoWiTT model in EnergyPlus
+    #[valid(Vertical Wall - MoWiTT Exterior Convection Coefficient )]
+    fn mowitt() -> Box<dyn Validate> {
+        let (.., expected_out, found_out) = calc_algorithm_convection(
+            "convection_mowitt",
+            HEIGHT,
+            AREA,
+            PERIMETER,
+            Vector3D::new(0., -1., 0.), // South
+            InteriorConvectionAlgorithm::Tarp,
+            ExteriorConvectionAlgorithm::MoWiTT,
+        );
+        get_algorithm_validator("EnergyPlus (MoWiTT)", expected_out, found_out)
+    }
+
+    validations.push(ashrae_simple());
+    validations.push(adaptive());
+    validations.push(doe2());
+    validations.push(mowitt());
+}
+
 #[test]
 fn validate() {
     // cargo test --package heat --test validate_convection -- validate --exact --nocapture
@@ -195,6 +301,7 @@ fn validate() {
     vertical(&mut validations);
     tilted(&mut validations);
     horizontal(&mut validations);
+    algorithms(&mut validations);
 
     validations.validate().unwrap();
 }
@@ -1,5 +1,7 @@
 use communication_protocols::SimulationModel;
-use heat::model::ThermalModel;
+use heat::cavity::CavitySide;
+use heat::convection::ConvectionCoefficient;
+use heat::model::{ThermalModel, ThermalModelOptions};
 use heat::Float;
 
 use calendar::Date;
@@ -60,7 +62,7 @@ struct SingleZoneTestModel {
 }
 
 impl SingleZoneTestModel {
-    fn get_closed_solution(&self) -> Box<impl Fn(Float) -> Float> {        
+    fn get_closed_solution(&self) -> Box<impl Fn(Float) -> Float> {
         // heat balance in the form
         // of C*dT/dt = A - B*T
         let air = heat::gas::AIR;
@@ -116,8 +118,14 @@ fn march_with_window() -> (Vec<Float>, Vec<Float>) {
     // Finished model the SimpleModel
     let n: usize = 6;
     let main_dt = 60. * 60. / n as Float;
-    let mut thermal_model =
-        ThermalModel::new(&META_OPTIONS, (), &simple_model, &mut state_header, n).unwrap();
+    let mut thermal_model = ThermalModel::new(
+        &META_OPTIONS,
+        ThermalModelOptions::default(),
+        &simple_model,
+        &mut state_header,
+        n,
+    )
+    .unwrap();
     let mut memory = thermal_model.allocate_memory().unwrap();
 
     let mut state = state_header.take_values().unwrap();
@@ -128,10 +136,11 @@ fn march_with_window() -> (Vec<Float>, Vec<Float>) {
     // START TESTING.
     let hs_front = 10.;
     let hs_back = 10.;
-    thermal_model.surfaces[0].front_hs = Some(hs_front);
-    thermal_model.surfaces[0].back_hs = Some(hs_back);
+    thermal_model.surfaces[0].front_hs = Some(ConvectionCoefficient::Fixed(hs_front));
+    thermal_model.surfaces[0].back_hs = Some(ConvectionCoefficient::Fixed(hs_back));
 
-    let r = thermal_model.surfaces[0].discretization.r_value() + 1. / hs_front + 1. / hs_back;
+    let r =
+        thermal_model.surfaces[0].discretization.r_value().unwrap() + 1. / hs_front + 1. / hs_back;
 
     // Initial T of the zone
     let t_start = thermal_model.zones[0]
@@ -208,18 +217,25 @@ fn very_simple_march() -> (Vec<Float>, Vec<Float>) {
 
     let n: usize = 60;
     let main_dt = 60. * 60. / n as Float;
-    let mut thermal_model =
-        ThermalModel::new(&META_OPTIONS, (), &simple_model, &mut state_header, n).unwrap();
+    let mut thermal_model = ThermalModel::new(
+        &META_OPTIONS,
+        ThermalModelOptions::default(),
+        &simple_model,
+        &mut state_header,
+        n,
+    )
+    .unwrap();
     let mut memory = thermal_model.allocate_memory().unwrap();
 
     let mut state = state_header.take_values().unwrap();
 
     let hs_front = 10.;
     let hs_back = 10.;
-    thermal_model.surfaces[0].front_hs = Some(hs_front);
-    thermal_model.surfaces[0].back_hs = Some(hs_back);
+    thermal_model.surfaces[0].front_hs = Some(ConvectionCoefficient::Fixed(hs_front));
+    thermal_model.surfaces[0].back_hs = Some(ConvectionCoefficient::Fixed(hs_back));
 
-    let r = thermal_model.surfaces[0].discretization.r_value() + 1. / hs_front + 1. / hs_back;
+    let r =
+        thermal_model.surfaces[0].discretization.r_value().unwrap() + 1. / hs_front + 1. / hs_back;
 
     // Initial T of the zone
     let t_start = thermal_model.zones[0]
@@ -300,8 +316,14 @@ fn march_with_window_and_luminaire() -> (Vec<Float>, Vec<Float>) {
 
     let n: usize = 20;
     let main_dt = 60. * 60. / n as Float;
-    let mut thermal_model =
-        ThermalModel::new(&META_OPTIONS, (), &simple_model, &mut state_header, n).unwrap();
+    let mut thermal_model = ThermalModel::new(
+        &META_OPTIONS,
+        ThermalModelOptions::default(),
+        &simple_model,
+        &mut state_header,
+        n,
+    )
+    .unwrap();
     let mut memory = thermal_model.allocate_memory().unwrap();
 
     let mut state = state_header.take_values().unwrap();
@@ -316,9 +338,10 @@ fn march_with_window_and_luminaire() -> (Vec<Float>, Vec<Float>) {
 
     let hs_front = 10.;
     let hs_back = 10.;
-    thermal_model.surfaces[0].front_hs = Some(hs_front);
-    thermal_model.surfaces[0].back_hs = Some(hs_back);
-    let r = thermal_model.surfaces[0].discretization.r_value() + 1. / hs_front + 1. / hs_back;
+    thermal_model.surfaces[0].front_hs = Some(ConvectionCoefficient::Fixed(hs_front));
+    thermal_model.surfaces[0].back_hs = Some(ConvectionCoefficient::Fixed(hs_back));
+    let r =
+        thermal_model.surfaces[0].discretization.r_value().unwrap() + 1. / hs_front + 1. / hs_back;
 
     // Initial T of the zone
     let t_start = 22.;
@@ -405,8 +428,14 @@ fn march_with_window_and_heater() -> (Vec<Float>, Vec<Float>) {
 
     let n: usize = 20;
     let main_dt = 60. * 60. / n as Float;
-    let mut thermal_model =
-        ThermalModel::new(&META_OPTIONS, (), &simple_model, &mut state_header, n).unwrap();
+    let mut thermal_model = ThermalModel::new(
+        &META_OPTIONS,
+        ThermalModelOptions::default(),
+        &simple_model,
+        &mut state_header,
+        n,
+    )
+    .unwrap();
     let mut memory = thermal_model.allocate_memory().unwrap();
     let mut state = state_header.take_values().unwrap();
     // MAP THE STATE
@@ -423,10 +452,11 @@ fn march_with_window_and_heater() -> (Vec<Float>, Vec<Float>) {
 
     let hs_front = 10.;
     let hs_back = 10.;
-    thermal_model.surfaces[0].front_hs = Some(hs_front);
-    thermal_model.surfaces[0].back_hs = Some(hs_back);
+    thermal_model.surfaces[0].front_hs = Some(ConvectionCoefficient::Fixed(hs_front));
+    thermal_model.surfaces[0].back_hs = Some(ConvectionCoefficient::Fixed(hs_back));
 
-    let r = thermal_model.surfaces[0].discretization.r_value() + 1. / hs_front + 1. / hs_back;
+    let r =
+        thermal_model.surfaces[0].discretization.r_value().unwrap() + 1. / hs_front + 1. / hs_back;
 
     // Initial T of the zone
     let t_start = thermal_model.zones[0]
@@ -512,8 +542,14 @@ fn march_with_window_heater_and_infiltration() -> (Vec<Float>, Vec<Float>) {
 
     let n: usize = 20;
     let main_dt = 60. * 60. / n as Float;
-    let mut thermal_model =
-        ThermalModel::new(&META_OPTIONS, (), &simple_model, &mut state_header, n).unwrap();
+    let mut thermal_model = ThermalModel::new(
+        &META_OPTIONS,
+        ThermalModelOptions::default(),
+        &simple_model,
+        &mut state_header,
+        n,
+    )
+    .unwrap();
     let mut memory = thermal_model.allocate_memory().unwrap();
     // Set infiltration
     let inf_vol_index = state_header
@@ -549,10 +585,11 @@ fn march_with_window_heater_and_infiltration() -> (Vec<Float>, Vec<Float>) {
 
     let hs_front = 10.;
     let hs_back = 10.;
-    thermal_model.surfaces[0].front_hs = Some(hs_front);
-    thermal_model.surfaces[0].back_hs = Some(hs_back);
+    thermal_model.surfaces[0].front_hs = Some(ConvectionCoefficient::Fixed(hs_front));
+    thermal_model.surfaces[0].back_hs = Some(ConvectionCoefficient::Fixed(hs_back));
 
-    let r = thermal_model.surfaces[0].discretization.r_value() + 1. / hs_front + 1. / hs_back;
+    let r =
+        thermal_model.surfaces[0].discretization.r_value().unwrap() + 1. / hs_front + 1. / hs_back;
 
     // Initial T of the zone
     let t_start = thermal_model.zones[0]
@@ -623,8 +660,14 @@ fn march_model(
 
     let n: usize = 20;
     // let main_dt = 60. * 60. / n as Float;
-    let mut thermal_model =
-        ThermalModel::new(&META_OPTIONS, (), &simple_model, &mut state_header, n).unwrap();
+    let mut thermal_model = ThermalModel::new(
+        &META_OPTIONS,
+        ThermalModelOptions::default(),
+        &simple_model,
+        &mut state_header,
+        n,
+    )
+    .unwrap();
     let mut memory = thermal_model.allocate_memory().unwrap();
     // in model like these—i.e., a single surface—EnergyPlus assumes Zero IR radation
     thermal_model.surfaces[0].back_emissivity = 0.0;
@@ -993,126 +1036,147 @@ fn nomass(validations: &mut Validator) {
     validations.push(wall4());
 }
 
-// fn march_trombe_wall(
-//     dir: &'static str,
-//     emissivity: Float,
-//     solar_abs: Float,
-//     construction: Vec<TestMat>,
-// ) -> (Vec<Float>, Vec<Float>) {
-//     let surface_area = 20. * 3.;
-//     let zone_volume = 600.;
-
-//     let (simple_model, mut state_header) = get_single_zone_test_building(
-//         // &mut state,
-//         &SingleZoneTestBuildingOptions {
-//             zone_volume,
-//             surface_area,
-//             construction,
-//             emissivity,
-//             solar_absorbtance: solar_abs,
-//             ..Default::default()
-//         },
-//     );
-
-//     // Finished model the SimpleModel
-
-//     let n: usize = 20;
-//     // let main_dt = 60. * 60. / n as Float;
-//     let thermal_model = ThermalModel::new(&simple_model, &mut state_header, n).unwrap();
-
-//     let mut state = state_header.take_values().unwrap();
-
-//     let path_string = format!("./tests/{}/eplusout.csv", dir);
-//     let path = path_string.as_str();
-//     let cols = validate::from_csv(path, &[3, 15, 17, 18, 24]);
-//     let incident_solar_radiation = &cols[0]; //3
-//     let indoor_thermal_heat_gain = &cols[1]; //15
-//     let outdoor_temp = &cols[2]; //17
-//     let outdoor_thermal_heat_gain = &cols[3]; //18
-//     let exp_zone_air_temp = &cols[4]; //24
-
-//     // Set initial temperature
-//     simple_model.spaces[0].set_dry_bulb_temperature(&mut state, exp_zone_air_temp[0]);
-
-//     let mut date = Date {
-//         month: 1,
-//         day: 1,
-//         hour: 0.0,
-//     };
-//     let n = outdoor_temp.len();
-//     let mut exp = Vec::with_capacity(n);
-//     let mut found = Vec::with_capacity(n);
-//     for i in 0..n {
-//         // Get zone's temp
-//         let found_temp = simple_model.spaces[0].dry_bulb_temperature(&state).unwrap();
-//         let exp_temp = exp_zone_air_temp[i];
-//         if i > 000 {
-//             // skip warmup
-//             exp.push(exp_temp);
-//             found.push(found_temp);
-//         }
-
-//         // Set outdoor temp
-//         let mut weather = SyntheticWeather::default();
-//         weather.dry_bulb_temperature = Box::new(ScheduleConstant::new(outdoor_temp[i]));
-//            weather.wind_direction = Box::new(ScheduleConstant::new(0.0));
-
-//         let surface = &simple_model.surfaces[0];
-
-//         // Set Solar Radiation
-//         surface.set_back_incident_solar_irradiance(&mut state, incident_solar_radiation[i]);
-
-//         // Set Long Wave radiation
-//         if emissivity > 1e-3 {
-//             let ts = surface.last_node_temperature(&state).unwrap();
-//             let v = outdoor_thermal_heat_gain[i] / surface_area / emissivity
-//                 + heat::SIGMA * (ts + 273.15).powi(4);
-//             surface.set_back_ir_irradiance(&mut state, v);
-
-//             let ts = surface.first_node_temperature(&state).unwrap();
-//             let v = indoor_thermal_heat_gain[i] / surface_area / emissivity
-//                 + heat::SIGMA * (ts + 273.15).powi(4);
-//             surface.set_front_ir_irradiance(&mut state, v);
-//         }
-
-//         // March
-//         thermal_model
-//             .march(date, &weather, &simple_model, &mut state)
-//             .unwrap();
-
-//         // Advance
-//         date.add_hours(1. / n as Float);
-//     }
-//     (exp, found)
-// }
-
-// fn trombe_wall(validations: &mut Validator) {
-//     // No Mass, With solar Radiation and Long Wave
-//     let (expected, found) = march_trombe_wall(
-//         "trombe_wall_full",
-//         0.9,
-//         0.08,
-//         vec![
-//             TestMat::Concrete(0.2),
-//             TestMat::Air(0.05),
-//             TestMat::Glass(0.03, 0.82),
-//         ],
-//     );
-//     let v = validate::SeriesValidator {
-//         title: "Trombe Wall, with Solar Radiation and Long Wave Radiation",
-//         x_label: Some("time step"),
-//         y_label: Some("Zone Temperature"),
-//         y_units: Some("C"),
-//         found_name: "Simple",
-//         expected_name: "EnergyPlus",
-
-//         expected,
-//         found,
-
-//         ..validate::SeriesValidator::default()
-//     };
-//     validations.push(Box::new(v));
-// }
+/// Finds the position of each of the `columns` in the header of an EnergyPlus
+/// output, so they can be read with [`validate::from_csv`]
+fn csv_columns(path: &str, columns: &[&str]) -> Vec<usize> {
+    let content = std::fs::read_to_string(path).unwrap_or_else(|e| {
+        panic!("Could not read '{path}' (run tests/sim_eplus.sh to generate it): {e}")
+    });
+    let header: Vec<&str> = content
+        .lines()
+        .next()
+        .unwrap_or_default()
+        .split(',')
+        .map(|c| c.trim())
+        .collect();
+    columns
+        .iter()
+        .map(|name| {
+            header
+                .iter()
+                .position(|h| h == name)
+                .unwrap_or_else(|| panic!("Could not find column '{name}' in '{path}'"))
+        })
+        .collect()
+}
+
+/// Marches the model in `tests/trombe_wall_full`, which replicates its `in.idf`: a
+/// glazing, a 15cm air gap and a 20cm concrete wall in front of an 'Interior Space'.
+/// The air gap has an upper and a lower vent of 0.5m2 each towards the 'Interior
+/// Space', which EnergyPlus models as a 'Mid Space' zone connected to the interior
+/// by an AirflowNetwork. Returns the expected and found air temperatures of the
+/// 'Interior Space'.
+///
+/// In EnergyPlus, the window covers all but 0.4596m2 of the exterior wall. Here,
+/// the glazing covers all of it.
+fn march_trombe_wall() -> (Vec<Float>, Vec<Float>) {
+    const DIR: &str = "trombe_wall_full";
+    const VENT_AREA: Float = 0.5;
+    const DISCHARGE_COEFFICIENT: Float = 0.6;
+    let (simple_model, mut state_header) =
+        SimpleModel::from_file(format!("./tests/{DIR}/trombe.spl")).unwrap();
+
+    let n: usize = 20;
+    let mut thermal_model = ThermalModel::new(
+        &META_OPTIONS,
+        ThermalModelOptions::default(),
+        &simple_model,
+        &mut state_header,
+        n,
+    )
+    .unwrap();
+    // Upper and lower vents, from the air gap (layer 1) to the Interior Space (at the back)
+    thermal_model.surfaces[0]
+        .add_vents(1, VENT_AREA, DISCHARGE_COEFFICIENT, CavitySide::Back)
+        .unwrap();
+    let mut memory = thermal_model.allocate_memory().unwrap();
+    let mut state = state_header.take_values().unwrap();
+
+    let wall = &simple_model.surfaces[0];
+    let space = &simple_model.spaces[0];
+
+    let path = format!("./tests/{DIR}/eplusout.csv");
+    let columns = csv_columns(
+        &path,
+        &[
+            "Environment:Site Wind Speed [m/s](TimeStep)",
+            "Environment:Site Wind Direction [deg](TimeStep)",
+            "TROMBE WINDOW OUTSIDE:Surface Outside Face Outdoor Air Drybulb Temperature [C](TimeStep)",
+            "TROMBE WINDOW OUTSIDE:Surface Outside Face Incident Solar Radiation Rate per Area [W/m2](TimeStep)",
+            "TROMBE WINDOW OUTSIDE:Surface Outside Face Net Thermal Radiation Heat Gain Rate [W](TimeStep)",
+            "INTERIOR SPACE:Zone Mean Air Temperature [C](TimeStep)",
+        ],
+    );
+    let cols = validate::from_csv(&path, &columns);
+    let site_wind_speed = &cols[0];
+    let site_wind_direction = &cols[1];
+    let outdoor_temp = &cols[2];
+    let incident_solar = &cols[3];
+    let thermal_heat_gain = &cols[4];
+    let exp_temps = &cols[5];
+
+    // EnergyPlus reports the long wave radiation of its 19.98m x 2.98m window
+    let window_area = 19.98 * 2.98;
+
+    // Set initial temperature
+    space
+        .set_dry_bulb_temperature(&mut state, exp_temps[0])
+        .unwrap();
+
+    let mut date = Date {
+        month: 1,
+        day: 1,
+        hour: 0.0,
+    };
+    let n_rows = outdoor_temp.len();
+    let mut exp = Vec::with_capacity(n_rows);
+    let mut found = Vec::with_capacity(n_rows);
+    for i in 0..n_rows {
+        // Get zone's temp
+        let found_temp = space.dry_bulb_temperature(&state).unwrap();
+        if i > 5000 {
+            // skip warmup
+            exp.push(exp_temps[i]);
+            found.push(found_temp);
+        }
+
+        // Set outdoor temp
+        let mut weather = SyntheticWeather::default();
+        weather.dry_bulb_temperature = Box::new(ScheduleConstant::new(outdoor_temp[i]));
+        weather.wind_direction = Box::new(ScheduleConstant::new(site_wind_direction[i]));
+        weather.wind_speed = Box::new(ScheduleConstant::new(site_wind_speed[i]));
+
+        // Set Solar Radiation. The glazing transmits it to the concrete, through the air gap.
+        wall.set_front_incident_solar_irradiance(&mut state, incident_solar[i])
+            .unwrap();
+
+        // Set Long Wave radiation, as received by the window
+        let ts = wall.first_node_temperature(&state).unwrap();
+        let v = thermal_heat_gain[i] / window_area / 0.84 + heat::SIGMA * (ts + 273.15).powi(4);
+        wall.set_front_ir_irradiance(&mut state, v).unwrap();
+
+        // March
+        thermal_model
+            .march(date, &weather, &simple_model, &mut state, &mut memory)
+            .unwrap();
+
+        // Advance
+        date.add_hours(1. / n as Float);
+    }
+    (exp, found)
+}
+
+fn trombe_wall(validations: &mut Validator) {
+    const EXPECTED_LEGEND: &'static str = "EnergyPlus";
+
+    #[valid(Vented Trombe Wall - Interior Space)]
+    fn interior_space() -> Box<dyn Validate> {
+        let (expected, found) = march_trombe_wall();
+        get_validator(expected, found, EXPECTED_LEGEND)
+    }
+
+    validations.push(interior_space());
+}
 
 #[test]
 fn validate() {
@@ -1135,7 +1199,7 @@ fn validate() {
     tilted(&mut validations);
     horizontal(&mut validations);
 
-    // trombe_wall(&mut validations);
+    trombe_wall(&mut validations);
     validations.validate().unwrap();
 }
 //